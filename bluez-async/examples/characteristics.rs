@@ -16,6 +16,9 @@ async fn main() -> Result<(), eyre::Report> {
     let devices = session.get_devices().await?;
     println!("Devices:");
     for device in devices {
+        if !device.services_resolved {
+            continue;
+        }
         let services = session.get_services(&device.id).await?;
         if !services.is_empty() {
             println!("{}: {}", device.mac_address, device.id);
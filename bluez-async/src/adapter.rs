@@ -1,9 +1,16 @@
+use bluez_generated::OrgBluezAdapter1Properties;
 use dbus::Path;
+use serde_derive::{Deserialize, Serialize};
 use std::fmt::{self, Display, Formatter};
+use std::time::Duration;
+use uuid::Uuid;
+
+use crate::{AddressType, BluetoothError, DeviceId, MacAddress};
 
 /// Opaque identifier for a Bluetooth adapter on the system.
-#[derive(Clone, Debug, Eq, Hash, Ord, PartialEq, PartialOrd)]
+#[derive(Clone, Debug, Deserialize, Eq, Hash, Ord, PartialEq, PartialOrd, Serialize)]
 pub struct AdapterId {
+    #[serde(with = "crate::serde_path")]
     pub(crate) object_path: Path<'static>,
 }
 
@@ -13,6 +20,35 @@ impl AdapterId {
             object_path: object_path.to_owned().into(),
         }
     }
+
+    /// Get the D-Bus object path of the adapter, for use with the `dbus` crate directly.
+    pub fn path(&self) -> &Path<'static> {
+        &self.object_path
+    }
+
+    /// Get the ID which the device with the given MAC address has (or would have) on this adapter.
+    ///
+    /// Note that BlueZ only knows about a device on a particular adapter once that adapter has
+    /// discovered it or connected to it, so the ID may not refer to an existing device.
+    pub fn device(&self, mac_address: &MacAddress) -> DeviceId {
+        DeviceId::new(&format!(
+            "{}/dev_{}",
+            self.object_path,
+            mac_address.0.replace(':', "_")
+        ))
+    }
+}
+
+impl From<AdapterId> for Path<'static> {
+    fn from(id: AdapterId) -> Self {
+        id.object_path
+    }
+}
+
+impl AsRef<Path<'static>> for AdapterId {
+    fn as_ref(&self) -> &Path<'static> {
+        &self.object_path
+    }
 }
 
 impl Display for AdapterId {
@@ -27,3 +63,225 @@ impl Display for AdapterId {
         )
     }
 }
+
+/// Information about a Bluetooth adapter on the system.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct AdapterInfo {
+    /// An opaque identifier for the adapter. This can be used to perform operations on it.
+    pub id: AdapterId,
+    /// The MAC address of the adapter.
+    pub mac_address: MacAddress,
+    /// The type of MAC address the adapter uses, if available. Older versions of BlueZ don't
+    /// provide this.
+    pub address_type: Option<AddressType>,
+    /// The Bluetooth system name of the adapter, if available.
+    pub name: Option<String>,
+    /// The alias of the adapter, which defaults to the system name, if available.
+    pub alias: Option<String>,
+    /// Whether the adapter is currently powered on.
+    pub powered: bool,
+    /// Whether the adapter is currently scanning for devices.
+    pub discovering: bool,
+    /// Whether the adapter is currently discoverable by other devices, if available.
+    pub discoverable: Option<bool>,
+    /// How long the adapter stays discoverable after being made discoverable, or `None` if it
+    /// stays discoverable indefinitely. When this expires BlueZ emits
+    /// [`AdapterEvent::Discoverable`](crate::AdapterEvent::Discoverable) with `false`, so a
+    /// countdown can be shown from the time the adapter became discoverable.
+    pub discoverable_timeout: Option<Duration>,
+    /// Whether the adapter currently accepts pairing requests, if available.
+    pub pairable: Option<bool>,
+    /// How long the adapter stays pairable after being made pairable, or `None` if it stays
+    /// pairable indefinitely.
+    pub pairable_timeout: Option<Duration>,
+    /// The UUIDs of the profiles which the adapter supports, such as A2DP or HID host.
+    pub supported_uuids: Vec<Uuid>,
+}
+
+impl AdapterInfo {
+    /// Returns the static random address of the adapter, if it uses one rather than a public
+    /// address. This is the identity address which other devices will see when LE Privacy is
+    /// enabled.
+    pub fn static_random_address(&self) -> Option<&MacAddress> {
+        if self.address_type == Some(AddressType::Random) && self.mac_address.is_static_random() {
+            Some(&self.mac_address)
+        } else {
+            None
+        }
+    }
+
+    pub(crate) fn from_properties(
+        id: AdapterId,
+        adapter_properties: OrgBluezAdapter1Properties,
+    ) -> Result<AdapterInfo, BluetoothError> {
+        let mac_address = adapter_properties
+            .address()
+            .ok_or_else(|| BluetoothError::RequiredPropertyMissing("Address".to_string()))?;
+        let address_type = adapter_properties.address_type().and_then(|address_type| {
+            address_type
+                .parse()
+                .map_err(|err| log::warn!("Error parsing adapter address type: {}", err))
+                .ok()
+        });
+        let supported_uuids = adapter_properties
+            .uuids()
+            .map(|uuids| {
+                uuids
+                    .iter()
+                    .filter_map(|uuid| {
+                        Uuid::parse_str(uuid)
+                            .map_err(|err| {
+                                log::warn!("Error parsing adapter UUID: {}", err);
+                                err
+                            })
+                            .ok()
+                    })
+                    .collect()
+            })
+            .unwrap_or_default();
+
+        Ok(AdapterInfo {
+            id,
+            mac_address: MacAddress(mac_address.to_owned()),
+            address_type,
+            name: adapter_properties.name().cloned(),
+            alias: adapter_properties.alias().cloned(),
+            powered: adapter_properties
+                .powered()
+                .ok_or_else(|| BluetoothError::RequiredPropertyMissing("Powered".to_string()))?,
+            discovering: adapter_properties.discovering().ok_or_else(|| {
+                BluetoothError::RequiredPropertyMissing("Discovering".to_string())
+            })?,
+            discoverable: adapter_properties.discoverable(),
+            discoverable_timeout: timeout(adapter_properties.discoverable_timeout()),
+            pairable: adapter_properties.pairable(),
+            pairable_timeout: timeout(adapter_properties.pairable_timeout()),
+            supported_uuids,
+        })
+    }
+}
+
+/// Convert a BlueZ timeout in seconds to a `Duration`, where 0 means no timeout.
+fn timeout(seconds: Option<u32>) -> Option<Duration> {
+    match seconds {
+        None | Some(0) => None,
+        Some(seconds) => Some(Duration::from_secs(seconds.into())),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::uuid_from_u16;
+    use dbus::arg::{PropMap, Variant};
+
+    #[test]
+    fn adapter_device() {
+        let adapter_id = AdapterId::new("/org/bluez/hci1");
+        let mac_address: MacAddress = "11:22:33:44:55:66".parse().unwrap();
+        let device_id = adapter_id.device(&mac_address);
+        assert_eq!(
+            device_id,
+            DeviceId::new("/org/bluez/hci1/dev_11_22_33_44_55_66")
+        );
+        assert_eq!(device_id.adapter(), adapter_id);
+    }
+
+    #[test]
+    fn adapter_info() {
+        let id = AdapterId::new("/org/bluez/hci0");
+        let mut adapter_properties = PropMap::new();
+        adapter_properties.insert(
+            "Address".to_string(),
+            Variant(Box::new("00:11:22:33:44:55".to_string())),
+        );
+        adapter_properties.insert(
+            "AddressType".to_string(),
+            Variant(Box::new("public".to_string())),
+        );
+        adapter_properties.insert("Name".to_string(), Variant(Box::new("host".to_string())));
+        adapter_properties.insert("Alias".to_string(), Variant(Box::new("host".to_string())));
+        adapter_properties.insert("Powered".to_string(), Variant(Box::new(true)));
+        adapter_properties.insert("Discovering".to_string(), Variant(Box::new(false)));
+        adapter_properties.insert("Discoverable".to_string(), Variant(Box::new(true)));
+        adapter_properties.insert("DiscoverableTimeout".to_string(), Variant(Box::new(180u32)));
+        adapter_properties.insert("Pairable".to_string(), Variant(Box::new(false)));
+        adapter_properties.insert("PairableTimeout".to_string(), Variant(Box::new(0u32)));
+        adapter_properties.insert(
+            "UUIDs".to_string(),
+            Variant(Box::new(vec![
+                "0000110a-0000-1000-8000-00805f9b34fb".to_string(),
+                "invalid".to_string(),
+            ])),
+        );
+
+        assert_eq!(
+            AdapterInfo::from_properties(
+                id.clone(),
+                OrgBluezAdapter1Properties(&adapter_properties)
+            )
+            .unwrap(),
+            AdapterInfo {
+                id,
+                mac_address: "00:11:22:33:44:55".parse().unwrap(),
+                address_type: Some(AddressType::Public),
+                name: Some("host".to_string()),
+                alias: Some("host".to_string()),
+                powered: true,
+                discovering: false,
+                discoverable: Some(true),
+                discoverable_timeout: Some(Duration::from_secs(180)),
+                pairable: Some(false),
+                pairable_timeout: None,
+                supported_uuids: vec![uuid_from_u16(0x110a)],
+            }
+        );
+    }
+
+    #[test]
+    fn adapter_info_minimal() {
+        let id = AdapterId::new("/org/bluez/hci0");
+        let mut adapter_properties = PropMap::new();
+        adapter_properties.insert(
+            "Address".to_string(),
+            Variant(Box::new("00:11:22:33:44:55".to_string())),
+        );
+        adapter_properties.insert(
+            "AddressType".to_string(),
+            Variant(Box::new("unknown".to_string())),
+        );
+        adapter_properties.insert("Powered".to_string(), Variant(Box::new(true)));
+        adapter_properties.insert("Discovering".to_string(), Variant(Box::new(false)));
+
+        let adapter =
+            AdapterInfo::from_properties(id, OrgBluezAdapter1Properties(&adapter_properties))
+                .unwrap();
+        assert_eq!(adapter.address_type, None);
+        assert_eq!(adapter.name, None);
+        assert_eq!(adapter.discoverable, None);
+        assert_eq!(adapter.pairable, None);
+    }
+
+    #[test]
+    fn adapter_static_random_address() {
+        let mut adapter = AdapterInfo {
+            id: AdapterId::new("/org/bluez/hci0"),
+            mac_address: "00:11:22:33:44:55".parse().unwrap(),
+            address_type: Some(AddressType::Public),
+            name: Some("host".to_string()),
+            alias: Some("host".to_string()),
+            powered: true,
+            discovering: false,
+            discoverable: Some(false),
+            discoverable_timeout: None,
+            pairable: Some(false),
+            pairable_timeout: None,
+            supported_uuids: vec![],
+        };
+        assert_eq!(adapter.static_random_address(), None);
+
+        adapter.address_type = Some(AddressType::Random);
+        adapter.mac_address = "C0:11:22:33:44:55".parse().unwrap();
+        assert_eq!(adapter.static_random_address(), Some(&adapter.mac_address));
+    }
+}
@@ -0,0 +1,181 @@
+use bluez_generated::ORG_BLUEZ_ADAPTER1_NAME;
+use dbus::message::{MatchRule, SignalArgs};
+use dbus::nonblock::stdintf::org_freedesktop_dbus::{
+    ObjectManagerInterfacesAdded, ObjectManagerInterfacesRemoved,
+};
+use dbus::strings::BusName;
+use dbus::Message;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Mutex;
+
+use crate::AdapterInfo;
+
+/// A cache of the Bluetooth adapters on the system, which is invalidated whenever an adapter is
+/// added or removed.
+///
+/// The cache is only filled once it has been enabled, which should be done once the D-Bus matches
+/// for the signals which invalidate it have been added.
+#[derive(Debug, Default)]
+pub(crate) struct AdapterCache {
+    enabled: AtomicBool,
+    state: Mutex<CacheState>,
+}
+
+#[derive(Debug, Default)]
+struct CacheState {
+    adapters: Option<Vec<AdapterInfo>>,
+    /// Incremented every time the cache is invalidated, so that a refresh which started before
+    /// the invalidation doesn't fill the cache with stale results.
+    generation: u64,
+}
+
+impl AdapterCache {
+    /// Get the match rules for the signals which may invalidate the cache.
+    pub fn match_rules(service_name: &BusName<'static>) -> Vec<MatchRule<'static>> {
+        vec![
+            ObjectManagerInterfacesAdded::match_rule(Some(service_name), None).static_clone(),
+            ObjectManagerInterfacesRemoved::match_rule(Some(service_name), None).static_clone(),
+        ]
+    }
+
+    /// Mark the cache as enabled. This should only be done once the D-Bus matches for the signals
+    /// which invalidate it have been added.
+    pub fn enable(&self) {
+        self.enabled.store(true, Ordering::SeqCst);
+    }
+
+    /// Whether the cache is enabled.
+    pub fn is_enabled(&self) -> bool {
+        self.enabled.load(Ordering::SeqCst)
+    }
+
+    /// Get the cached adapters if there are any, along with the current generation to pass to
+    /// `set` after refreshing.
+    pub fn get(&self) -> (Option<Vec<AdapterInfo>>, u64) {
+        let state = self.state.lock().unwrap();
+        (state.adapters.clone(), state.generation)
+    }
+
+    /// Fill the cache with the given adapters, unless it isn't enabled or has been invalidated
+    /// since the given generation was returned by `get`.
+    pub fn set(&self, adapters: Vec<AdapterInfo>, generation: u64) {
+        let mut state = self.state.lock().unwrap();
+        if self.is_enabled() && state.generation == generation {
+            state.adapters = Some(adapters);
+        }
+    }
+
+    /// Invalidate the cache if the given message is a signal that an adapter has been added or
+    /// removed.
+    pub fn handle_message(&self, message: &Message) {
+        if adapters_changed(message) {
+            let mut state = self.state.lock().unwrap();
+            state.adapters = None;
+            state.generation += 1;
+        }
+    }
+}
+
+/// Returns whether the given message is a signal that an adapter has been added or removed.
+fn adapters_changed(message: &Message) -> bool {
+    if let Some(interfaces_added) = ObjectManagerInterfacesAdded::from_message(message) {
+        interfaces_added
+            .interfaces
+            .contains_key(ORG_BLUEZ_ADAPTER1_NAME)
+    } else if let Some(interfaces_removed) = ObjectManagerInterfacesRemoved::from_message(message) {
+        interfaces_removed
+            .interfaces
+            .iter()
+            .any(|interface| interface == ORG_BLUEZ_ADAPTER1_NAME)
+    } else {
+        false
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{AdapterId, AddressType, MacAddress};
+    use dbus::arg::PropMap;
+    use std::collections::HashMap;
+
+    fn adapter_info() -> AdapterInfo {
+        AdapterInfo {
+            id: AdapterId::new("/org/bluez/hci0"),
+            mac_address: MacAddress("00:11:22:33:44:55".to_string()),
+            address_type: Some(AddressType::Public),
+            name: Some("name".to_string()),
+            alias: Some("alias".to_string()),
+            powered: true,
+            discovering: false,
+            discoverable: Some(false),
+            discoverable_timeout: None,
+            pairable: Some(false),
+            pairable_timeout: None,
+            supported_uuids: vec![],
+        }
+    }
+
+    fn interfaces_removed(interface: &str) -> Message {
+        ObjectManagerInterfacesRemoved {
+            object: "/org/bluez/hci1".into(),
+            interfaces: vec![interface.to_string()],
+        }
+        .to_emit_message(&"/".into())
+    }
+
+    #[test]
+    fn invalidated_by_adapter_removal() {
+        let cache = AdapterCache::default();
+        cache.enable();
+        let (adapters, generation) = cache.get();
+        assert_eq!(adapters, None);
+        cache.set(vec![adapter_info()], generation);
+        assert_eq!(cache.get().0, Some(vec![adapter_info()]));
+
+        cache.handle_message(&interfaces_removed("org.bluez.Device1"));
+        assert_eq!(cache.get().0, Some(vec![adapter_info()]));
+
+        cache.handle_message(&interfaces_removed(ORG_BLUEZ_ADAPTER1_NAME));
+        assert_eq!(cache.get().0, None);
+    }
+
+    #[test]
+    fn invalidated_by_adapter_addition() {
+        let cache = AdapterCache::default();
+        cache.enable();
+        cache.set(vec![adapter_info()], cache.get().1);
+
+        let mut interfaces = HashMap::new();
+        interfaces.insert(ORG_BLUEZ_ADAPTER1_NAME.to_string(), PropMap::new());
+        let message = ObjectManagerInterfacesAdded {
+            object: "/org/bluez/hci1".into(),
+            interfaces,
+        }
+        .to_emit_message(&"/".into());
+        cache.handle_message(&message);
+        assert_eq!(cache.get().0, None);
+    }
+
+    #[test]
+    fn stale_refresh_ignored() {
+        let cache = AdapterCache::default();
+        cache.enable();
+        let (_, generation) = cache.get();
+        cache.handle_message(&interfaces_removed(ORG_BLUEZ_ADAPTER1_NAME));
+        cache.set(vec![adapter_info()], generation);
+        assert_eq!(cache.get().0, None);
+    }
+
+    #[test]
+    fn not_filled_until_enabled() {
+        let cache = AdapterCache::default();
+        cache.set(vec![adapter_info()], cache.get().1);
+        assert_eq!(cache.get().0, None);
+
+        cache.enable();
+        assert!(cache.is_enabled());
+        cache.set(vec![adapter_info()], cache.get().1);
+        assert_eq!(cache.get().0, Some(vec![adapter_info()]));
+    }
+}
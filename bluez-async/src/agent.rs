@@ -0,0 +1,470 @@
+//! Support for registering a pairing agent with BlueZ, to respond to requests for PIN codes,
+//! passkeys and confirmation when pairing with devices.
+
+use async_trait::async_trait;
+use bluez_generated::{OrgBluezAgentManager1, ORG_BLUEZ_AGENT_MANAGER1_NAME};
+use dbus::channel::Sender;
+use dbus::strings::ErrorName;
+use dbus::{Message, Path};
+use std::ffi::CString;
+use std::fmt::{self, Debug, Display, Formatter};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+use uuid::Uuid;
+
+use crate::exportedobject::ExportedObject;
+use crate::{BluetoothError, BluetoothSession, DeviceId};
+
+const ORG_BLUEZ_AGENT1_NAME: &str = "org.bluez.Agent1";
+const AGENT_MANAGER_PATH: &str = "/org/bluez";
+
+/// Used to give each registered agent a unique object path.
+static NEXT_AGENT_ID: AtomicUsize = AtomicUsize::new(0);
+
+/// The input and output capabilities of a pairing agent, which determine which pairing method
+/// BlueZ will use.
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+pub enum AgentCapability {
+    /// The agent can display a passkey, but not accept any input.
+    DisplayOnly,
+    /// The agent can display a passkey and ask the user to confirm it.
+    DisplayYesNo,
+    /// The agent can ask the user to enter a passkey, but not display anything.
+    KeyboardOnly,
+    /// The agent can neither display anything nor accept any input.
+    NoInputNoOutput,
+    /// The agent can both display a passkey and ask the user to enter one.
+    KeyboardDisplay,
+}
+
+impl Display for AgentCapability {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        let s = match self {
+            Self::DisplayOnly => "DisplayOnly",
+            Self::DisplayYesNo => "DisplayYesNo",
+            Self::KeyboardOnly => "KeyboardOnly",
+            Self::NoInputNoOutput => "NoInputNoOutput",
+            Self::KeyboardDisplay => "KeyboardDisplay",
+        };
+        f.write_str(s)
+    }
+}
+
+/// A reason for a pairing agent to refuse a request.
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+pub enum AgentError {
+    /// The request was rejected, e.g. because the user declined it.
+    Rejected,
+    /// The request was cancelled, e.g. because the user didn't respond.
+    Canceled,
+}
+
+impl AgentError {
+    fn error_name(&self) -> &'static str {
+        match self {
+            Self::Rejected => "org.bluez.Error.Rejected",
+            Self::Canceled => "org.bluez.Error.Canceled",
+        }
+    }
+}
+
+/// A pairing agent, which BlueZ calls to get input from or display information to the user while
+/// pairing with a device.
+///
+/// Which methods are called depends on the [`AgentCapability`] the agent is registered with and
+/// the capabilities of the remote device. By default, requests for input are rejected and requests
+/// to display something are ignored, so implementations only need to override the methods relevant
+/// to their capability.
+#[async_trait]
+pub trait BluetoothAgent: Send + Sync + 'static {
+    /// Get the PIN code to use to pair with the given device, a string of 1 to 16 alphanumeric
+    /// characters.
+    async fn request_pin_code(&self, _device: DeviceId) -> Result<String, AgentError> {
+        Err(AgentError::Rejected)
+    }
+
+    /// Display the given PIN code, which the user should enter on the given device.
+    async fn display_pin_code(
+        &self,
+        _device: DeviceId,
+        _pin_code: String,
+    ) -> Result<(), AgentError> {
+        Ok(())
+    }
+
+    /// Get the passkey to use to pair with the given device, a number from 0 to 999999.
+    async fn request_passkey(&self, _device: DeviceId) -> Result<u32, AgentError> {
+        Err(AgentError::Rejected)
+    }
+
+    /// Display the given passkey, which the user should enter on the given device. `entered` is
+    /// the number of digits which have been entered so far.
+    ///
+    /// This may be called again as the user enters each digit.
+    async fn display_passkey(&self, _device: DeviceId, _passkey: u32, _entered: u16) {}
+
+    /// Ask the user to confirm that the given passkey matches the one shown on the given device.
+    async fn request_confirmation(
+        &self,
+        _device: DeviceId,
+        _passkey: u32,
+    ) -> Result<(), AgentError> {
+        Err(AgentError::Rejected)
+    }
+
+    /// Ask the user whether to allow an incoming pairing request from the given device, which
+    /// would otherwise be accepted without any confirmation.
+    async fn request_authorization(&self, _device: DeviceId) -> Result<(), AgentError> {
+        Err(AgentError::Rejected)
+    }
+
+    /// Ask the user whether the given device may connect to the service with the given UUID.
+    async fn authorize_service(&self, _device: DeviceId, _uuid: Uuid) -> Result<(), AgentError> {
+        Err(AgentError::Rejected)
+    }
+
+    /// The previous request was cancelled by BlueZ, e.g. because it timed out.
+    async fn cancel(&self) {}
+
+    /// BlueZ has unregistered the agent, e.g. because BlueZ is shutting down.
+    async fn release(&self) {}
+}
+
+/// A method call from BlueZ to an agent.
+#[derive(Clone, Debug, Eq, PartialEq)]
+enum AgentRequest {
+    Release,
+    RequestPinCode {
+        device: DeviceId,
+    },
+    DisplayPinCode {
+        device: DeviceId,
+        pin_code: String,
+    },
+    RequestPasskey {
+        device: DeviceId,
+    },
+    DisplayPasskey {
+        device: DeviceId,
+        passkey: u32,
+        entered: u16,
+    },
+    RequestConfirmation {
+        device: DeviceId,
+        passkey: u32,
+    },
+    RequestAuthorization {
+        device: DeviceId,
+    },
+    AuthorizeService {
+        device: DeviceId,
+        uuid: Uuid,
+    },
+    Cancel,
+}
+
+/// A successful response from an agent to a request.
+#[derive(Clone, Debug, Eq, PartialEq)]
+enum AgentResponse {
+    Empty,
+    PinCode(String),
+    Passkey(u32),
+}
+
+impl AgentRequest {
+    /// Parse the given method call on the `Agent1` interface, returning `None` if it isn't a known
+    /// method or has the wrong arguments.
+    fn from_message(message: &Message) -> Option<Self> {
+        if message.interface().as_deref() != Some(ORG_BLUEZ_AGENT1_NAME) {
+            return None;
+        }
+        let device = || {
+            message.get1::<Path>().map(|object_path| DeviceId {
+                object_path: object_path.into_static(),
+            })
+        };
+        Some(match &*message.member()? {
+            "Release" => Self::Release,
+            "RequestPinCode" => Self::RequestPinCode { device: device()? },
+            "DisplayPinCode" => {
+                let (_, pin_code) = message.get2::<Path, String>();
+                Self::DisplayPinCode {
+                    device: device()?,
+                    pin_code: pin_code?,
+                }
+            }
+            "RequestPasskey" => Self::RequestPasskey { device: device()? },
+            "DisplayPasskey" => {
+                let (_, passkey, entered) = message.get3::<Path, u32, u16>();
+                Self::DisplayPasskey {
+                    device: device()?,
+                    passkey: passkey?,
+                    entered: entered?,
+                }
+            }
+            "RequestConfirmation" => {
+                let (_, passkey) = message.get2::<Path, u32>();
+                Self::RequestConfirmation {
+                    device: device()?,
+                    passkey: passkey?,
+                }
+            }
+            "RequestAuthorization" => Self::RequestAuthorization { device: device()? },
+            "AuthorizeService" => {
+                let (_, uuid) = message.get2::<Path, String>();
+                Self::AuthorizeService {
+                    device: device()?,
+                    uuid: Uuid::parse_str(&uuid?).ok()?,
+                }
+            }
+            "Cancel" => Self::Cancel,
+            _ => return None,
+        })
+    }
+
+    /// Pass the request to the appropriate method of the given agent.
+    async fn handle(self, agent: &dyn BluetoothAgent) -> Result<AgentResponse, AgentError> {
+        match self {
+            Self::Release => agent.release().await,
+            Self::RequestPinCode { device } => {
+                return agent
+                    .request_pin_code(device)
+                    .await
+                    .map(AgentResponse::PinCode)
+            }
+            Self::DisplayPinCode { device, pin_code } => {
+                agent.display_pin_code(device, pin_code).await?
+            }
+            Self::RequestPasskey { device } => {
+                return agent
+                    .request_passkey(device)
+                    .await
+                    .map(AgentResponse::Passkey)
+            }
+            Self::DisplayPasskey {
+                device,
+                passkey,
+                entered,
+            } => agent.display_passkey(device, passkey, entered).await,
+            Self::RequestConfirmation { device, passkey } => {
+                agent.request_confirmation(device, passkey).await?
+            }
+            Self::RequestAuthorization { device } => agent.request_authorization(device).await?,
+            Self::AuthorizeService { device, uuid } => {
+                agent.authorize_service(device, uuid).await?
+            }
+            Self::Cancel => agent.cancel().await,
+        }
+        Ok(AgentResponse::Empty)
+    }
+}
+
+/// Construct the reply to the given method call for the given result from the agent.
+fn agent_reply(message: &Message, result: Result<AgentResponse, AgentError>) -> Message {
+    match result {
+        Ok(AgentResponse::Empty) => message.method_return(),
+        Ok(AgentResponse::PinCode(pin_code)) => message.method_return().append1(pin_code),
+        Ok(AgentResponse::Passkey(passkey)) => message.method_return().append1(passkey),
+        Err(e) => message.error(
+            &ErrorName::from(e.error_name()),
+            &CString::new(format!("{:?}", e)).unwrap(),
+        ),
+    }
+}
+
+/// A pairing agent which has been registered with BlueZ.
+///
+/// The agent is unregistered when this is dropped.
+pub struct RegisteredAgent {
+    agent: ExportedObject,
+}
+
+impl RegisteredAgent {
+    /// Make this the default agent, which BlueZ uses for pairing requests which weren't initiated
+    /// by any particular agent, such as incoming pairing requests from remote devices.
+    pub async fn request_default(&self) -> Result<(), BluetoothError> {
+        Ok(self
+            .agent
+            .manager()
+            .request_default_agent(self.agent.path().clone())
+            .await?)
+    }
+}
+
+impl Debug for RegisteredAgent {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        write!(f, "RegisteredAgent({})", self.agent.path())
+    }
+}
+
+impl BluetoothSession {
+    /// Register the given pairing agent with BlueZ, with the given capability.
+    ///
+    /// The agent will be used for pairing initiated by this process, e.g. with
+    /// [`pair`](Self::pair). Call [`RegisteredAgent::request_default`] if it should also handle
+    /// incoming pairing requests. The agent remains registered until the returned value is
+    /// dropped.
+    pub async fn register_agent(
+        &self,
+        agent: impl BluetoothAgent,
+        capability: AgentCapability,
+    ) -> Result<RegisteredAgent, BluetoothError> {
+        let id = NEXT_AGENT_ID.fetch_add(1, Ordering::Relaxed);
+        let agent_path: Path<'static> = format!("/bluez_async/agent{}", id).into();
+
+        let agent: Arc<dyn BluetoothAgent> = Arc::new(agent);
+        let connection = self.connection.clone();
+        let mut exported_agent = ExportedObject::new(
+            self,
+            agent_path.clone(),
+            false,
+            AGENT_MANAGER_PATH.into(),
+            ORG_BLUEZ_AGENT_MANAGER1_NAME,
+            "agent",
+            move |message| match AgentRequest::from_message(&message) {
+                Some(request) => {
+                    let agent = agent.clone();
+                    let connection = connection.clone();
+                    tokio::spawn(async move {
+                        let result = request.handle(&*agent).await;
+                        if connection.send(agent_reply(&message, result)).is_err() {
+                            log::error!("Error sending reply to agent method call");
+                        }
+                    });
+                    None
+                }
+                None => Some(
+                    dbus::channel::default_reply(&message)
+                        .unwrap_or_else(|| message.method_return()),
+                ),
+            },
+        );
+        exported_agent
+            .register(
+                "RegisterAgent",
+                (agent_path, capability.to_string()),
+                "UnregisterAgent",
+            )
+            .await?;
+        Ok(RegisteredAgent {
+            agent: exported_agent,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    struct TestAgent;
+
+    #[async_trait]
+    impl BluetoothAgent for TestAgent {
+        async fn request_passkey(&self, _device: DeviceId) -> Result<u32, AgentError> {
+            Ok(123456)
+        }
+
+        async fn request_confirmation(
+            &self,
+            _device: DeviceId,
+            passkey: u32,
+        ) -> Result<(), AgentError> {
+            if passkey == 123456 {
+                Ok(())
+            } else {
+                Err(AgentError::Canceled)
+            }
+        }
+    }
+
+    fn agent_method_call(method: &str) -> Message {
+        Message::new_method_call(
+            "org.bluez",
+            "/bluez_async/agent0",
+            ORG_BLUEZ_AGENT1_NAME,
+            method,
+        )
+        .unwrap()
+    }
+
+    #[test]
+    fn parse_requests() {
+        let device = DeviceId::new("/org/bluez/hci0/dev_11_22_33_44_55_66");
+        let message =
+            agent_method_call("RequestConfirmation").append2(device.object_path.clone(), 42u32);
+        assert_eq!(
+            AgentRequest::from_message(&message),
+            Some(AgentRequest::RequestConfirmation {
+                device: device.clone(),
+                passkey: 42
+            })
+        );
+
+        let message = agent_method_call("AuthorizeService").append2(
+            device.object_path.clone(),
+            "0000180d-0000-1000-8000-00805f9b34fb",
+        );
+        assert_eq!(
+            AgentRequest::from_message(&message),
+            Some(AgentRequest::AuthorizeService {
+                device,
+                uuid: Uuid::parse_str("0000180d-0000-1000-8000-00805f9b34fb").unwrap(),
+            })
+        );
+
+        assert_eq!(
+            AgentRequest::from_message(&agent_method_call("Cancel")),
+            Some(AgentRequest::Cancel)
+        );
+        assert_eq!(
+            AgentRequest::from_message(&agent_method_call("RequestPasskey")),
+            None
+        );
+        assert_eq!(
+            AgentRequest::from_message(&agent_method_call("Unknown")),
+            None
+        );
+    }
+
+    #[tokio::test]
+    async fn handle_requests() {
+        let device = DeviceId::new("/org/bluez/hci0/dev_11_22_33_44_55_66");
+        assert_eq!(
+            AgentRequest::RequestPasskey {
+                device: device.clone()
+            }
+            .handle(&TestAgent)
+            .await,
+            Ok(AgentResponse::Passkey(123456))
+        );
+        assert_eq!(
+            AgentRequest::RequestConfirmation {
+                device: device.clone(),
+                passkey: 111111
+            }
+            .handle(&TestAgent)
+            .await,
+            Err(AgentError::Canceled)
+        );
+        assert_eq!(
+            AgentRequest::RequestPinCode { device }
+                .handle(&TestAgent)
+                .await,
+            Err(AgentError::Rejected)
+        );
+    }
+
+    #[test]
+    fn replies() {
+        let mut message = agent_method_call("RequestPasskey");
+        message.set_serial(1);
+        let reply = agent_reply(&message, Ok(AgentResponse::Passkey(42)));
+        assert_eq!(reply.read1::<u32>().unwrap(), 42);
+
+        let mut reply = agent_reply(&message, Err(AgentError::Rejected));
+        assert_eq!(
+            reply.as_result().unwrap_err().name(),
+            Some("org.bluez.Error.Rejected")
+        );
+    }
+}
@@ -0,0 +1,138 @@
+/// The category of a device's GAP appearance, as assigned by the Bluetooth SIG.
+///
+/// The appearance value consists of a 10-bit category and a 6-bit subcategory; only the category
+/// is decoded here, which is enough to e.g. pick an icon for a device.
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+#[non_exhaustive]
+pub enum Appearance {
+    Unknown,
+    Phone,
+    Computer,
+    Watch,
+    Clock,
+    Display,
+    RemoteControl,
+    EyeGlasses,
+    Tag,
+    Keyring,
+    MediaPlayer,
+    BarcodeScanner,
+    Thermometer,
+    HeartRateSensor,
+    BloodPressure,
+    HumanInterfaceDevice,
+    GlucoseMeter,
+    RunningWalkingSensor,
+    Cycling,
+    ControlDevice,
+    NetworkDevice,
+    Sensor,
+    LightFixture,
+    Fan,
+    Hvac,
+    AirConditioning,
+    Humidifier,
+    Heating,
+    AccessControl,
+    MotorizedDevice,
+    PowerDevice,
+    LightSource,
+    WindowCovering,
+    AudioSink,
+    AudioSource,
+    MotorizedVehicle,
+    DomesticAppliance,
+    WearableAudioDevice,
+    Aircraft,
+    AvEquipment,
+    DisplayEquipment,
+    HearingAid,
+    Gaming,
+    Signage,
+    PulseOximeter,
+    WeightScale,
+    PersonalMobilityDevice,
+    ContinuousGlucoseMonitor,
+    InsulinPump,
+    MedicationDelivery,
+    Spirometer,
+    OutdoorSportsActivity,
+    /// A category which isn't known to this crate, with the given 10-bit category number.
+    Other(u16),
+}
+
+impl Appearance {
+    /// Decode the category of the given GAP appearance value.
+    pub fn from_u16(appearance: u16) -> Self {
+        match appearance >> 6 {
+            0x000 => Self::Unknown,
+            0x001 => Self::Phone,
+            0x002 => Self::Computer,
+            0x003 => Self::Watch,
+            0x004 => Self::Clock,
+            0x005 => Self::Display,
+            0x006 => Self::RemoteControl,
+            0x007 => Self::EyeGlasses,
+            0x008 => Self::Tag,
+            0x009 => Self::Keyring,
+            0x00a => Self::MediaPlayer,
+            0x00b => Self::BarcodeScanner,
+            0x00c => Self::Thermometer,
+            0x00d => Self::HeartRateSensor,
+            0x00e => Self::BloodPressure,
+            0x00f => Self::HumanInterfaceDevice,
+            0x010 => Self::GlucoseMeter,
+            0x011 => Self::RunningWalkingSensor,
+            0x012 => Self::Cycling,
+            0x013 => Self::ControlDevice,
+            0x014 => Self::NetworkDevice,
+            0x015 => Self::Sensor,
+            0x016 => Self::LightFixture,
+            0x017 => Self::Fan,
+            0x018 => Self::Hvac,
+            0x019 => Self::AirConditioning,
+            0x01a => Self::Humidifier,
+            0x01b => Self::Heating,
+            0x01c => Self::AccessControl,
+            0x01d => Self::MotorizedDevice,
+            0x01e => Self::PowerDevice,
+            0x01f => Self::LightSource,
+            0x020 => Self::WindowCovering,
+            0x021 => Self::AudioSink,
+            0x022 => Self::AudioSource,
+            0x023 => Self::MotorizedVehicle,
+            0x024 => Self::DomesticAppliance,
+            0x025 => Self::WearableAudioDevice,
+            0x026 => Self::Aircraft,
+            0x027 => Self::AvEquipment,
+            0x028 => Self::DisplayEquipment,
+            0x029 => Self::HearingAid,
+            0x02a => Self::Gaming,
+            0x02b => Self::Signage,
+            0x031 => Self::PulseOximeter,
+            0x032 => Self::WeightScale,
+            0x033 => Self::PersonalMobilityDevice,
+            0x034 => Self::ContinuousGlucoseMonitor,
+            0x035 => Self::InsulinPump,
+            0x036 => Self::MedicationDelivery,
+            0x037 => Self::Spirometer,
+            0x051 => Self::OutdoorSportsActivity,
+            category => Self::Other(category),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn categories() {
+        assert_eq!(Appearance::from_u16(0x0000), Appearance::Unknown);
+        assert_eq!(Appearance::from_u16(0x0340), Appearance::HeartRateSensor);
+        assert_eq!(Appearance::from_u16(0x0341), Appearance::HeartRateSensor);
+        assert_eq!(Appearance::from_u16(0x00c1), Appearance::Watch);
+        assert_eq!(Appearance::from_u16(0x0c80), Appearance::WeightScale);
+        assert_eq!(Appearance::from_u16(0xffff), Appearance::Other(0x3ff));
+    }
+}
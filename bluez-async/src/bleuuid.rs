@@ -18,6 +18,18 @@ pub const fn uuid_from_u16(short: u16) -> Uuid {
 
 /// An extension trait for `Uuid` which provides BLE-specific methods.
 pub trait BleUuid {
+    /// Convert a 32-bit BLE short UUID to a full 128-bit UUID by filling in the standard Bluetooth
+    /// Base UUID. This is equivalent to [`uuid_from_u32`].
+    fn from_ble_u32(short: u32) -> Self
+    where
+        Self: Sized;
+
+    /// Convert a 16-bit BLE short UUID to a full 128-bit UUID by filling in the standard Bluetooth
+    /// Base UUID. This is equivalent to [`uuid_from_u16`].
+    fn from_ble_u16(short: u16) -> Self
+    where
+        Self: Sized;
+
     /// If the UUID is a valid BLE short UUID then return its short form, otherwise return `None`.
     fn to_ble_u32(&self) -> Option<u32>;
 
@@ -30,6 +42,14 @@ pub trait BleUuid {
 }
 
 impl BleUuid for Uuid {
+    fn from_ble_u32(short: u32) -> Self {
+        uuid_from_u32(short)
+    }
+
+    fn from_ble_u16(short: u16) -> Self {
+        uuid_from_u16(short)
+    }
+
     fn to_ble_u32(&self) -> Option<u32> {
         let value = self.as_u128();
         if value & BLUETOOTH_BASE_MASK == BLUETOOTH_BASE_UUID {
@@ -71,6 +91,12 @@ mod tests {
         );
     }
 
+    #[test]
+    fn from_ble_test() {
+        assert_eq!(Uuid::from_ble_u32(0x11223344), uuid_from_u32(0x11223344));
+        assert_eq!(Uuid::from_ble_u16(0x1122), uuid_from_u16(0x1122));
+    }
+
     #[test]
     fn uuid_from_u16_test() {
         assert_eq!(
@@ -1,14 +1,18 @@
 use bitflags::bitflags;
+use bluez_generated::OrgBluezGattCharacteristic1Properties;
+use dbus::arg::prop_cast;
 use dbus::Path;
-use std::convert::TryFrom;
+use serde_derive::{Deserialize, Serialize};
+use std::convert::{TryFrom, TryInto};
 use std::fmt::{self, Display, Formatter};
 use uuid::Uuid;
 
-use crate::{BluetoothError, ServiceId};
+use crate::{BluetoothError, DeviceId, ServiceId, WriteType};
 
 /// Opaque identifier for a GATT characteristic on a Bluetooth device.
-#[derive(Clone, Debug, Eq, Hash, Ord, PartialEq, PartialOrd)]
+#[derive(Clone, Debug, Deserialize, Eq, Hash, Ord, PartialEq, PartialOrd, Serialize)]
 pub struct CharacteristicId {
+    #[serde(with = "crate::serde_path")]
     pub(crate) object_path: Path<'static>,
 }
 
@@ -19,6 +23,11 @@ impl CharacteristicId {
         }
     }
 
+    /// Get the D-Bus object path of the characteristic, for use with the `dbus` crate directly.
+    pub fn path(&self) -> &Path<'static> {
+        &self.object_path
+    }
+
     /// Get the ID of the service on which this characteristic was advertised.
     pub fn service(&self) -> ServiceId {
         let index = self
@@ -27,6 +36,17 @@ impl CharacteristicId {
             .expect("CharacteristicId object_path must contain a slash.");
         ServiceId::new(&self.object_path[0..index])
     }
+
+    /// Get the ID of the device on which this characteristic was advertised.
+    pub fn device(&self) -> DeviceId {
+        self.service().device()
+    }
+}
+
+impl AsRef<Path<'static>> for CharacteristicId {
+    fn as_ref(&self) -> &Path<'static> {
+        &self.object_path
+    }
 }
 
 impl From<CharacteristicId> for Path<'static> {
@@ -49,7 +69,7 @@ impl Display for CharacteristicId {
 }
 
 /// Information about a GATT characteristic on a Bluetooth device.
-#[derive(Clone, Debug, Eq, PartialEq)]
+#[derive(Clone, Debug, Deserialize, Eq, PartialEq, Serialize)]
 pub struct CharacteristicInfo {
     /// An opaque identifier for the characteristic on the device, including a reference to which
     /// adapter it was discovered on.
@@ -59,11 +79,112 @@ pub struct CharacteristicInfo {
     /// The set of flags (a.k.a. properties) of the characteristic, defining how the characteristic
     /// can be used.
     pub flags: CharacteristicFlags,
+    /// The ATT handle of the characteristic, if BlueZ provides it. This can be used to detect when
+    /// the GATT layout of a device has changed between connections.
+    pub handle: Option<u16>,
+    /// Whether a client currently holds a file descriptor for writing to the characteristic,
+    /// acquired with `AcquireWrite`, if BlueZ provides this.
+    pub write_acquired: Option<bool>,
+    /// Whether a client currently holds a file descriptor for notifications from the
+    /// characteristic, acquired with `AcquireNotify`, if BlueZ provides this. If so then
+    /// notifications are delivered to that client rather than as D-Bus signals.
+    pub notify_acquired: Option<bool>,
+}
+
+impl CharacteristicInfo {
+    /// Get the ID of the device on which this characteristic was advertised.
+    pub fn device(&self) -> DeviceId {
+        self.id.device()
+    }
+
+    pub(crate) fn from_properties(
+        id: CharacteristicId,
+        characteristic_properties: OrgBluezGattCharacteristic1Properties,
+    ) -> Result<CharacteristicInfo, BluetoothError> {
+        let uuid = Uuid::parse_str(
+            characteristic_properties
+                .uuid()
+                .ok_or_else(|| BluetoothError::RequiredPropertyMissing("UUID".to_string()))?,
+        )?;
+        let flags = characteristic_properties
+            .flags()
+            .ok_or_else(|| BluetoothError::RequiredPropertyMissing("Flags".to_string()))?
+            .to_owned()
+            .try_into()?;
+        let handle = prop_cast(characteristic_properties.0, "Handle").copied();
+        Ok(CharacteristicInfo {
+            id,
+            uuid,
+            flags,
+            handle,
+            write_acquired: characteristic_properties.write_acquired(),
+            notify_acquired: characteristic_properties.notify_acquired(),
+        })
+    }
+}
+
+/// The default ATT MTU, which is the minimum that every LE connection supports.
+const DEFAULT_ATT_MTU: u16 = 23;
+
+/// The length of the header of an ATT Write Command: 1 byte for the opcode and 2 for the handle.
+const WRITE_HEADER_LENGTH: u16 = 3;
+
+/// The maximum length of an attribute value, as defined by the Bluetooth Core Specification.
+pub(crate) const MAX_ATTRIBUTE_VALUE_LENGTH: usize = 512;
+
+/// The longest value which can be written to a characteristic with any flags and MTU, as a single
+/// ATT Write Command with the default MTU.
+pub(crate) const MIN_MAX_WRITE_LENGTH: usize = (DEFAULT_ATT_MTU - WRITE_HEADER_LENGTH) as usize;
+
+/// Get the maximum length of a value which can be written to a characteristic with the given
+/// flags and negotiated MTU, with the given write type, if it is known.
+///
+/// A write with response can use a long write if necessary, so the limit is the maximum attribute
+/// value length. A write without response must fit in a single packet, so the limit depends on
+/// the MTU. If no write type is given then BlueZ prefers a write with response if the flags allow
+/// it.
+pub(crate) fn max_write_length(
+    flags: CharacteristicFlags,
+    mtu: Option<u16>,
+    write_type: Option<WriteType>,
+) -> Option<usize> {
+    let without_response_length = mtu.map(|mtu| mtu.saturating_sub(WRITE_HEADER_LENGTH) as usize);
+    match write_type {
+        Some(WriteType::WithResponse) | Some(WriteType::Reliable) => {
+            Some(MAX_ATTRIBUTE_VALUE_LENGTH)
+        }
+        Some(WriteType::WithoutResponse) => without_response_length,
+        None => {
+            if flags.intersects(
+                CharacteristicFlags::WRITE
+                    | CharacteristicFlags::RELIABLE_WRITE
+                    | CharacteristicFlags::ENCRYPT_WRITE
+                    | CharacteristicFlags::ENCRYPT_AUTHENTICATED_WRITE,
+            ) {
+                Some(MAX_ATTRIBUTE_VALUE_LENGTH)
+            } else if flags.contains(CharacteristicFlags::WRITE_WITHOUT_RESPONSE) {
+                without_response_length
+            } else {
+                None
+            }
+        }
+    }
+}
+
+/// Get the write type to use for a long write to a characteristic with the given flags: a reliable
+/// write if the characteristic supports it, otherwise a write with response.
+pub(crate) fn long_write_type(flags: CharacteristicFlags) -> WriteType {
+    if flags.contains(CharacteristicFlags::RELIABLE_WRITE) {
+        WriteType::Reliable
+    } else {
+        WriteType::WithResponse
+    }
 }
 
 bitflags! {
     /// The set of flags (a.k.a. properties) of a characteristic, defining how the characteristic
     /// can be used.
+    #[derive(Deserialize, Serialize)]
     pub struct CharacteristicFlags: u16 {
         const BROADCAST = 0x01;
         const READ = 0x02;
@@ -83,6 +204,27 @@ bitflags! {
     }
 }
 
+impl CharacteristicFlags {
+    /// Returns whether the characteristic's value can be read, possibly requiring encryption or
+    /// authentication.
+    pub fn is_readable(&self) -> bool {
+        self.intersects(Self::READ | Self::ENCRYPT_READ | Self::ENCRYPT_AUTHENTICATED_READ)
+    }
+
+    /// Returns whether the characteristic's value can be written, with or without response,
+    /// possibly requiring encryption or authentication.
+    pub fn is_writable(&self) -> bool {
+        self.intersects(
+            Self::WRITE
+                | Self::WRITE_WITHOUT_RESPONSE
+                | Self::SIGNED_WRITE
+                | Self::RELIABLE_WRITE
+                | Self::ENCRYPT_WRITE
+                | Self::ENCRYPT_AUTHENTICATED_WRITE,
+        )
+    }
+}
+
 impl TryFrom<Vec<String>> for CharacteristicFlags {
     type Error = BluetoothError;
 
@@ -117,7 +259,8 @@ impl TryFrom<Vec<String>> for CharacteristicFlags {
 mod tests {
     use super::*;
 
-    use std::convert::TryInto;
+    use dbus::arg::{RefArg, Variant};
+    use std::collections::HashMap;
 
     #[test]
     fn characteristic_service() {
@@ -127,6 +270,28 @@ mod tests {
         assert_eq!(characteristic_id.service(), service_id);
     }
 
+    #[test]
+    fn characteristic_device() {
+        let characteristic_id =
+            CharacteristicId::new("/org/bluez/hci1/dev_AA_BB_CC_DD_EE_FF/service000c/char000d");
+        let device_id = characteristic_id.device();
+        assert_eq!(
+            device_id,
+            DeviceId::new("/org/bluez/hci1/dev_AA_BB_CC_DD_EE_FF")
+        );
+        assert_eq!(device_id.to_string(), "hci1/dev_AA_BB_CC_DD_EE_FF");
+
+        let characteristic_info = CharacteristicInfo {
+            id: characteristic_id,
+            uuid: Uuid::nil(),
+            flags: CharacteristicFlags::NOTIFY,
+            handle: None,
+            write_acquired: None,
+            notify_acquired: None,
+        };
+        assert_eq!(characteristic_info.device(), device_id);
+    }
+
     #[test]
     fn parse_flags() {
         let flags: CharacteristicFlags = vec!["read".to_string(), "encrypt-write".to_string()]
@@ -146,4 +311,92 @@ mod tests {
             matches!(flags, Err(BluetoothError::FlagParseError(string)) if string == "invalid flag")
         );
     }
+
+    #[test]
+    fn characteristic_info_with_handle() {
+        let id =
+            CharacteristicId::new("/org/bluez/hci0/dev_11_22_33_44_55_66/service0022/char0033");
+        let uuid = Uuid::parse_str("ebe0ccb7-7a0a-4b0c-8a1a-6ff2997da3a6").unwrap();
+        let mut properties: HashMap<String, Variant<Box<dyn RefArg>>> = HashMap::new();
+        properties.insert("UUID".to_string(), Variant(Box::new(uuid.to_string())));
+        properties.insert(
+            "Flags".to_string(),
+            Variant(Box::new(vec!["read".to_string()])),
+        );
+        properties.insert("Handle".to_string(), Variant(Box::new(0x0033u16)));
+        properties.insert("NotifyAcquired".to_string(), Variant(Box::new(true)));
+
+        assert_eq!(
+            CharacteristicInfo::from_properties(
+                id.clone(),
+                OrgBluezGattCharacteristic1Properties(&properties)
+            )
+            .unwrap(),
+            CharacteristicInfo {
+                id,
+                uuid,
+                flags: CharacteristicFlags::READ,
+                handle: Some(0x0033),
+                write_acquired: None,
+                notify_acquired: Some(true),
+            }
+        );
+    }
+
+    #[test]
+    fn flag_predicates() {
+        assert!(CharacteristicFlags::READ.is_readable());
+        assert!(CharacteristicFlags::ENCRYPT_READ.is_readable());
+        assert!(!CharacteristicFlags::WRITE.is_readable());
+        assert!(CharacteristicFlags::WRITE_WITHOUT_RESPONSE.is_writable());
+        assert!(!(CharacteristicFlags::READ | CharacteristicFlags::NOTIFY).is_writable());
+    }
+
+    #[test]
+    fn long_write_types() {
+        assert_eq!(
+            long_write_type(CharacteristicFlags::WRITE | CharacteristicFlags::RELIABLE_WRITE),
+            WriteType::Reliable
+        );
+        assert_eq!(
+            long_write_type(CharacteristicFlags::WRITE),
+            WriteType::WithResponse
+        );
+    }
+
+    #[test]
+    fn max_write_lengths() {
+        assert_eq!(
+            max_write_length(CharacteristicFlags::WRITE, None, None),
+            Some(MAX_ATTRIBUTE_VALUE_LENGTH)
+        );
+        assert_eq!(
+            max_write_length(
+                CharacteristicFlags::WRITE | CharacteristicFlags::WRITE_WITHOUT_RESPONSE,
+                Some(23),
+                None
+            ),
+            Some(MAX_ATTRIBUTE_VALUE_LENGTH)
+        );
+        assert_eq!(
+            max_write_length(CharacteristicFlags::WRITE_WITHOUT_RESPONSE, Some(247), None),
+            Some(244)
+        );
+        assert_eq!(
+            max_write_length(CharacteristicFlags::WRITE_WITHOUT_RESPONSE, None, None),
+            None
+        );
+        assert_eq!(
+            max_write_length(CharacteristicFlags::READ, Some(23), None),
+            None
+        );
+        assert_eq!(
+            max_write_length(
+                CharacteristicFlags::WRITE | CharacteristicFlags::WRITE_WITHOUT_RESPONSE,
+                Some(23),
+                Some(WriteType::WithoutResponse)
+            ),
+            Some(20)
+        );
+    }
 }
@@ -0,0 +1,316 @@
+//! Support for Bluetooth Classic RFCOMM connections, such as those used by the Serial Port Profile.
+//!
+//! This works by registering a client profile with BlueZ's `ProfileManager1` interface and then
+//! asking BlueZ to connect it, at which point BlueZ passes us the file descriptor of the connected
+//! RFCOMM socket.
+
+use bluez_generated::ORG_BLUEZ_PROFILE_MANAGER1_NAME;
+use dbus::arg::{OwnedFd, PropMap, Variant};
+use dbus::{Message, Path};
+use futures::channel::oneshot;
+use std::collections::HashMap;
+use std::io;
+use std::os::unix::io::AsRawFd;
+use std::pin::Pin;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::task::{Context, Poll};
+use tokio::io::unix::AsyncFd;
+use tokio::io::{AsyncRead, AsyncWrite, ReadBuf};
+use tokio::time;
+use uuid::Uuid;
+
+use crate::exportedobject::ExportedObject;
+use crate::{uuid_from_u16, BluetoothError, BluetoothSession, DeviceId, DBUS_METHOD_CALL_TIMEOUT};
+
+const ORG_BLUEZ_PROFILE1_NAME: &str = "org.bluez.Profile1";
+
+/// The UUID of the Serial Port Profile.
+pub const SERIAL_PORT_PROFILE_UUID: Uuid = uuid_from_u16(0x1101);
+
+/// Used to give each registered profile a unique object path.
+static NEXT_PROFILE_ID: AtomicUsize = AtomicUsize::new(0);
+
+/// A connected RFCOMM stream to a Bluetooth Classic device, returned by
+/// [`BluetoothSession::connect_rfcomm`].
+///
+/// The connection is closed and the profile unregistered when this is dropped.
+#[derive(Debug)]
+pub struct RfcommStream {
+    socket: RfcommSocket,
+    _profile: ExportedObject,
+}
+
+impl AsyncRead for RfcommStream {
+    fn poll_read(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &mut ReadBuf<'_>,
+    ) -> Poll<io::Result<()>> {
+        Pin::new(&mut self.socket).poll_read(cx, buf)
+    }
+}
+
+impl AsyncWrite for RfcommStream {
+    fn poll_write(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &[u8],
+    ) -> Poll<io::Result<usize>> {
+        Pin::new(&mut self.socket).poll_write(cx, buf)
+    }
+
+    fn poll_flush(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        Pin::new(&mut self.socket).poll_flush(cx)
+    }
+
+    fn poll_shutdown(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        Pin::new(&mut self.socket).poll_shutdown(cx)
+    }
+}
+
+/// A connected socket passed to us by BlueZ, registered with the Tokio reactor.
+///
+/// This is an `AF_BLUETOOTH` socket, so it is read and written with plain `read` and `write` calls
+/// rather than being treated as any particular kind of socket from the standard library.
+#[derive(Debug)]
+struct RfcommSocket(AsyncFd<OwnedFd>);
+
+impl RfcommSocket {
+    /// Wrap the given connected socket, putting it into non-blocking mode.
+    fn new(socket: OwnedFd) -> io::Result<Self> {
+        let fd = socket.as_raw_fd();
+        // Safe because we own the file descriptor and fcntl doesn't touch any memory.
+        let flags = unsafe { libc::fcntl(fd, libc::F_GETFL) };
+        if flags < 0 || unsafe { libc::fcntl(fd, libc::F_SETFL, flags | libc::O_NONBLOCK) } < 0 {
+            return Err(io::Error::last_os_error());
+        }
+        Ok(Self(AsyncFd::new(socket)?))
+    }
+}
+
+/// Convert the return value of a libc read or write call to a `Result`.
+fn check_length(length: libc::ssize_t) -> io::Result<usize> {
+    if length < 0 {
+        Err(io::Error::last_os_error())
+    } else {
+        Ok(length as usize)
+    }
+}
+
+impl AsyncRead for RfcommSocket {
+    fn poll_read(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &mut ReadBuf<'_>,
+    ) -> Poll<io::Result<()>> {
+        loop {
+            let mut guard = futures::ready!(self.0.poll_read_ready(cx))?;
+            let unfilled = buf.initialize_unfilled();
+            let result = guard.try_io(|socket| {
+                // Safe because the buffer is valid for writes of its length.
+                check_length(unsafe {
+                    libc::read(
+                        socket.as_raw_fd(),
+                        unfilled.as_mut_ptr() as *mut libc::c_void,
+                        unfilled.len(),
+                    )
+                })
+            });
+            match result {
+                Ok(Ok(length)) => {
+                    buf.advance(length);
+                    return Poll::Ready(Ok(()));
+                }
+                Ok(Err(e)) => return Poll::Ready(Err(e)),
+                Err(_would_block) => continue,
+            }
+        }
+    }
+}
+
+impl AsyncWrite for RfcommSocket {
+    fn poll_write(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &[u8],
+    ) -> Poll<io::Result<usize>> {
+        loop {
+            let mut guard = futures::ready!(self.0.poll_write_ready(cx))?;
+            let result = guard.try_io(|socket| {
+                // Safe because the buffer is valid for reads of its length.
+                check_length(unsafe {
+                    libc::write(
+                        socket.as_raw_fd(),
+                        buf.as_ptr() as *const libc::c_void,
+                        buf.len(),
+                    )
+                })
+            });
+            match result {
+                Ok(result) => return Poll::Ready(result),
+                Err(_would_block) => continue,
+            }
+        }
+    }
+
+    fn poll_flush(self: Pin<&mut Self>, _cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        Poll::Ready(Ok(()))
+    }
+
+    fn poll_shutdown(self: Pin<&mut Self>, _cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        // Safe because shutdown doesn't touch any memory.
+        if unsafe { libc::shutdown(self.0.as_raw_fd(), libc::SHUT_WR) } < 0 {
+            Poll::Ready(Err(io::Error::last_os_error()))
+        } else {
+            Poll::Ready(Ok(()))
+        }
+    }
+}
+
+impl BluetoothSession {
+    /// Open an RFCOMM connection to the given profile on the given Bluetooth Classic device, e.g.
+    /// [`SERIAL_PORT_PROFILE_UUID`] for a serial port.
+    ///
+    /// The device should already be paired. Note that only one client on the system can register
+    /// a given profile UUID at a time.
+    pub async fn connect_rfcomm(
+        &self,
+        device: &DeviceId,
+        profile_uuid: Uuid,
+    ) -> Result<RfcommStream, BluetoothError> {
+        let id = NEXT_PROFILE_ID.fetch_add(1, Ordering::Relaxed);
+        let profile_path: Path<'static> = format!("/bluez_async/profile{}", id).into();
+
+        let (fd_sender, fd_receiver) = oneshot::channel();
+        let mut fd_sender = Some(fd_sender);
+        let mut profile = ExportedObject::new(
+            self,
+            profile_path.clone(),
+            false,
+            "/org/bluez".into(),
+            ORG_BLUEZ_PROFILE_MANAGER1_NAME,
+            "profile",
+            move |message| Some(handle_profile_message(&message, &mut fd_sender)),
+        );
+        let mut options: PropMap = HashMap::new();
+        options.insert("Role".to_string(), Variant(Box::new("client".to_string())));
+        profile
+            .register(
+                "RegisterProfile",
+                (profile_path, profile_uuid.to_string(), options),
+                "UnregisterProfile",
+            )
+            .await?;
+
+        self.connect_profile(device, profile_uuid).await?;
+
+        // BlueZ should have called NewConnection before ConnectProfile returns, but allow some
+        // time in case it hasn't.
+        let fd = match time::timeout(DBUS_METHOD_CALL_TIMEOUT, fd_receiver).await {
+            Ok(Ok(fd)) => fd,
+            Ok(Err(_)) | Err(_) => return Err(BluetoothError::Timeout),
+        };
+        Ok(RfcommStream {
+            socket: RfcommSocket::new(fd)?,
+            _profile: profile,
+        })
+    }
+}
+
+/// Handle a method call from BlueZ to a profile object, sending the file descriptor of a new
+/// connection to the given sender, and return the reply to send.
+fn handle_profile_message(
+    message: &Message,
+    fd_sender: &mut Option<oneshot::Sender<OwnedFd>>,
+) -> Message {
+    let interface = message.interface();
+    let member = message.member();
+    match (interface.as_deref(), member.as_deref()) {
+        (Some(ORG_BLUEZ_PROFILE1_NAME), Some("NewConnection")) => {
+            match message.read3::<Path, OwnedFd, PropMap>() {
+                Ok((device, fd, _properties)) => {
+                    log::trace!("New RFCOMM connection to {}", device);
+                    if let Some(fd_sender) = fd_sender.take() {
+                        // If the receiver has been dropped then the connection attempt was
+                        // abandoned, and the file descriptor will be closed when it is dropped.
+                        let _ = fd_sender.send(fd);
+                    }
+                    message.method_return()
+                }
+                Err(e) => {
+                    log::error!("Invalid NewConnection call: {}", e);
+                    dbus::channel::default_reply(message).unwrap_or_else(|| message.method_return())
+                }
+            }
+        }
+        (Some(ORG_BLUEZ_PROFILE1_NAME), Some("RequestDisconnection"))
+        | (Some(ORG_BLUEZ_PROFILE1_NAME), Some("Release")) => message.method_return(),
+        _ => dbus::channel::default_reply(message).unwrap_or_else(|| message.method_return()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::{Read, Write};
+    use std::os::unix::io::{FromRawFd, IntoRawFd};
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+    #[test]
+    fn new_connection_sends_fd() {
+        let (socket, mut other) = std::os::unix::net::UnixStream::pair().unwrap();
+        let fd = unsafe { OwnedFd::from_raw_fd(socket.into_raw_fd()) };
+        let properties: PropMap = HashMap::new();
+        let mut message = Message::new_method_call(
+            "org.bluez",
+            "/bluez_async/profile0",
+            ORG_BLUEZ_PROFILE1_NAME,
+            "NewConnection",
+        )
+        .unwrap()
+        .append3(
+            Path::from("/org/bluez/hci0/dev_11_22_33_44_55_66"),
+            fd,
+            properties,
+        );
+        message.set_serial(1);
+
+        let (sender, mut receiver) = oneshot::channel();
+        let mut sender = Some(sender);
+        handle_profile_message(&message, &mut sender);
+
+        assert!(sender.is_none());
+        // Check that the file descriptor we received still refers to the socket.
+        let received_fd = receiver.try_recv().unwrap().unwrap();
+        let mut received =
+            unsafe { std::os::unix::net::UnixStream::from_raw_fd(received_fd.into_raw_fd()) };
+        other.write_all(&[42]).unwrap();
+        let mut buf = [0];
+        received.read_exact(&mut buf).unwrap();
+        assert_eq!(buf, [42]);
+    }
+
+    #[tokio::test]
+    async fn socket_read_write() {
+        let (socket, other) = std::os::unix::net::UnixStream::pair().unwrap();
+        let fd = unsafe { OwnedFd::from_raw_fd(socket.into_raw_fd()) };
+        let mut socket = RfcommSocket::new(fd).unwrap();
+        other.set_nonblocking(true).unwrap();
+        let mut other = tokio::net::UnixStream::from_std(other).unwrap();
+
+        socket.write_all(&[1, 2, 3]).await.unwrap();
+        let mut buf = [0; 3];
+        other.read_exact(&mut buf).await.unwrap();
+        assert_eq!(buf, [1, 2, 3]);
+
+        other.write_all(&[42]).await.unwrap();
+        let mut buf = [0];
+        socket.read_exact(&mut buf).await.unwrap();
+        assert_eq!(buf, [42]);
+
+        socket.shutdown().await.unwrap();
+        let mut rest = vec![];
+        other.read_to_end(&mut rest).await.unwrap();
+        assert!(rest.is_empty());
+    }
+}
@@ -0,0 +1,182 @@
+use futures::Stream;
+use std::collections::VecDeque;
+use std::mem;
+use std::pin::Pin;
+use std::task::{Context, Poll};
+use std::time::Duration;
+use tokio::time::{interval_at, Instant, Interval};
+
+use crate::{BluetoothEvent, DeviceEvent};
+
+/// The maximum number of events to coalesce in a single call to `poll_next` before yielding, so
+/// that a flood of advertisements can't starve other tasks.
+const MAX_EVENTS_PER_POLL: usize = 64;
+
+/// Extension trait for streams of `BluetoothEvent`s.
+pub trait BluetoothEventStreamExt: Stream<Item = BluetoothEvent> + Sized {
+    /// Coalesce frequent advertisement events (RSSI and manufacturer-specific data updates) so
+    /// that at most one of each kind is emitted per device per `window`, with the latest value.
+    ///
+    /// Other events are passed through immediately. Coalesced events are emitted at the end of each
+    /// window, so may be delayed by up to `window`.
+    fn coalesced_by_device(self, window: Duration) -> CoalescedByDevice<Self> {
+        CoalescedByDevice {
+            events: Box::pin(self),
+            window,
+            interval: None,
+            pending: vec![],
+            ready: VecDeque::new(),
+            finished: false,
+        }
+    }
+}
+
+impl<S: Stream<Item = BluetoothEvent>> BluetoothEventStreamExt for S {}
+
+/// Stream returned by [`BluetoothEventStreamExt::coalesced_by_device`].
+pub struct CoalescedByDevice<S> {
+    events: Pin<Box<S>>,
+    window: Duration,
+    /// This is created on the first poll, as it must be created within the Tokio runtime.
+    interval: Option<Interval>,
+    /// Coalesced events waiting for the end of the current window, in the order in which they were
+    /// first received.
+    pending: Vec<BluetoothEvent>,
+    /// Events ready to be returned.
+    ready: VecDeque<BluetoothEvent>,
+    finished: bool,
+}
+
+impl<S> CoalescedByDevice<S> {
+    /// Add the given event to the pending list, replacing any earlier event of the same kind for
+    /// the same device.
+    fn add_pending(&mut self, event: BluetoothEvent) {
+        if let Some(existing) = self
+            .pending
+            .iter_mut()
+            .find(|existing| same_device_and_kind(existing, &event))
+        {
+            *existing = event;
+        } else {
+            self.pending.push(event);
+        }
+    }
+
+    fn flush_pending(&mut self) {
+        self.ready.extend(self.pending.drain(..));
+    }
+}
+
+impl<S: Stream<Item = BluetoothEvent>> Stream for CoalescedByDevice<S> {
+    type Item = BluetoothEvent;
+
+    fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        let window = self.window;
+        let mut coalesced_count = 0;
+        loop {
+            if let Some(event) = self.ready.pop_front() {
+                return Poll::Ready(Some(event));
+            }
+            if self.finished {
+                return Poll::Ready(None);
+            }
+
+            let interval = self
+                .interval
+                .get_or_insert_with(|| interval_at(Instant::now() + window, window));
+            if interval.poll_tick(cx).is_ready() {
+                self.flush_pending();
+                continue;
+            }
+
+            if coalesced_count >= MAX_EVENTS_PER_POLL {
+                cx.waker().wake_by_ref();
+                return Poll::Pending;
+            }
+
+            match self.events.as_mut().poll_next(cx) {
+                Poll::Ready(Some(event)) => {
+                    if is_coalescable(&event) {
+                        self.add_pending(event);
+                        coalesced_count += 1;
+                    } else {
+                        return Poll::Ready(Some(event));
+                    }
+                }
+                Poll::Ready(None) => {
+                    self.finished = true;
+                    self.flush_pending();
+                }
+                Poll::Pending => return Poll::Pending,
+            }
+        }
+    }
+}
+
+fn is_coalescable(event: &BluetoothEvent) -> bool {
+    matches!(
+        event,
+        BluetoothEvent::Device {
+            event: DeviceEvent::RSSI { .. },
+            ..
+        } | BluetoothEvent::Device {
+            event: DeviceEvent::ManufacturerData { .. },
+            ..
+        }
+    )
+}
+
+fn same_device_and_kind(a: &BluetoothEvent, b: &BluetoothEvent) -> bool {
+    match (a, b) {
+        (
+            BluetoothEvent::Device {
+                id: id_a,
+                event: event_a,
+            },
+            BluetoothEvent::Device {
+                id: id_b,
+                event: event_b,
+            },
+        ) => id_a == id_b && mem::discriminant(event_a) == mem::discriminant(event_b),
+        _ => false,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::DeviceId;
+    use futures::stream::{self, StreamExt};
+
+    fn rssi_event(device: &DeviceId, rssi: i16) -> BluetoothEvent {
+        BluetoothEvent::Device {
+            id: device.to_owned(),
+            event: DeviceEvent::RSSI { rssi },
+        }
+    }
+
+    #[tokio::test]
+    async fn coalesce_rssi() {
+        let device1 = DeviceId::new("/org/bluez/hci0/dev_11_22_33_44_55_66");
+        let device2 = DeviceId::new("/org/bluez/hci0/dev_66_55_44_33_22_11");
+        let connected = BluetoothEvent::Device {
+            id: device1.clone(),
+            event: DeviceEvent::Connected { connected: true },
+        };
+        let events = stream::iter(vec![
+            rssi_event(&device1, 10),
+            rssi_event(&device1, 20),
+            connected.clone(),
+            rssi_event(&device2, 5),
+            rssi_event(&device1, 30),
+        ]);
+
+        assert_eq!(
+            events
+                .coalesced_by_device(Duration::from_secs(3600))
+                .collect::<Vec<_>>()
+                .await,
+            vec![connected, rssi_event(&device1, 30), rssi_event(&device2, 5)]
+        );
+    }
+}
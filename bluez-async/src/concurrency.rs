@@ -0,0 +1,36 @@
+use futures::stream::{self, StreamExt};
+use std::future::Future;
+
+/// Run the given futures with at most `max_concurrent` of them in progress at once, and return
+/// their outputs in the order in which they finished.
+///
+/// A `max_concurrent` of 0 is treated as 1, as otherwise no future would ever be polled.
+pub(crate) async fn run_concurrently<T>(
+    futures: impl IntoIterator<Item = impl Future<Output = T>>,
+    max_concurrent: usize,
+) -> Vec<T> {
+    stream::iter(futures)
+        .buffer_unordered(max_concurrent.max(1))
+        .collect()
+        .await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use futures::future::ready;
+
+    #[tokio::test]
+    async fn limited() {
+        let mut results = run_concurrently((0..5).map(ready), 2).await;
+        results.sort_unstable();
+        assert_eq!(results, vec![0, 1, 2, 3, 4]);
+    }
+
+    #[tokio::test]
+    async fn zero_max_concurrent() {
+        let mut results = run_concurrently((0..3).map(ready), 0).await;
+        results.sort_unstable();
+        assert_eq!(results, vec![0, 1, 2]);
+    }
+}
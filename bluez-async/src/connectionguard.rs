@@ -0,0 +1,59 @@
+use std::fmt::{self, Debug, Formatter};
+
+use crate::spawnondrop::spawn_on_drop;
+use crate::{BluetoothError, BluetoothSession, DeviceId};
+
+/// A connection to a Bluetooth device, returned by
+/// [`BluetoothSession::connect_guarded`](crate::BluetoothSession::connect_guarded), which
+/// disconnects from the device when it is dropped.
+///
+/// Dropping the guard only starts disconnecting in the background, and does nothing outside of a
+/// Tokio runtime. To wait for the disconnection and find out whether it succeeded, call
+/// [`close`](ConnectionGuard::close) instead.
+#[must_use = "the device is disconnected as soon as the guard is dropped"]
+pub struct ConnectionGuard {
+    session: BluetoothSession,
+    id: DeviceId,
+    /// Whether the device has already been explicitly disconnected.
+    closed: bool,
+}
+
+impl ConnectionGuard {
+    pub(crate) fn new(session: BluetoothSession, id: DeviceId) -> Self {
+        Self {
+            session,
+            id,
+            closed: false,
+        }
+    }
+
+    /// Get the ID of the connected device.
+    pub fn id(&self) -> &DeviceId {
+        &self.id
+    }
+
+    /// Disconnect from the device, and wait for the disconnection to complete.
+    pub async fn close(mut self) -> Result<(), BluetoothError> {
+        self.closed = true;
+        self.session.disconnect(&self.id).await
+    }
+}
+
+impl Debug for ConnectionGuard {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        write!(f, "ConnectionGuard({})", self.id)
+    }
+}
+
+impl Drop for ConnectionGuard {
+    fn drop(&mut self) {
+        if self.closed {
+            return;
+        }
+        let session = self.session.clone();
+        let id = self.id.clone();
+        spawn_on_drop(format!("disconnecting from {}", id), async move {
+            session.disconnect(&id).await
+        });
+    }
+}
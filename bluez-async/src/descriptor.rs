@@ -1,12 +1,16 @@
+use bluez_generated::OrgBluezGattDescriptor1Properties;
+use dbus::arg::prop_cast;
 use dbus::Path;
+use serde_derive::{Deserialize, Serialize};
 use std::fmt::{self, Display, Formatter};
 use uuid::Uuid;
 
-use crate::CharacteristicId;
+use crate::{BluetoothError, CharacteristicId};
 
 /// Opaque identifier for a GATT characteristic descriptor on a Bluetooth device.
-#[derive(Clone, Debug, Eq, Hash, Ord, PartialEq, PartialOrd)]
+#[derive(Clone, Debug, Deserialize, Eq, Hash, Ord, PartialEq, PartialOrd, Serialize)]
 pub struct DescriptorId {
+    #[serde(with = "crate::serde_path")]
     pub(crate) object_path: Path<'static>,
 }
 
@@ -18,6 +22,11 @@ impl DescriptorId {
         }
     }
 
+    /// Get the D-Bus object path of the descriptor, for use with the `dbus` crate directly.
+    pub fn path(&self) -> &Path<'static> {
+        &self.object_path
+    }
+
     /// Get the ID of the characteristic on which this descriptor was advertised.
     pub fn characteristic(&self) -> CharacteristicId {
         let index = self
@@ -28,6 +37,12 @@ impl DescriptorId {
     }
 }
 
+impl AsRef<Path<'static>> for DescriptorId {
+    fn as_ref(&self) -> &Path<'static> {
+        &self.object_path
+    }
+}
+
 impl From<DescriptorId> for Path<'static> {
     fn from(id: DescriptorId) -> Self {
         id.object_path
@@ -48,19 +63,39 @@ impl Display for DescriptorId {
 }
 
 /// Information about a GATT descriptor on a Bluetooth device.
-#[derive(Clone, Debug, Eq, PartialEq)]
+#[derive(Clone, Debug, Deserialize, Eq, PartialEq, Serialize)]
 pub struct DescriptorInfo {
     /// An opaque identifier for the descriptor on the device, including a reference to which
     /// adapter it was discovered on.
     pub id: DescriptorId,
     /// The 128-bit UUID of the descriptor.
     pub uuid: Uuid,
+    /// The ATT handle of the descriptor, if BlueZ provides it.
+    pub handle: Option<u16>,
+}
+
+impl DescriptorInfo {
+    pub(crate) fn from_properties(
+        id: DescriptorId,
+        descriptor_properties: OrgBluezGattDescriptor1Properties,
+    ) -> Result<DescriptorInfo, BluetoothError> {
+        let uuid = Uuid::parse_str(
+            descriptor_properties
+                .uuid()
+                .ok_or_else(|| BluetoothError::RequiredPropertyMissing("UUID".to_string()))?,
+        )?;
+        let handle = prop_cast(descriptor_properties.0, "Handle").copied();
+        Ok(DescriptorInfo { id, uuid, handle })
+    }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
 
+    use dbus::arg::{RefArg, Variant};
+    use std::collections::HashMap;
+
     #[test]
     fn descriptor_characteristic() {
         let characteristic_id =
@@ -70,4 +105,27 @@ mod tests {
         );
         assert_eq!(descriptor_id.characteristic(), characteristic_id);
     }
+
+    #[test]
+    fn descriptor_info_without_handle() {
+        let id = DescriptorId::new(
+            "/org/bluez/hci0/dev_11_22_33_44_55_66/service0022/char0033/desc0034",
+        );
+        let uuid = Uuid::parse_str("00002902-0000-1000-8000-00805f9b34fb").unwrap();
+        let mut properties: HashMap<String, Variant<Box<dyn RefArg>>> = HashMap::new();
+        properties.insert("UUID".to_string(), Variant(Box::new(uuid.to_string())));
+
+        assert_eq!(
+            DescriptorInfo::from_properties(
+                id.clone(),
+                OrgBluezGattDescriptor1Properties(&properties)
+            )
+            .unwrap(),
+            DescriptorInfo {
+                id,
+                uuid,
+                handle: None,
+            }
+        );
+    }
 }
@@ -1,17 +1,27 @@
 use bluez_generated::OrgBluezDevice1Properties;
-use dbus::arg::{cast, RefArg, Variant};
+use dbus::arg::{cast, prop_cast, RefArg, Variant};
 use dbus::Path;
+use serde_derive::{Deserialize, Serialize};
 use std::collections::HashMap;
 use std::fmt::{self, Display, Formatter};
+use std::str::FromStr;
 use uuid::Uuid;
 
 use crate::{AdapterId, BluetoothError, MacAddress};
 
+/// The path loss exponent for free space, used for estimating distance from RSSI.
+const FREE_SPACE_PATH_LOSS_EXPONENT: f64 = 2.0;
+
+/// The free-space path loss in dB at a distance of 1 metre at 2.4 GHz, used as the reference
+/// distance for estimating distance from RSSI.
+const REFERENCE_PATH_LOSS_DB: f64 = 40.0;
+
 /// Opaque identifier for a Bluetooth device which the system knows about. This includes a reference
 /// to which Bluetooth adapter it was discovered on, which means that any attempt to connect to it
 /// will also happen from that adapter (in case the system has more than one).
-#[derive(Clone, Debug, Eq, Hash, Ord, PartialEq, PartialOrd)]
+#[derive(Clone, Debug, Deserialize, Eq, Hash, Ord, PartialEq, PartialOrd, Serialize)]
 pub struct DeviceId {
+    #[serde(with = "crate::serde_path")]
     pub(crate) object_path: Path<'static>,
 }
 
@@ -22,6 +32,11 @@ impl DeviceId {
         }
     }
 
+    /// Get the D-Bus object path of the device, for use with the `dbus` crate directly.
+    pub fn path(&self) -> &Path<'static> {
+        &self.object_path
+    }
+
     /// Get the ID of the Bluetooth adapter on which this device was discovered, e.g. `"hci0"`.
     pub fn adapter(&self) -> AdapterId {
         let index = self
@@ -32,6 +47,12 @@ impl DeviceId {
     }
 }
 
+impl AsRef<Path<'static>> for DeviceId {
+    fn as_ref(&self) -> &Path<'static> {
+        &self.object_path
+    }
+}
+
 impl From<DeviceId> for Path<'static> {
     fn from(id: DeviceId) -> Self {
         id.object_path
@@ -51,16 +72,72 @@ impl Display for DeviceId {
     }
 }
 
+/// The type of a Bluetooth address.
+#[derive(Copy, Clone, Debug, Eq, PartialEq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum AddressType {
+    /// A public address, assigned by the manufacturer from its IEEE-registered range.
+    Public,
+    /// A random address. These are only used by Bluetooth Low Energy devices, and may change over
+    /// time if the device uses LE Privacy.
+    Random,
+}
+
+impl AddressType {
+    fn as_str(&self) -> &'static str {
+        match self {
+            Self::Public => "public",
+            Self::Random => "random",
+        }
+    }
+
+    /// Returns true if this type of address is only used by Bluetooth Low Energy devices.
+    ///
+    /// Note that a device with a public address may support LE, BR/EDR (Classic) or both.
+    pub fn is_le_only(&self) -> bool {
+        *self == Self::Random
+    }
+}
+
+impl Display for AddressType {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+impl FromStr for AddressType {
+    type Err = BluetoothError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "public" => Ok(Self::Public),
+            "random" => Ok(Self::Random),
+            _ => Err(BluetoothError::AddressTypeParseError(s.to_owned())),
+        }
+    }
+}
+
 /// Information about a Bluetooth device which was discovered.
-#[derive(Clone, Debug, Eq, PartialEq)]
+///
+/// This can be serialized, e.g. to JSON, with the advertisement data hex encoded.
+#[derive(Clone, Debug, Eq, PartialEq, Serialize)]
 pub struct DeviceInfo {
     /// An opaque identifier for the device, including a reference to which adapter it was
     /// discovered on. This can be used to connect to it.
     pub id: DeviceId,
+    /// The Bluetooth adapter through which the device was discovered, and through which it may be
+    /// connected.
+    pub adapter: AdapterId,
     /// The MAC address of the device.
     pub mac_address: MacAddress,
+    /// The type of MAC address the device uses, if available. Older versions of BlueZ don't
+    /// provide this.
+    pub address_type: Option<AddressType>,
     /// The human-readable name of the device, if available.
     pub name: Option<String>,
+    /// The alias of the device, if available. This defaults to the name of the device, but may be
+    /// set to a custom value with `BluetoothSession::set_device_alias`.
+    pub alias: Option<String>,
     /// The appearance of the device, as defined by GAP.
     pub appearance: Option<u16>,
     /// The GATT service UUIDs (if any) from the device's advertisement or service discovery.
@@ -70,20 +147,42 @@ pub struct DeviceInfo {
     pub services: Vec<Uuid>,
     /// Whether the device is currently paired with the adapter.
     pub paired: bool,
+    /// Whether the device is trusted, so that BlueZ will accept incoming connections from it
+    /// without authorization. This may be set with `BluetoothSession::set_trusted`.
+    pub trusted: bool,
     /// Whether the device is currently connected to the adapter.
     pub connected: bool,
     /// The Received Signal Strength Indicator of the device advertisement or inquiry.
     pub rssi: Option<i16>,
+    /// The transmit power level of the device advertisement or inquiry, in dBm.
+    pub tx_power: Option<i16>,
     /// Manufacturer-specific advertisement data, if any. The keys are 'manufacturer IDs'.
+    #[serde(serialize_with = "crate::serde_hex::serialize_map")]
     pub manufacturer_data: HashMap<u16, Vec<u8>>,
     /// The GATT service data from the device's advertisement, if any. This is a map from the
     /// service UUID to its data.
+    #[serde(serialize_with = "crate::serde_hex::serialize_map")]
     pub service_data: HashMap<Uuid, Vec<u8>>,
+    /// The raw flags from the device's advertisement, if available. Older versions of BlueZ don't
+    /// provide this.
+    #[serde(serialize_with = "crate::serde_hex::serialize_option")]
+    pub advertising_flags: Option<Vec<u8>>,
+    /// The raw AD structures from the device's advertisement, as a map from AD type to data.
+    /// Older versions of BlueZ don't provide this, in which case it is empty.
+    #[serde(serialize_with = "crate::serde_hex::serialize_map")]
+    pub advertising_data: HashMap<u8, Vec<u8>>,
     /// Whether service discovery has finished for the device.
     pub services_resolved: bool,
 }
 
 impl DeviceInfo {
+    /// Get the category of the device's appearance, if it has one, e.g. to choose an icon for the
+    /// device.
+    #[cfg(feature = "assigned-numbers")]
+    pub fn appearance_category(&self) -> Option<crate::Appearance> {
+        self.appearance.map(crate::Appearance::from_u16)
+    }
+
     pub(crate) fn from_properties(
         id: DeviceId,
         device_properties: OrgBluezDevice1Properties,
@@ -91,30 +190,120 @@ impl DeviceInfo {
         let mac_address = device_properties
             .address()
             .ok_or_else(|| BluetoothError::RequiredPropertyMissing("Address".to_string()))?;
+        let address_type = device_properties.address_type().and_then(|address_type| {
+            address_type
+                .parse()
+                .map_err(|err| log::warn!("Error parsing device address type: {}", err))
+                .ok()
+        });
+        let adapter = device_properties
+            .adapter()
+            .ok_or_else(|| BluetoothError::RequiredPropertyMissing("Adapter".to_string()))?;
         let services = get_services(device_properties);
         let manufacturer_data = get_manufacturer_data(device_properties).unwrap_or_default();
         let service_data = get_service_data(device_properties).unwrap_or_default();
+        let advertising_flags =
+            prop_cast::<Vec<u8>>(device_properties.0, "AdvertisingFlags").cloned();
+        let advertising_data = get_advertising_data(device_properties).unwrap_or_default();
 
         Ok(DeviceInfo {
             id,
+            adapter: AdapterId::new(adapter),
             mac_address: MacAddress(mac_address.to_owned()),
+            address_type,
             name: device_properties.name().cloned(),
+            alias: device_properties.alias().cloned(),
             appearance: device_properties.appearance(),
             services,
             paired: device_properties
                 .paired()
                 .ok_or_else(|| BluetoothError::RequiredPropertyMissing("Paired".to_string()))?,
+            trusted: device_properties
+                .trusted()
+                .ok_or_else(|| BluetoothError::RequiredPropertyMissing("Trusted".to_string()))?,
             connected: device_properties
                 .connected()
                 .ok_or_else(|| BluetoothError::RequiredPropertyMissing("Connected".to_string()))?,
             rssi: device_properties.rssi(),
+            tx_power: device_properties.tx_power(),
             manufacturer_data,
             service_data,
+            advertising_flags,
+            advertising_data,
             services_resolved: device_properties.services_resolved().ok_or_else(|| {
                 BluetoothError::RequiredPropertyMissing("ServicesResolved".to_string())
             })?,
         })
     }
+
+    /// Returns the identity address of the device if it is known, or `None` if the device is
+    /// currently known only by a private address.
+    ///
+    /// Devices using LE Privacy advertise with resolvable private addresses which change over time.
+    /// Once such a device has been bonded, BlueZ can resolve its private addresses using the
+    /// device's identity resolving key, and will update `mac_address` to the device's identity
+    /// address (either public or static random). This is stable across reconnections, so is
+    /// suitable for recognising a returning bonded device.
+    ///
+    /// If BlueZ doesn't report the address type then this also returns `None`, as it's not possible
+    /// to tell whether the address is an identity address.
+    pub fn identity_address(&self) -> Option<&MacAddress> {
+        match self.address_type? {
+            AddressType::Public => Some(&self.mac_address),
+            AddressType::Random if self.mac_address.is_static_random() => Some(&self.mac_address),
+            AddressType::Random => None,
+        }
+    }
+
+    /// Returns a human-readable label for the device, for display in a UI.
+    ///
+    /// This is the alias if it is set and not empty, otherwise the name if it is set and not empty,
+    /// otherwise the MAC address. Note that BlueZ itself defaults the alias to the name, or to the
+    /// address if there is no name.
+    pub fn display_name(&self) -> String {
+        self.alias
+            .as_deref()
+            .filter(|alias| !alias.is_empty())
+            .or_else(|| self.name.as_deref().filter(|name| !name.is_empty()))
+            .map(ToOwned::to_owned)
+            .unwrap_or_else(|| self.mac_address.to_string())
+    }
+
+    /// Returns a rough estimate of the distance to the device in metres, based on its RSSI and
+    /// transmit power, or `None` if either is unknown.
+    ///
+    /// This uses the free-space path loss model, assuming a loss of about 40 dB over the first
+    /// metre at 2.4 GHz. It is only a crude estimate: obstacles, reflections, antenna orientation
+    /// and interference can all affect the RSSI considerably.
+    pub fn estimated_distance(&self) -> Option<f64> {
+        let path_loss = f64::from(self.tx_power?) - f64::from(self.rssi?) - REFERENCE_PATH_LOSS_DB;
+        Some(10f64.powf(path_loss / (10.0 * FREE_SPACE_PATH_LOSS_EXPONENT)))
+    }
+}
+
+/// Convert an error from BlueZ's `Connect` method to a `BluetoothError`, picking out the common
+/// failure reasons which callers may want to handle differently.
+///
+/// BlueZ reports most connection failures as `org.bluez.Error.Failed` with one of a fixed set of
+/// reason strings such as `br-connection-page-timeout` or `le-connection-key-missing` as the
+/// message, so these are matched exactly.
+pub(crate) fn convert_connect_error(error: dbus::Error) -> BluetoothError {
+    match (error.name(), error.message()) {
+        (Some("org.bluez.Error.AuthenticationFailed"), _)
+        | (Some("org.bluez.Error.AuthenticationRejected"), _)
+        | (Some("org.bluez.Error.AuthenticationCanceled"), _)
+        | (Some("org.bluez.Error.Failed"), Some("br-connection-key-missing"))
+        | (Some("org.bluez.Error.Failed"), Some("le-connection-key-missing")) => {
+            BluetoothError::ConnectAuthFailed
+        }
+        (Some("org.bluez.Error.Failed"), Some("br-connection-page-timeout")) => {
+            BluetoothError::ConnectPageTimeout
+        }
+        (Some("org.bluez.Error.Failed"), Some("br-connection-refused")) => {
+            BluetoothError::ConnectRefused
+        }
+        _ => error.into(),
+    }
 }
 
 fn get_manufacturer_data(
@@ -140,6 +329,26 @@ pub(crate) fn convert_manufacturer_data(
         .collect()
 }
 
+fn get_advertising_data(
+    device_properties: OrgBluezDevice1Properties,
+) -> Option<HashMap<u8, Vec<u8>>> {
+    let advertising_data: &HashMap<u8, Variant<Box<dyn RefArg>>> =
+        prop_cast(device_properties.0, "AdvertisingData")?;
+    Some(
+        advertising_data
+            .iter()
+            .filter_map(|(&ad_type, v)| {
+                if let Some(v) = cast::<Vec<u8>>(&v.0) {
+                    Some((ad_type, v.to_owned()))
+                } else {
+                    log::warn!("Advertising data had wrong type: {:?}", &v.0);
+                    None
+                }
+            })
+            .collect(),
+    )
+}
+
 fn get_service_data(
     device_properties: OrgBluezDevice1Properties,
 ) -> Option<HashMap<Uuid, Vec<u8>>> {
@@ -188,6 +397,58 @@ fn get_services(device_properties: OrgBluezDevice1Properties) -> Vec<Uuid> {
     }
 }
 
+/// Convert an error from BlueZ's `Pair` method to a `BluetoothError`.
+pub(crate) fn convert_pair_error(error: dbus::Error) -> BluetoothError {
+    match error.name() {
+        Some("org.bluez.Error.AuthenticationFailed")
+        | Some("org.bluez.Error.AuthenticationRejected")
+        | Some("org.bluez.Error.AuthenticationCanceled")
+        | Some("org.bluez.Error.AuthenticationTimeout") => {
+            BluetoothError::PairingFailed(error.message().unwrap_or_default().to_owned())
+        }
+        _ => error.into(),
+    }
+}
+
+/// Returns whether the given error from reading or writing a GATT attribute indicates that an
+/// encrypted or authenticated link is required, so pairing may help.
+pub(crate) fn is_security_error(error: &dbus::Error) -> bool {
+    let message = error.message().unwrap_or_default().to_lowercase();
+    match error.name() {
+        Some("org.bluez.Error.NotPermitted") | Some("org.bluez.Error.NotAuthorized") => true,
+        _ => {
+            message.contains("insufficient encryption")
+                || message.contains("insufficient authentication")
+        }
+    }
+}
+
+/// Construct a device with the given fixed ID and address and no optional properties, for tests to
+/// override the fields they care about.
+#[cfg(test)]
+pub(crate) fn test_device_info() -> DeviceInfo {
+    DeviceInfo {
+        id: DeviceId::new("/org/bluez/hci0/dev_11_22_33_44_55_66"),
+        adapter: AdapterId::new("/org/bluez/hci0"),
+        mac_address: MacAddress("11:22:33:44:55:66".to_string()),
+        address_type: Some(AddressType::Public),
+        name: None,
+        alias: None,
+        appearance: None,
+        services: vec![],
+        paired: false,
+        trusted: false,
+        connected: false,
+        rssi: None,
+        tx_power: None,
+        manufacturer_data: HashMap::new(),
+        service_data: HashMap::new(),
+        advertising_flags: None,
+        advertising_data: HashMap::new(),
+        services_resolved: false,
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use crate::uuid_from_u32;
@@ -201,6 +462,32 @@ mod tests {
         assert_eq!(device_id.adapter(), adapter_id);
     }
 
+    #[test]
+    fn parse_address_type() {
+        assert_eq!(
+            "public".parse::<AddressType>().unwrap(),
+            AddressType::Public
+        );
+        assert_eq!(
+            "random".parse::<AddressType>().unwrap(),
+            AddressType::Random
+        );
+        assert!(matches!(
+            "invalid".parse::<AddressType>(),
+            Err(BluetoothError::AddressTypeParseError(string)) if string == "invalid"
+        ));
+    }
+
+    #[test]
+    fn address_type_round_trip() {
+        for address_type in &[AddressType::Public, AddressType::Random] {
+            assert_eq!(
+                address_type.to_string().parse::<AddressType>().unwrap(),
+                *address_type
+            );
+        }
+    }
+
     #[test]
     fn service_data() {
         let uuid = uuid_from_u32(0x11223344);
@@ -238,6 +525,25 @@ mod tests {
         );
     }
 
+    #[test]
+    fn advertising_data() {
+        let mut advertising_data: HashMap<u8, Variant<Box<dyn RefArg>>> = HashMap::new();
+        advertising_data.insert(0x0a, Variant(Box::new(vec![0xf4u8])));
+        let mut device_properties: HashMap<String, Variant<Box<dyn RefArg>>> = HashMap::new();
+        device_properties.insert(
+            "AdvertisingData".to_string(),
+            Variant(Box::new(advertising_data)),
+        );
+
+        let mut expected_advertising_data = HashMap::new();
+        expected_advertising_data.insert(0x0a, vec![0xf4u8]);
+
+        assert_eq!(
+            get_advertising_data(OrgBluezDevice1Properties(&device_properties)),
+            Some(expected_advertising_data)
+        );
+    }
+
     #[test]
     fn device_info_minimal() {
         let id = DeviceId::new("/org/bluez/hci0/dev_11_22_33_44_55_66");
@@ -246,7 +552,16 @@ mod tests {
             "Address".to_string(),
             Variant(Box::new("00:11:22:33:44:55".to_string())),
         );
+        device_properties.insert(
+            "AddressType".to_string(),
+            Variant(Box::new("public".to_string())),
+        );
+        device_properties.insert(
+            "Adapter".to_string(),
+            Variant(Box::new(Path::from("/org/bluez/hci0"))),
+        );
         device_properties.insert("Paired".to_string(), Variant(Box::new(false)));
+        device_properties.insert("Trusted".to_string(), Variant(Box::new(false)));
         device_properties.insert("Connected".to_string(), Variant(Box::new(false)));
         device_properties.insert("ServicesResolved".to_string(), Variant(Box::new(false)));
 
@@ -258,17 +573,165 @@ mod tests {
             DeviceInfo {
                 id,
                 mac_address: MacAddress("00:11:22:33:44:55".to_string()),
-                name: None,
-                appearance: None,
-                services: vec![],
-                paired: false,
-                connected: false,
-                rssi: None,
-                manufacturer_data: HashMap::new(),
-                service_data: HashMap::new(),
-                services_resolved: false,
+                ..test_device_info()
             }
-        )
+        );
+
+        // An unrecognised address type shouldn't hide the device.
+        device_properties.insert(
+            "AddressType".to_string(),
+            Variant(Box::new("other".to_string())),
+        );
+        let device =
+            DeviceInfo::from_properties(device.id, OrgBluezDevice1Properties(&device_properties))
+                .unwrap();
+        assert_eq!(device.address_type, None);
+        assert_eq!(device.identity_address(), None);
+    }
+
+    #[test]
+    fn identity_address() {
+        let mut device = test_device_info();
+        assert_eq!(device.identity_address(), Some(&device.mac_address));
+
+        // Resolvable private address.
+        device.address_type = Some(AddressType::Random);
+        device.mac_address = MacAddress("4A:22:33:44:55:66".to_string());
+        assert_eq!(device.identity_address(), None);
+
+        // Static random address.
+        device.mac_address = MacAddress("CA:22:33:44:55:66".to_string());
+        assert_eq!(device.identity_address(), Some(&device.mac_address));
+    }
+
+    #[test]
+    fn display_name() {
+        let mut device = test_device_info();
+        assert_eq!(device.display_name(), "11:22:33:44:55:66");
+
+        device.name = Some("Name".to_string());
+        assert_eq!(device.display_name(), "Name");
+
+        device.alias = Some("".to_string());
+        assert_eq!(device.display_name(), "Name");
+
+        device.alias = Some("Alias".to_string());
+        assert_eq!(device.display_name(), "Alias");
+    }
+
+    #[test]
+    fn estimated_distance() {
+        let mut device = DeviceInfo {
+            rssi: Some(-60),
+            ..test_device_info()
+        };
+        assert_eq!(device.estimated_distance(), None);
+
+        device.tx_power = Some(0);
+        assert_eq!(device.estimated_distance(), Some(10.0));
+
+        device.rssi = Some(-40);
+        assert_eq!(device.estimated_distance(), Some(1.0));
+
+        device.rssi = Some(-80);
+        assert_eq!(device.estimated_distance(), Some(100.0));
+
+        device.rssi = None;
+        assert_eq!(device.estimated_distance(), None);
+    }
+
+    #[test]
+    fn connect_errors() {
+        assert!(matches!(
+            convert_connect_error(dbus::Error::new_custom(
+                "org.bluez.Error.Failed",
+                "br-connection-page-timeout"
+            )),
+            BluetoothError::ConnectPageTimeout
+        ));
+        assert!(matches!(
+            convert_connect_error(dbus::Error::new_custom(
+                "org.bluez.Error.Failed",
+                "Page Timeout"
+            )),
+            BluetoothError::DbusError(_)
+        ));
+        assert!(matches!(
+            convert_connect_error(dbus::Error::new_custom(
+                "org.bluez.Error.Failed",
+                "le-connection-key-missing"
+            )),
+            BluetoothError::ConnectAuthFailed
+        ));
+        assert!(matches!(
+            convert_connect_error(dbus::Error::new_custom(
+                "org.bluez.Error.NotReady",
+                "br-connection-refused"
+            )),
+            BluetoothError::DbusError(_)
+        ));
+        assert!(matches!(
+            convert_connect_error(dbus::Error::new_custom(
+                "org.bluez.Error.Failed",
+                "br-connection-refused"
+            )),
+            BluetoothError::ConnectRefused
+        ));
+        assert!(matches!(
+            convert_connect_error(dbus::Error::new_custom(
+                "org.bluez.Error.AuthenticationFailed",
+                "Authentication Failed"
+            )),
+            BluetoothError::ConnectAuthFailed
+        ));
+        assert!(matches!(
+            convert_connect_error(dbus::Error::new_custom(
+                "org.bluez.Error.Failed",
+                "le-connection-abort-by-local"
+            )),
+            BluetoothError::DbusError(_)
+        ));
+        assert!(matches!(
+            convert_connect_error(dbus::Error::new_custom(
+                "org.freedesktop.DBus.Error.UnknownObject",
+                "Method \"Connect\" with signature \"\" on interface \"org.bluez.Device1\" doesn't exist"
+            )),
+            BluetoothError::ObjectRemoved
+        ));
+    }
+
+    #[test]
+    fn pair_errors() {
+        assert!(matches!(
+            convert_pair_error(dbus::Error::new_custom(
+                "org.bluez.Error.AuthenticationRejected",
+                "Authentication Rejected"
+            )),
+            BluetoothError::PairingFailed(message) if message == "Authentication Rejected"
+        ));
+        assert!(matches!(
+            convert_pair_error(dbus::Error::new_custom(
+                "org.bluez.Error.AlreadyExists",
+                "Already Exists"
+            )),
+            BluetoothError::DbusError(_)
+        ));
+    }
+
+    #[test]
+    fn security_errors() {
+        assert!(is_security_error(&dbus::Error::new_custom(
+            "org.bluez.Error.NotPermitted",
+            "Read not permitted"
+        )));
+        assert!(is_security_error(&dbus::Error::new_custom(
+            "org.bluez.Error.Failed",
+            "Operation failed with ATT error: 0x0f (Insufficient Encryption)"
+        )));
+        assert!(!is_security_error(&dbus::Error::new_custom(
+            "org.bluez.Error.Failed",
+            "Not connected"
+        )));
     }
 
     #[test]
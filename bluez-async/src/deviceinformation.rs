@@ -0,0 +1,139 @@
+use futures::future::join_all;
+use std::collections::HashMap;
+use uuid::Uuid;
+
+use crate::format;
+use crate::well_known::device_information;
+use crate::{BluetoothError, BluetoothSession, DeviceId};
+
+/// The contents of the standard Device Information service of a device, as returned by
+/// [`BluetoothSession::get_device_information`]. Each field is `None` if the device doesn't have
+/// the corresponding characteristic, or if reading or decoding it failed.
+#[derive(Clone, Debug, Default, Eq, PartialEq)]
+pub struct DeviceInformation {
+    /// The name of the manufacturer of the device.
+    pub manufacturer_name: Option<String>,
+    /// The model number assigned by the manufacturer.
+    pub model_number: Option<String>,
+    /// The serial number of this particular device.
+    pub serial_number: Option<String>,
+    /// The hardware revision of the device.
+    pub hardware_revision: Option<String>,
+    /// The firmware revision of the device.
+    pub firmware_revision: Option<String>,
+    /// The software revision of the device.
+    pub software_revision: Option<String>,
+    /// The raw System ID, consisting of a 40-bit manufacturer-defined identifier followed by a
+    /// 24-bit Organizationally Unique Identifier, both little-endian.
+    pub system_id: Option<Vec<u8>>,
+}
+
+impl DeviceInformation {
+    /// Construct from the values of the characteristics which were read, keyed by UUID. Any
+    /// strings which can't be decoded are logged and left as `None`.
+    fn from_values(values: &HashMap<Uuid, Vec<u8>>) -> Self {
+        let string = |uuid| {
+            let value = values.get(&uuid)?;
+            match format::decode_string(value) {
+                Ok(string) => Some(string),
+                Err(e) => {
+                    log::warn!("Error decoding Device Information {}: {}", uuid, e);
+                    None
+                }
+            }
+        };
+        Self {
+            manufacturer_name: string(device_information::MANUFACTURER_NAME),
+            model_number: string(device_information::MODEL_NUMBER),
+            serial_number: string(device_information::SERIAL_NUMBER),
+            hardware_revision: string(device_information::HARDWARE_REVISION),
+            firmware_revision: string(device_information::FIRMWARE_REVISION),
+            software_revision: string(device_information::SOFTWARE_REVISION),
+            system_id: values.get(&device_information::SYSTEM_ID).cloned(),
+        }
+    }
+}
+
+impl BluetoothSession {
+    /// Read the standard Device Information service of the given device. All characteristics which
+    /// the device has are read concurrently, and any which it doesn't have are left as `None`.
+    ///
+    /// A failure to read one characteristic doesn't prevent the others from being returned: the
+    /// error is logged and the corresponding field is left as `None`.
+    ///
+    /// Returns `BluetoothError::UUIDNotFound` if the device doesn't have the Device Information
+    /// service. Note that this generally won't work until the device is connected.
+    pub async fn get_device_information(
+        &self,
+        device: &DeviceId,
+    ) -> Result<DeviceInformation, BluetoothError> {
+        const CHARACTERISTICS: [Uuid; 7] = [
+            device_information::MANUFACTURER_NAME,
+            device_information::MODEL_NUMBER,
+            device_information::SERIAL_NUMBER,
+            device_information::HARDWARE_REVISION,
+            device_information::FIRMWARE_REVISION,
+            device_information::SOFTWARE_REVISION,
+            device_information::SYSTEM_ID,
+        ];
+
+        let service = self
+            .get_service_by_uuid(device, device_information::SERVICE)
+            .await?;
+        let characteristics = self.get_characteristics(&service.id).await?;
+        let values = join_all(
+            characteristics
+                .iter()
+                .filter(|characteristic| CHARACTERISTICS.contains(&characteristic.uuid))
+                .map(|characteristic| async move {
+                    match self.read_characteristic_value(&characteristic.id).await {
+                        Ok(value) => Some((characteristic.uuid, value)),
+                        Err(e) => {
+                            log::warn!("Error reading {}: {}", characteristic.id, e);
+                            None
+                        }
+                    }
+                }),
+        )
+        .await;
+        Ok(DeviceInformation::from_values(
+            &values.into_iter().flatten().collect(),
+        ))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn from_values() {
+        let mut values = HashMap::new();
+        values.insert(device_information::MANUFACTURER_NAME, b"Acme".to_vec());
+        values.insert(device_information::MODEL_NUMBER, b"X1\0".to_vec());
+        values.insert(device_information::SYSTEM_ID, vec![1, 2, 3, 4, 5, 6, 7, 8]);
+        assert_eq!(
+            DeviceInformation::from_values(&values),
+            DeviceInformation {
+                manufacturer_name: Some("Acme".to_string()),
+                model_number: Some("X1".to_string()),
+                system_id: Some(vec![1, 2, 3, 4, 5, 6, 7, 8]),
+                ..Default::default()
+            }
+        );
+    }
+
+    #[test]
+    fn from_values_invalid_string() {
+        let mut values = HashMap::new();
+        values.insert(device_information::SERIAL_NUMBER, vec![0xff]);
+        values.insert(device_information::MODEL_NUMBER, b"X1".to_vec());
+        assert_eq!(
+            DeviceInformation::from_values(&values),
+            DeviceInformation {
+                model_number: Some("X1".to_string()),
+                ..Default::default()
+            }
+        );
+    }
+}
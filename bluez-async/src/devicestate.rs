@@ -0,0 +1,305 @@
+use std::collections::HashMap;
+
+use crate::{BluetoothEvent, DeviceEvent, DeviceId, DisconnectReason};
+
+/// A change in the lifecycle state of a Bluetooth device, as returned by
+/// [`BluetoothSession::device_state_stream`](crate::BluetoothSession::device_state_stream).
+#[derive(Clone, Debug, Eq, PartialEq)]
+#[non_exhaustive]
+pub enum DeviceStateChange {
+    /// A new device has been discovered. This is also implied by the first change in the
+    /// connection state of a device which hadn't been seen before, so it always comes first.
+    Discovered,
+    /// The device has connected.
+    Connected,
+    /// The device's GATT services have been resolved, so its services and characteristics may now
+    /// be queried. This is always preceded by `Connected`.
+    ServicesResolved,
+    /// The device has disconnected, for the given reason if BlueZ reported one.
+    ///
+    /// Only recent versions of BlueZ report the reason, and only if BlueZ sends it before the
+    /// change to the device's `Connected` property.
+    Disconnected { reason: Option<DisconnectReason> },
+    /// The device has been removed. If it was connected then this is preceded by `Disconnected`.
+    Removed,
+}
+
+#[derive(Clone, Debug, Default)]
+struct DeviceState {
+    /// Whether the device is connected, or `None` if we haven't seen it connect or disconnect.
+    connected: Option<bool>,
+    services_resolved: bool,
+}
+
+/// Reduces low-level `BluetoothEvent`s to `DeviceStateChange`s, keeping track of the state of each
+/// device so that redundant events are dropped and missing transitions are filled in.
+#[derive(Clone, Debug, Default)]
+pub(crate) struct DeviceStateTracker {
+    devices: HashMap<DeviceId, DeviceState>,
+}
+
+impl DeviceStateTracker {
+    /// Get the state of the given device, adding it and recording the implied `Discovered` change
+    /// if it hasn't been seen before.
+    fn state(&mut self, id: &DeviceId, changes: &mut Vec<DeviceStateChange>) -> &mut DeviceState {
+        self.devices.entry(id.clone()).or_insert_with(|| {
+            changes.push(DeviceStateChange::Discovered);
+            DeviceState::default()
+        })
+    }
+
+    /// Update the state for the given event, and return the resulting state changes in order.
+    pub fn update(&mut self, event: BluetoothEvent) -> Vec<(DeviceId, DeviceStateChange)> {
+        let (id, event) = match event {
+            BluetoothEvent::Device { id, event } => (id, event),
+            _ => return vec![],
+        };
+        let mut changes = vec![];
+        match event {
+            DeviceEvent::Discovered => {
+                self.state(&id, &mut changes);
+            }
+            DeviceEvent::Connected { connected } => {
+                let state = self.state(&id, &mut changes);
+                if state.connected != Some(connected) {
+                    state.connected = Some(connected);
+                    if connected {
+                        changes.push(DeviceStateChange::Connected);
+                    } else {
+                        state.services_resolved = false;
+                        changes.push(DeviceStateChange::Disconnected { reason: None });
+                    }
+                }
+            }
+            DeviceEvent::Disconnected { reason } => {
+                let state = self.state(&id, &mut changes);
+                if state.connected != Some(false) {
+                    state.connected = Some(false);
+                    state.services_resolved = false;
+                    changes.push(DeviceStateChange::Disconnected {
+                        reason: Some(reason),
+                    });
+                }
+            }
+            DeviceEvent::ServicesResolved { services_resolved } => {
+                let state = self.state(&id, &mut changes);
+                if services_resolved && !state.services_resolved {
+                    // Services can only be resolved on a connected device, even if we haven't
+                    // seen the connected event yet.
+                    if state.connected != Some(true) {
+                        state.connected = Some(true);
+                        changes.push(DeviceStateChange::Connected);
+                    }
+                    changes.push(DeviceStateChange::ServicesResolved);
+                }
+                state.services_resolved = services_resolved;
+            }
+            DeviceEvent::Removed => {
+                if let Some(state) = self.devices.remove(&id) {
+                    if state.connected == Some(true) {
+                        changes.push(DeviceStateChange::Disconnected { reason: None });
+                    }
+                }
+                changes.push(DeviceStateChange::Removed);
+            }
+            _ => {}
+        }
+        changes
+            .into_iter()
+            .map(|change| (id.clone(), change))
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn device_event(id: &DeviceId, event: DeviceEvent) -> BluetoothEvent {
+        BluetoothEvent::Device {
+            id: id.clone(),
+            event,
+        }
+    }
+
+    fn changes(
+        tracker: &mut DeviceStateTracker,
+        events: Vec<BluetoothEvent>,
+    ) -> Vec<(DeviceId, DeviceStateChange)> {
+        events
+            .into_iter()
+            .flat_map(|event| tracker.update(event))
+            .collect()
+    }
+
+    #[test]
+    fn full_lifecycle() {
+        let id = DeviceId::new("/org/bluez/hci0/dev_11_22_33_44_55_66");
+        let mut tracker = DeviceStateTracker::default();
+        assert_eq!(
+            changes(
+                &mut tracker,
+                vec![
+                    device_event(&id, DeviceEvent::Discovered),
+                    device_event(&id, DeviceEvent::RSSI { rssi: 42 }),
+                    device_event(&id, DeviceEvent::Connected { connected: true }),
+                    device_event(
+                        &id,
+                        DeviceEvent::ServicesResolved {
+                            services_resolved: true
+                        }
+                    ),
+                    device_event(
+                        &id,
+                        DeviceEvent::ServicesResolved {
+                            services_resolved: false
+                        }
+                    ),
+                    device_event(&id, DeviceEvent::Connected { connected: false }),
+                    device_event(&id, DeviceEvent::Removed),
+                ]
+            ),
+            vec![
+                (id.clone(), DeviceStateChange::Discovered),
+                (id.clone(), DeviceStateChange::Connected),
+                (id.clone(), DeviceStateChange::ServicesResolved),
+                (id.clone(), DeviceStateChange::Disconnected { reason: None }),
+                (id, DeviceStateChange::Removed),
+            ]
+        );
+    }
+
+    #[test]
+    fn redundant_events_dropped() {
+        let id = DeviceId::new("/org/bluez/hci0/dev_11_22_33_44_55_66");
+        let mut tracker = DeviceStateTracker::default();
+        assert_eq!(
+            changes(
+                &mut tracker,
+                vec![
+                    device_event(&id, DeviceEvent::Discovered),
+                    device_event(&id, DeviceEvent::Discovered),
+                    device_event(&id, DeviceEvent::Connected { connected: true }),
+                    device_event(&id, DeviceEvent::Connected { connected: true }),
+                ]
+            ),
+            vec![
+                (id.clone(), DeviceStateChange::Discovered),
+                (id, DeviceStateChange::Connected),
+            ]
+        );
+    }
+
+    #[test]
+    fn missing_transitions_filled_in() {
+        let id = DeviceId::new("/org/bluez/hci0/dev_11_22_33_44_55_66");
+        let mut tracker = DeviceStateTracker::default();
+        assert_eq!(
+            changes(
+                &mut tracker,
+                vec![
+                    device_event(
+                        &id,
+                        DeviceEvent::ServicesResolved {
+                            services_resolved: true
+                        }
+                    ),
+                    device_event(&id, DeviceEvent::Removed),
+                ]
+            ),
+            vec![
+                (id.clone(), DeviceStateChange::Discovered),
+                (id.clone(), DeviceStateChange::Connected),
+                (id.clone(), DeviceStateChange::ServicesResolved),
+                (id.clone(), DeviceStateChange::Disconnected { reason: None }),
+                (id, DeviceStateChange::Removed),
+            ]
+        );
+    }
+
+    #[test]
+    fn connected_implies_discovered() {
+        let id = DeviceId::new("/org/bluez/hci0/dev_11_22_33_44_55_66");
+        let mut tracker = DeviceStateTracker::default();
+        assert_eq!(
+            changes(
+                &mut tracker,
+                vec![
+                    device_event(&id, DeviceEvent::Connected { connected: true }),
+                    device_event(&id, DeviceEvent::Discovered),
+                    device_event(&id, DeviceEvent::Connected { connected: false }),
+                ]
+            ),
+            vec![
+                (id.clone(), DeviceStateChange::Discovered),
+                (id.clone(), DeviceStateChange::Connected),
+                (id, DeviceStateChange::Disconnected { reason: None }),
+            ]
+        );
+    }
+
+    #[test]
+    fn devices_tracked_separately() {
+        let id1 = DeviceId::new("/org/bluez/hci0/dev_11_22_33_44_55_66");
+        let id2 = DeviceId::new("/org/bluez/hci0/dev_66_55_44_33_22_11");
+        let mut tracker = DeviceStateTracker::default();
+        assert_eq!(
+            changes(
+                &mut tracker,
+                vec![
+                    device_event(&id1, DeviceEvent::Connected { connected: true }),
+                    device_event(&id2, DeviceEvent::Connected { connected: true }),
+                    device_event(&id1, DeviceEvent::Connected { connected: false }),
+                ]
+            ),
+            vec![
+                (id1.clone(), DeviceStateChange::Discovered),
+                (id1.clone(), DeviceStateChange::Connected),
+                (id2.clone(), DeviceStateChange::Discovered),
+                (id2, DeviceStateChange::Connected),
+                (id1, DeviceStateChange::Disconnected { reason: None }),
+            ]
+        );
+    }
+
+    #[test]
+    fn disconnect_reason() {
+        let id = DeviceId::new("/org/bluez/hci0/dev_11_22_33_44_55_66");
+        let mut tracker = DeviceStateTracker::default();
+        assert_eq!(
+            changes(
+                &mut tracker,
+                vec![
+                    device_event(&id, DeviceEvent::Connected { connected: true }),
+                    device_event(
+                        &id,
+                        DeviceEvent::Disconnected {
+                            reason: DisconnectReason::Timeout
+                        }
+                    ),
+                    device_event(&id, DeviceEvent::Connected { connected: false }),
+                    device_event(&id, DeviceEvent::Connected { connected: true }),
+                    device_event(&id, DeviceEvent::Connected { connected: false }),
+                    device_event(
+                        &id,
+                        DeviceEvent::Disconnected {
+                            reason: DisconnectReason::Local
+                        }
+                    ),
+                ]
+            ),
+            vec![
+                (id.clone(), DeviceStateChange::Discovered),
+                (id.clone(), DeviceStateChange::Connected),
+                (
+                    id.clone(),
+                    DeviceStateChange::Disconnected {
+                        reason: Some(DisconnectReason::Timeout)
+                    }
+                ),
+                (id.clone(), DeviceStateChange::Connected),
+                (id, DeviceStateChange::Disconnected { reason: None }),
+            ]
+        );
+    }
+}
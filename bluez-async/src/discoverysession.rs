@@ -0,0 +1,51 @@
+use std::fmt::{self, Debug, Formatter};
+
+use crate::spawnondrop::spawn_on_drop;
+use crate::{BluetoothError, BluetoothSession};
+
+/// An ongoing scan for devices, returned by
+/// [`BluetoothSession::start_discovery_session`](crate::BluetoothSession::start_discovery_session),
+/// which stops discovery when it is dropped.
+///
+/// Dropping the guard only starts stopping discovery in the background, and does nothing outside of
+/// a Tokio runtime. To wait for discovery to stop and find out whether it succeeded, call
+/// [`stop`](DiscoverySession::stop) instead.
+#[must_use = "discovery is stopped as soon as the session is dropped"]
+pub struct DiscoverySession {
+    session: BluetoothSession,
+    /// Whether discovery has already been explicitly stopped.
+    stopped: bool,
+}
+
+impl DiscoverySession {
+    pub(crate) fn new(session: BluetoothSession) -> Self {
+        Self {
+            session,
+            stopped: false,
+        }
+    }
+
+    /// Stop scanning for devices, and wait for it to stop.
+    pub async fn stop(mut self) -> Result<(), BluetoothError> {
+        self.stopped = true;
+        self.session.stop_discovery().await
+    }
+}
+
+impl Debug for DiscoverySession {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        f.write_str("DiscoverySession")
+    }
+}
+
+impl Drop for DiscoverySession {
+    fn drop(&mut self) {
+        if self.stopped {
+            return;
+        }
+        let session = self.session.clone();
+        spawn_on_drop("stopping discovery".to_string(), async move {
+            session.stop_discovery().await
+        });
+    }
+}
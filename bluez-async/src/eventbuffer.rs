@@ -0,0 +1,153 @@
+use futures::stream::{self, Stream, StreamExt};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+use tokio::sync::broadcast::{self, error::RecvError};
+use tokio::sync::{mpsc, oneshot};
+
+/// What to do when the consumer of a buffered event stream can't keep up, and the buffer is full.
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub enum OverflowPolicy {
+    /// Stop reading D-Bus messages for the stream until the consumer has caught up, so that no
+    /// events are lost. Messages will queue up in the D-Bus connection in the meantime.
+    Block,
+    /// Discard the oldest buffered event to make room for each new one. The number of events
+    /// discarded is counted by
+    /// [`BluetoothSession::dropped_message_count`](crate::BluetoothSession::dropped_message_count).
+    DropOldest,
+}
+
+/// Spawn a task to read events from the given stream into a buffer of the given capacity, and
+/// return a stream of events from the buffer. Any events dropped due to the overflow policy are
+/// added to `dropped`.
+///
+/// The task stops as soon as the returned stream is dropped, dropping `events` with it. A
+/// `capacity` of 0 is treated as 1.
+pub(crate) fn buffer_events<T: Clone + Send + 'static>(
+    events: impl Stream<Item = T> + Send + 'static,
+    capacity: usize,
+    overflow: OverflowPolicy,
+    dropped: Arc<AtomicU64>,
+) -> impl Stream<Item = T> {
+    let capacity = capacity.max(1);
+    match overflow {
+        OverflowPolicy::Block => {
+            let (sender, receiver) = mpsc::channel(capacity);
+            tokio::spawn(async move {
+                let mut events = Box::pin(events.take_until(sender.closed()));
+                while let Some(event) = events.next().await {
+                    if sender.send(event).await.is_err() {
+                        // The receiver has been dropped.
+                        break;
+                    }
+                }
+            });
+            stream::unfold(receiver, |mut receiver| async move {
+                let event = receiver.recv().await?;
+                Some((event, receiver))
+            })
+            .left_stream()
+        }
+        OverflowPolicy::DropOldest => {
+            let (sender, receiver) = broadcast::channel(capacity);
+            // Dropped along with the returned stream, to tell the task to stop.
+            let (stop_sender, stop_receiver) = oneshot::channel::<()>();
+            tokio::spawn(async move {
+                let mut events = Box::pin(events.take_until(stop_receiver));
+                while let Some(event) = events.next().await {
+                    if sender.send(event).is_err() {
+                        // The receiver has been dropped.
+                        break;
+                    }
+                }
+            });
+            stream::unfold(
+                (receiver, stop_sender),
+                move |(mut receiver, stop_sender)| {
+                    let dropped = dropped.clone();
+                    async move {
+                        loop {
+                            match receiver.recv().await {
+                                Ok(event) => return Some((event, (receiver, stop_sender))),
+                                Err(RecvError::Lagged(skipped)) => {
+                                    dropped.fetch_add(skipped, Ordering::Relaxed);
+                                    log::warn!(
+                                        "Event stream consumer lagged, dropped {} events.",
+                                        skipped
+                                    )
+                                }
+                                Err(RecvError::Closed) => return None,
+                            }
+                        }
+                    }
+                },
+            )
+            .right_stream()
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use futures::future;
+    use std::time::Duration;
+
+    #[tokio::test]
+    async fn block_keeps_all_events() {
+        let dropped = Arc::new(AtomicU64::new(0));
+        let events = buffer_events(
+            stream::iter(0..5),
+            2,
+            OverflowPolicy::Block,
+            dropped.clone(),
+        );
+        assert_eq!(events.collect::<Vec<_>>().await, vec![0, 1, 2, 3, 4]);
+        assert_eq!(dropped.load(Ordering::Relaxed), 0);
+    }
+
+    #[tokio::test]
+    async fn drop_oldest() {
+        let dropped = Arc::new(AtomicU64::new(0));
+        let events = buffer_events(
+            stream::iter(0..5),
+            2,
+            OverflowPolicy::DropOldest,
+            dropped.clone(),
+        );
+        assert_eq!(events.collect::<Vec<_>>().await, vec![3, 4]);
+        assert_eq!(dropped.load(Ordering::Relaxed), 3);
+    }
+
+    #[tokio::test]
+    async fn zero_capacity_is_one() {
+        let dropped = Arc::new(AtomicU64::new(0));
+        let events = buffer_events(
+            stream::iter(0..5),
+            0,
+            OverflowPolicy::Block,
+            dropped.clone(),
+        );
+        assert_eq!(events.collect::<Vec<_>>().await, vec![0, 1, 2, 3, 4]);
+    }
+
+    #[tokio::test]
+    async fn task_stops_when_stream_dropped() {
+        for overflow in [OverflowPolicy::Block, OverflowPolicy::DropOldest] {
+            // The source never yields, so the task must notice the drop without an event.
+            let (source_sender, source_dropped) = oneshot::channel::<()>();
+            let source = stream::unfold(source_sender, |source_sender| async move {
+                let _source_sender = source_sender;
+                future::pending::<Option<(u32, _)>>().await
+            });
+            let events = buffer_events(source, 2, overflow, Arc::new(AtomicU64::new(0)));
+            drop(events);
+            assert!(
+                tokio::time::timeout(Duration::from_secs(1), source_dropped)
+                    .await
+                    .is_ok(),
+                "Task didn't stop for {:?}",
+                overflow
+            );
+        }
+    }
+}
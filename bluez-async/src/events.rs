@@ -2,18 +2,24 @@ use bluez_generated::{
     OrgBluezAdapter1Properties, OrgBluezDevice1Properties, OrgBluezGattCharacteristic1Properties,
     ORG_BLUEZ_ADAPTER1_NAME, ORG_BLUEZ_DEVICE1_NAME, ORG_BLUEZ_GATT_CHARACTERISTIC1_NAME,
 };
+use dbus::arg::prop_cast;
 use dbus::message::{MatchRule, SignalArgs};
 use dbus::nonblock::stdintf::org_freedesktop_dbus::{
-    ObjectManagerInterfacesAdded, PropertiesPropertiesChanged,
+    ObjectManagerInterfacesAdded, ObjectManagerInterfacesRemoved, PropertiesPropertiesChanged,
 };
+use dbus::strings::BusName;
 use dbus::{Message, Path};
 use std::collections::HashMap;
 
 use super::device::convert_manufacturer_data;
 use super::{AdapterId, CharacteristicId, DeviceId};
 
+/// The name of the signal which recent versions of BlueZ emit on a device when it disconnects.
+const DEVICE_DISCONNECTED_SIGNAL_NAME: &str = "Disconnected";
+
 /// An event relating to a Bluetooth device or adapter.
 #[derive(Clone, Debug, Eq, PartialEq)]
+#[non_exhaustive]
 pub enum BluetoothEvent {
     /// An event related to a Bluetooth adapter.
     Adapter {
@@ -36,6 +42,21 @@ pub enum BluetoothEvent {
         /// Details of the specific event.
         event: CharacteristicEvent,
     },
+    /// Properties changed on an interface which isn't otherwise handled. This is only emitted by
+    /// [`BluetoothSession::event_stream_with_unknown`](crate::BluetoothSession::event_stream_with_unknown),
+    /// and is intended for debugging.
+    Unknown {
+        /// The D-Bus object path of the object whose properties changed.
+        path: Path<'static>,
+        /// The D-Bus interface to which the properties belong.
+        interface: String,
+        /// The names of the changed properties, and their new values formatted for debugging.
+        changed: HashMap<String, String>,
+    },
+    /// A periodic heartbeat, emitted only by
+    /// [`BluetoothSession::event_stream_with_heartbeat`](crate::BluetoothSession::event_stream_with_heartbeat)
+    /// so that consumers can tell that the stream is still alive.
+    Heartbeat,
 }
 
 /// Details of an event related to a Bluetooth adapter.
@@ -46,6 +67,12 @@ pub enum AdapterEvent {
     Powered { powered: bool },
     /// The adapter has started or stopped scanning for devices.
     Discovering { discovering: bool },
+    /// The adapter has been made discoverable or not discoverable, including when the
+    /// discoverable timeout expires.
+    Discoverable { discoverable: bool },
+    /// The adapter has been made pairable or not pairable, including when the pairable timeout
+    /// expires.
+    Pairable { pairable: bool },
 }
 
 /// Details of an event related to a Bluetooth device.
@@ -56,6 +83,23 @@ pub enum DeviceEvent {
     Discovered,
     /// The device has connected or disconnected.
     Connected { connected: bool },
+    /// The device has disconnected, for the given reason. This is only emitted by recent versions
+    /// of BlueZ, and is in addition to `Connected { connected: false }`. Use
+    /// [`BluetoothSession::device_state_stream`](crate::BluetoothSession::device_state_stream) to
+    /// get a single `DeviceStateChange::Disconnected` with the reason instead.
+    Disconnected { reason: DisconnectReason },
+    /// The device's GATT services have been resolved, or are no longer resolved.
+    ServicesResolved { services_resolved: bool },
+    /// The device has been removed, e.g. because it hasn't been seen for a while or was
+    /// explicitly removed.
+    Removed,
+    /// The device has been paired or unpaired.
+    Paired { paired: bool },
+    /// The device has been bonded or unbonded, i.e. its pairing keys have been stored or removed.
+    /// This is only emitted by recent versions of BlueZ.
+    Bonded { bonded: bool },
+    /// The device has been marked as trusted or untrusted.
+    Trusted { trusted: bool },
     /// A new value is available for the RSSI of the device.
     RSSI { rssi: i16 },
     /// A new value is available for the manufacturer-specific advertisement data of the device.
@@ -64,6 +108,37 @@ pub enum DeviceEvent {
     },
 }
 
+/// The reason why a device disconnected.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+#[non_exhaustive]
+pub enum DisconnectReason {
+    /// The reason wasn't given or wasn't recognised.
+    Unknown,
+    /// The connection timed out, e.g. because the device went out of range.
+    Timeout,
+    /// The connection was closed by this host.
+    Local,
+    /// The connection was closed by the remote device.
+    Remote,
+    /// The connection was closed because authentication failed.
+    Authentication,
+    /// The connection was closed because this host was suspended.
+    Suspend,
+}
+
+impl DisconnectReason {
+    fn from_name(name: &str) -> Self {
+        match name {
+            "org.bluez.Reason.Timeout" => Self::Timeout,
+            "org.bluez.Reason.Local" => Self::Local,
+            "org.bluez.Reason.Remote" => Self::Remote,
+            "org.bluez.Reason.Authentication" => Self::Authentication,
+            "org.bluez.Reason.Suspend" => Self::Suspend,
+            _ => Self::Unknown,
+        }
+    }
+}
+
 /// Details of an event related to a GATT characteristic.
 #[derive(Clone, Debug, Eq, PartialEq)]
 #[non_exhaustive]
@@ -79,18 +154,20 @@ impl BluetoothEvent {
     ///
     /// Note that the match rules for a device will not match the device discovered event for that
     /// device, as it is considered an event for the system rather than the device itself.
-    pub(crate) fn match_rules(object: Option<impl Into<Path<'static>>>) -> Vec<MatchRule<'static>> {
-        // BusName validation just checks that the length and format is valid, so it should never
-        // fail for a constant that we know is valid.
-        let bus_name = "org.bluez".into();
-
+    pub(crate) fn match_rules(
+        bus_name: &BusName<'static>,
+        object: Option<impl Into<Path<'static>>>,
+    ) -> Vec<MatchRule<'static>> {
         let mut match_rules = vec![];
 
         // If we aren't filtering to a single device or characteristic, then match ObjectManager
         // signals so we can get events for new devices being discovered.
         if object.is_none() {
             let match_rule =
-                ObjectManagerInterfacesAdded::match_rule(Some(&bus_name), None).static_clone();
+                ObjectManagerInterfacesAdded::match_rule(Some(bus_name), None).static_clone();
+            match_rules.push(match_rule);
+            let match_rule =
+                ObjectManagerInterfacesRemoved::match_rule(Some(bus_name), None).static_clone();
             match_rules.push(match_rule);
         }
 
@@ -99,22 +176,66 @@ impl BluetoothEvent {
         // all BlueZ objects.
         let object_path = object.map(|o| o.into());
         let mut match_rule =
-            PropertiesPropertiesChanged::match_rule(Some(&bus_name), object_path.as_ref())
+            PropertiesPropertiesChanged::match_rule(Some(bus_name), object_path.as_ref())
                 .static_clone();
         match_rule.path_is_namespace = true;
         match_rules.push(match_rule);
 
+        // Match the Disconnected signal for devices, which newer versions of BlueZ emit with the
+        // reason for the disconnection.
+        let mut match_rule =
+            MatchRule::new_signal(ORG_BLUEZ_DEVICE1_NAME, DEVICE_DISCONNECTED_SIGNAL_NAME);
+        match_rule.sender = Some(bus_name.clone());
+        match_rule.path = object_path;
+        match_rule.path_is_namespace = true;
+        match_rules.push(match_rule);
+
         match_rules
     }
 
-    /// Return a list of Bluetooth events parsed from the given D-Bus message.
-    pub(crate) fn message_to_events(message: Message) -> Vec<BluetoothEvent> {
+    /// Return a match rule for PropertiesChanged signals for all BlueZ objects. This should be
+    /// combined with a filter on the first argument to only match a particular interface.
+    pub(crate) fn properties_changed_match_rule(bus_name: &BusName<'static>) -> MatchRule<'static> {
+        let mut match_rule =
+            PropertiesPropertiesChanged::match_rule(Some(bus_name), None).static_clone();
+        match_rule.path = Some("/".into());
+        match_rule.path_is_namespace = true;
+        match_rule
+    }
+
+    /// Return a list of Bluetooth events parsed from the given D-Bus message. If `include_unknown`
+    /// is true then changes to properties of unhandled interfaces are returned as
+    /// `BluetoothEvent::Unknown`, otherwise they are dropped.
+    pub(crate) fn message_to_events(
+        message: Message,
+        include_unknown: bool,
+    ) -> Vec<BluetoothEvent> {
         if let Some(properties_changed) = PropertiesPropertiesChanged::from_message(&message) {
             let object_path = message.path().unwrap().into_static();
-            Self::properties_changed_to_events(object_path, properties_changed)
+            Self::properties_changed_to_events(object_path, properties_changed, include_unknown)
         } else if let Some(interfaces_added) = ObjectManagerInterfacesAdded::from_message(&message)
         {
             Self::interfaces_added_to_events(interfaces_added)
+        } else if let Some(interfaces_removed) =
+            ObjectManagerInterfacesRemoved::from_message(&message)
+        {
+            Self::interfaces_removed_to_events(interfaces_removed)
+        } else if message.interface().as_deref() == Some(ORG_BLUEZ_DEVICE1_NAME)
+            && message.member().as_deref() == Some(DEVICE_DISCONNECTED_SIGNAL_NAME)
+        {
+            let id = DeviceId {
+                object_path: message.path().unwrap().into_static(),
+            };
+            // The arguments are the reason name and a human-readable message.
+            let reason = message
+                .get1::<String>()
+                .map_or(DisconnectReason::Unknown, |reason| {
+                    DisconnectReason::from_name(&reason)
+                });
+            vec![BluetoothEvent::Device {
+                id,
+                event: DeviceEvent::Disconnected { reason },
+            }]
         } else {
             log::info!("Unexpected message: {:?}", message);
             vec![]
@@ -140,10 +261,33 @@ impl BluetoothEvent {
         events
     }
 
+    /// Return a list of Bluetooth events parsed from an InterfacesRemoved signal.
+    fn interfaces_removed_to_events(
+        interfaces_removed: ObjectManagerInterfacesRemoved,
+    ) -> Vec<BluetoothEvent> {
+        log::trace!("InterfacesRemoved: {:?}", interfaces_removed);
+        let mut events = vec![];
+        if interfaces_removed
+            .interfaces
+            .iter()
+            .any(|interface| interface == ORG_BLUEZ_DEVICE1_NAME)
+        {
+            let id = DeviceId {
+                object_path: interfaces_removed.object,
+            };
+            events.push(BluetoothEvent::Device {
+                id,
+                event: DeviceEvent::Removed,
+            })
+        }
+        events
+    }
+
     /// Return a list of Bluetooth events parsed from a PropertiesChanged signal.
     fn properties_changed_to_events(
         object_path: Path<'static>,
         properties_changed: PropertiesPropertiesChanged,
+        include_unknown: bool,
     ) -> Vec<BluetoothEvent> {
         log::trace!(
             "PropertiesChanged for {}: {:?}",
@@ -164,10 +308,22 @@ impl BluetoothEvent {
                 }
                 if let Some(discovering) = adapter.discovering() {
                     events.push(BluetoothEvent::Adapter {
-                        id,
+                        id: id.clone(),
                         event: AdapterEvent::Discovering { discovering },
                     });
                 }
+                if let Some(discoverable) = adapter.discoverable() {
+                    events.push(BluetoothEvent::Adapter {
+                        id: id.clone(),
+                        event: AdapterEvent::Discoverable { discoverable },
+                    });
+                }
+                if let Some(pairable) = adapter.pairable() {
+                    events.push(BluetoothEvent::Adapter {
+                        id,
+                        event: AdapterEvent::Pairable { pairable },
+                    });
+                }
             }
             ORG_BLUEZ_DEVICE1_NAME => {
                 let id = DeviceId { object_path };
@@ -178,6 +334,30 @@ impl BluetoothEvent {
                         event: DeviceEvent::Connected { connected },
                     });
                 }
+                if let Some(services_resolved) = device.services_resolved() {
+                    events.push(BluetoothEvent::Device {
+                        id: id.clone(),
+                        event: DeviceEvent::ServicesResolved { services_resolved },
+                    });
+                }
+                if let Some(paired) = device.paired() {
+                    events.push(BluetoothEvent::Device {
+                        id: id.clone(),
+                        event: DeviceEvent::Paired { paired },
+                    });
+                }
+                if let Some(&bonded) = prop_cast::<bool>(changed_properties, "Bonded") {
+                    events.push(BluetoothEvent::Device {
+                        id: id.clone(),
+                        event: DeviceEvent::Bonded { bonded },
+                    });
+                }
+                if let Some(trusted) = device.trusted() {
+                    events.push(BluetoothEvent::Device {
+                        id: id.clone(),
+                        event: DeviceEvent::Trusted { trusted },
+                    });
+                }
                 if let Some(rssi) = device.rssi() {
                     events.push(BluetoothEvent::Device {
                         id: id.clone(),
@@ -205,7 +385,18 @@ impl BluetoothEvent {
                     })
                 }
             }
-            _ => {}
+            _ => {
+                if include_unknown {
+                    events.push(BluetoothEvent::Unknown {
+                        path: object_path,
+                        interface: properties_changed.interface_name.clone(),
+                        changed: changed_properties
+                            .iter()
+                            .map(|(name, value)| (name.to_owned(), format!("{:?}", value.0)))
+                            .collect(),
+                    })
+                }
+            }
         }
         events
     }
@@ -223,7 +414,7 @@ mod tests {
         let message = adapter_powered_message("/org/bluez/hci0", true);
         let id = AdapterId::new("/org/bluez/hci0");
         assert_eq!(
-            BluetoothEvent::message_to_events(message),
+            BluetoothEvent::message_to_events(message, false),
             vec![BluetoothEvent::Adapter {
                 id,
                 event: AdapterEvent::Powered { powered: true }
@@ -231,13 +422,53 @@ mod tests {
         )
     }
 
+    #[test]
+    fn adapter_discoverable() {
+        let mut changed_properties: HashMap<String, Variant<Box<dyn RefArg>>> = HashMap::new();
+        changed_properties.insert("Discoverable".to_string(), Variant(Box::new(true)));
+        let properties_changed = PropertiesPropertiesChanged {
+            interface_name: "org.bluez.Adapter1".to_string(),
+            changed_properties,
+            invalidated_properties: vec![],
+        };
+        let message = properties_changed.to_emit_message(&"/org/bluez/hci0".into());
+        let id = AdapterId::new("/org/bluez/hci0");
+        assert_eq!(
+            BluetoothEvent::message_to_events(message, false),
+            vec![BluetoothEvent::Adapter {
+                id,
+                event: AdapterEvent::Discoverable { discoverable: true }
+            }]
+        )
+    }
+
+    #[test]
+    fn adapter_pairable() {
+        let mut changed_properties: HashMap<String, Variant<Box<dyn RefArg>>> = HashMap::new();
+        changed_properties.insert("Pairable".to_string(), Variant(Box::new(false)));
+        let properties_changed = PropertiesPropertiesChanged {
+            interface_name: "org.bluez.Adapter1".to_string(),
+            changed_properties,
+            invalidated_properties: vec![],
+        };
+        let message = properties_changed.to_emit_message(&"/org/bluez/hci0".into());
+        let id = AdapterId::new("/org/bluez/hci0");
+        assert_eq!(
+            BluetoothEvent::message_to_events(message, false),
+            vec![BluetoothEvent::Adapter {
+                id,
+                event: AdapterEvent::Pairable { pairable: false }
+            }]
+        )
+    }
+
     #[test]
     fn device_rssi() {
         let rssi = 42;
         let message = device_rssi_message("/org/bluez/hci0/dev_11_22_33_44_55_66", rssi);
         let id = DeviceId::new("/org/bluez/hci0/dev_11_22_33_44_55_66");
         assert_eq!(
-            BluetoothEvent::message_to_events(message),
+            BluetoothEvent::message_to_events(message, false),
             vec![BluetoothEvent::Device {
                 id,
                 event: DeviceEvent::RSSI { rssi }
@@ -255,7 +486,7 @@ mod tests {
         );
         let id = DeviceId::new("/org/bluez/hci0/dev_11_22_33_44_55_66");
         assert_eq!(
-            BluetoothEvent::message_to_events(message),
+            BluetoothEvent::message_to_events(message, false),
             vec![BluetoothEvent::Device {
                 id,
                 event: DeviceEvent::ManufacturerData { manufacturer_data }
@@ -273,7 +504,7 @@ mod tests {
         let id =
             CharacteristicId::new("/org/bluez/hci0/dev_11_22_33_44_55_66/service0012/char0034");
         assert_eq!(
-            BluetoothEvent::message_to_events(message),
+            BluetoothEvent::message_to_events(message, false),
             vec![BluetoothEvent::Characteristic {
                 id,
                 event: CharacteristicEvent::Value { value }
@@ -286,7 +517,7 @@ mod tests {
         let message = new_device_message("/org/bluez/hci0/dev_11_22_33_44_55_66");
         let id = DeviceId::new("/org/bluez/hci0/dev_11_22_33_44_55_66");
         assert_eq!(
-            BluetoothEvent::message_to_events(message),
+            BluetoothEvent::message_to_events(message, false),
             vec![BluetoothEvent::Device {
                 id,
                 event: DeviceEvent::Discovered
@@ -294,9 +525,142 @@ mod tests {
         )
     }
 
+    #[test]
+    fn device_services_resolved() {
+        let mut changed_properties: HashMap<String, Variant<Box<dyn RefArg>>> = HashMap::new();
+        changed_properties.insert("Connected".to_string(), Variant(Box::new(true)));
+        changed_properties.insert("ServicesResolved".to_string(), Variant(Box::new(true)));
+        let properties_changed = PropertiesPropertiesChanged {
+            interface_name: "org.bluez.Device1".to_string(),
+            changed_properties,
+            invalidated_properties: vec![],
+        };
+        let message =
+            properties_changed.to_emit_message(&"/org/bluez/hci0/dev_11_22_33_44_55_66".into());
+        let id = DeviceId::new("/org/bluez/hci0/dev_11_22_33_44_55_66");
+        assert_eq!(
+            BluetoothEvent::message_to_events(message, false),
+            vec![
+                BluetoothEvent::Device {
+                    id: id.clone(),
+                    event: DeviceEvent::Connected { connected: true }
+                },
+                BluetoothEvent::Device {
+                    id,
+                    event: DeviceEvent::ServicesResolved {
+                        services_resolved: true
+                    }
+                }
+            ]
+        )
+    }
+
+    #[test]
+    fn device_paired() {
+        let mut changed_properties: HashMap<String, Variant<Box<dyn RefArg>>> = HashMap::new();
+        changed_properties.insert("Paired".to_string(), Variant(Box::new(true)));
+        changed_properties.insert("Bonded".to_string(), Variant(Box::new(true)));
+        changed_properties.insert("Trusted".to_string(), Variant(Box::new(false)));
+        let properties_changed = PropertiesPropertiesChanged {
+            interface_name: "org.bluez.Device1".to_string(),
+            changed_properties,
+            invalidated_properties: vec![],
+        };
+        let message =
+            properties_changed.to_emit_message(&"/org/bluez/hci0/dev_11_22_33_44_55_66".into());
+        let id = DeviceId::new("/org/bluez/hci0/dev_11_22_33_44_55_66");
+        assert_eq!(
+            BluetoothEvent::message_to_events(message, false),
+            vec![
+                BluetoothEvent::Device {
+                    id: id.clone(),
+                    event: DeviceEvent::Paired { paired: true }
+                },
+                BluetoothEvent::Device {
+                    id: id.clone(),
+                    event: DeviceEvent::Bonded { bonded: true }
+                },
+                BluetoothEvent::Device {
+                    id,
+                    event: DeviceEvent::Trusted { trusted: false }
+                }
+            ]
+        )
+    }
+
+    #[test]
+    fn device_removed() {
+        let interfaces_removed = ObjectManagerInterfacesRemoved {
+            object: "/org/bluez/hci0/dev_11_22_33_44_55_66".into(),
+            interfaces: vec![
+                "org.freedesktop.DBus.Properties".to_string(),
+                "org.bluez.Device1".to_string(),
+            ],
+        };
+        let message = interfaces_removed.to_emit_message(&"/".into());
+        let id = DeviceId::new("/org/bluez/hci0/dev_11_22_33_44_55_66");
+        assert_eq!(
+            BluetoothEvent::message_to_events(message, false),
+            vec![BluetoothEvent::Device {
+                id,
+                event: DeviceEvent::Removed
+            }]
+        )
+    }
+
+    #[test]
+    fn device_disconnected() {
+        let message = Message::new_signal(
+            "/org/bluez/hci0/dev_11_22_33_44_55_66",
+            "org.bluez.Device1",
+            "Disconnected",
+        )
+        .unwrap()
+        .append2("org.bluez.Reason.Timeout", "Connection timeout");
+        let id = DeviceId::new("/org/bluez/hci0/dev_11_22_33_44_55_66");
+        let match_rules = BluetoothEvent::match_rules(&"org.bluez".into(), Some(id.clone()));
+        assert!(match_rules.iter().any(|rule| rule.matches(&message)));
+        assert_eq!(
+            BluetoothEvent::message_to_events(message, false),
+            vec![BluetoothEvent::Device {
+                id,
+                event: DeviceEvent::Disconnected {
+                    reason: DisconnectReason::Timeout
+                }
+            }]
+        )
+    }
+
+    #[test]
+    fn unknown_interface() {
+        let mut changed_properties: HashMap<String, Variant<Box<dyn RefArg>>> = HashMap::new();
+        changed_properties.insert("Percentage".to_string(), Variant(Box::new(42u8)));
+        let properties_changed = PropertiesPropertiesChanged {
+            interface_name: "org.bluez.Battery1".to_string(),
+            changed_properties,
+            invalidated_properties: vec![],
+        };
+        let path = "/org/bluez/hci0/dev_11_22_33_44_55_66";
+        let message = properties_changed.to_emit_message(&path.into());
+        assert_eq!(
+            BluetoothEvent::message_to_events(message.duplicate().unwrap(), false),
+            vec![]
+        );
+        let mut changed = HashMap::new();
+        changed.insert("Percentage".to_string(), "42".to_string());
+        assert_eq!(
+            BluetoothEvent::message_to_events(message, true),
+            vec![BluetoothEvent::Unknown {
+                path: path.into(),
+                interface: "org.bluez.Battery1".to_string(),
+                changed
+            }]
+        )
+    }
+
     #[test]
     fn match_rules_all() {
-        let match_rules = BluetoothEvent::match_rules(None::<DeviceId>);
+        let match_rules = BluetoothEvent::match_rules(&"org.bluez".into(), None::<DeviceId>);
 
         let message = new_device_message("/org/bluez/hci0/dev_11_22_33_44_55_66");
         assert_eq!(match_rules.iter().any(|rule| rule.matches(&message)), true);
@@ -317,7 +681,7 @@ mod tests {
     #[test]
     fn match_rules_device() {
         let id = DeviceId::new("/org/bluez/hci0/dev_11_22_33_44_55_66");
-        let match_rules = BluetoothEvent::match_rules(Some(id));
+        let match_rules = BluetoothEvent::match_rules(&"org.bluez".into(), Some(id));
 
         let message = new_device_message("/org/bluez/hci0/dev_11_22_33_44_55_66");
         assert_eq!(match_rules.iter().any(|rule| rule.matches(&message)), false);
@@ -338,7 +702,7 @@ mod tests {
     #[test]
     fn match_rules_service() {
         let id = ServiceId::new("/org/bluez/hci0/dev_11_22_33_44_55_66/service0012");
-        let match_rules = BluetoothEvent::match_rules(Some(id));
+        let match_rules = BluetoothEvent::match_rules(&"org.bluez".into(), Some(id));
 
         let message = new_device_message("/org/bluez/hci0/dev_11_22_33_44_55_66");
         assert_eq!(match_rules.iter().any(|rule| rule.matches(&message)), false);
@@ -360,7 +724,7 @@ mod tests {
     fn match_rules_characteristic() {
         let id =
             CharacteristicId::new("/org/bluez/hci0/dev_11_22_33_44_55_66/service0012/char0034");
-        let match_rules = BluetoothEvent::match_rules(Some(id));
+        let match_rules = BluetoothEvent::match_rules(&"org.bluez".into(), Some(id));
 
         let message = new_device_message("/org/bluez/hci0/dev_11_22_33_44_55_66");
         assert_eq!(match_rules.iter().any(|rule| rule.matches(&message)), false);
@@ -0,0 +1,129 @@
+//! Support for exporting objects on our D-Bus connection for BlueZ to call, such as pairing agents
+//! and advertisements, and registering them with one of BlueZ's manager interfaces.
+
+use dbus::arg::AppendAll;
+use dbus::channel::{MatchingReceiver, Sender, Token};
+use dbus::message::MatchRule;
+use dbus::nonblock::{Proxy, SyncConnection};
+use dbus::{Message, Path};
+use std::fmt::{self, Debug, Formatter};
+use std::sync::Arc;
+
+use crate::spawnondrop::spawn_on_drop;
+use crate::{BluetoothSession, DBUS_METHOD_CALL_TIMEOUT};
+
+/// An object (or tree of objects) which we have exported on the D-Bus connection, and possibly
+/// registered with a BlueZ manager interface.
+///
+/// When this is dropped the object stops receiving method calls and, if it was registered, is
+/// unregistered from the manager.
+pub(crate) struct ExportedObject {
+    path: Path<'static>,
+    token: Token,
+    manager: Proxy<'static, Arc<SyncConnection>>,
+    manager_interface: &'static str,
+    /// The method to call on the manager to unregister the object, once it has been registered.
+    unregister_method: Option<&'static str>,
+    /// What kind of object this is, for log messages.
+    description: &'static str,
+}
+
+impl ExportedObject {
+    /// Start receiving method calls to the object at the given path, or to any object under it if
+    /// `path_is_namespace` is true.
+    ///
+    /// Each method call is passed to `handler`, which should return the reply to send, or `None`
+    /// if it will send the reply itself later. The object may then be registered with the given
+    /// manager interface on the BlueZ object at `manager_path` by calling `register`.
+    pub fn new(
+        session: &BluetoothSession,
+        path: Path<'static>,
+        path_is_namespace: bool,
+        manager_path: Path<'static>,
+        manager_interface: &'static str,
+        description: &'static str,
+        mut handler: impl FnMut(Message) -> Option<Message> + Send + 'static,
+    ) -> Self {
+        let mut match_rule = MatchRule::new_method_call();
+        match_rule.path = Some(path.clone());
+        match_rule.path_is_namespace = path_is_namespace;
+        let token = session.connection.start_receive(
+            match_rule,
+            Box::new(move |message, connection| {
+                if let Some(reply) = handler(message) {
+                    if connection.send(reply).is_err() {
+                        log::error!("Error sending reply to {} method call", description);
+                    }
+                }
+                true
+            }),
+        );
+        let manager = Proxy::new(
+            session.service_name.clone(),
+            manager_path,
+            DBUS_METHOD_CALL_TIMEOUT,
+            session.connection.clone(),
+        );
+        Self {
+            path,
+            token,
+            manager,
+            manager_interface,
+            unregister_method: None,
+            description,
+        }
+    }
+
+    /// Get the object path at which the object is exported.
+    pub fn path(&self) -> &Path<'static> {
+        &self.path
+    }
+
+    /// Get a proxy for the BlueZ manager object with which the object is registered.
+    pub fn manager(&self) -> &Proxy<'static, Arc<SyncConnection>> {
+        &self.manager
+    }
+
+    /// Register the object by calling the given method on the manager with the given arguments.
+    /// If this succeeds then it will be unregistered with `unregister_method` on drop, which is
+    /// passed just the object path.
+    pub async fn register(
+        &mut self,
+        register_method: &str,
+        args: impl AppendAll,
+        unregister_method: &'static str,
+    ) -> Result<(), dbus::Error> {
+        self.manager
+            .method_call::<(), _, _, _>(self.manager_interface, register_method, args)
+            .await?;
+        self.unregister_method = Some(unregister_method);
+        Ok(())
+    }
+}
+
+impl Debug for ExportedObject {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        write!(f, "ExportedObject({})", self.path)
+    }
+}
+
+impl Drop for ExportedObject {
+    fn drop(&mut self) {
+        self.manager.connection.stop_receive(self.token);
+        let unregister_method = match self.unregister_method {
+            Some(unregister_method) => unregister_method,
+            None => return,
+        };
+        let manager = self.manager.clone();
+        let manager_interface = self.manager_interface;
+        let path = self.path.clone();
+        spawn_on_drop(
+            format!("unregistering {} {}", self.description, path),
+            async move {
+                manager
+                    .method_call::<(), _, _, _>(manager_interface, unregister_method, (path,))
+                    .await
+            },
+        );
+    }
+}
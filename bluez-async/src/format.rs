@@ -0,0 +1,641 @@
+//! Decoders for the standard formats of GATT characteristic values, as described by the
+//! Characteristic Presentation Format descriptor (0x2904).
+
+use std::convert::{TryFrom, TryInto};
+use thiserror::Error;
+
+/// The format of a characteristic value, as specified by a Characteristic Presentation Format
+/// descriptor.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+#[repr(u8)]
+pub enum FormatType {
+    Boolean = 0x01,
+    Uint2 = 0x02,
+    Uint4 = 0x03,
+    Uint8 = 0x04,
+    Uint12 = 0x05,
+    Uint16 = 0x06,
+    Uint24 = 0x07,
+    Uint32 = 0x08,
+    Uint48 = 0x09,
+    Uint64 = 0x0a,
+    Uint128 = 0x0b,
+    Sint8 = 0x0c,
+    Sint12 = 0x0d,
+    Sint16 = 0x0e,
+    Sint24 = 0x0f,
+    Sint32 = 0x10,
+    Sint48 = 0x11,
+    Sint64 = 0x12,
+    Sint128 = 0x13,
+    Float32 = 0x14,
+    Float64 = 0x15,
+    /// IEEE-11073 16-bit SFLOAT.
+    SFloat = 0x16,
+    /// IEEE-11073 32-bit FLOAT.
+    Float = 0x17,
+    /// IEEE-20601 format.
+    Duint16 = 0x18,
+    Utf8s = 0x19,
+    Utf16s = 0x1a,
+    Struct = 0x1b,
+}
+
+impl TryFrom<u8> for FormatType {
+    type Error = FormatError;
+
+    fn try_from(value: u8) -> Result<Self, Self::Error> {
+        Ok(match value {
+            0x01 => Self::Boolean,
+            0x02 => Self::Uint2,
+            0x03 => Self::Uint4,
+            0x04 => Self::Uint8,
+            0x05 => Self::Uint12,
+            0x06 => Self::Uint16,
+            0x07 => Self::Uint24,
+            0x08 => Self::Uint32,
+            0x09 => Self::Uint48,
+            0x0a => Self::Uint64,
+            0x0b => Self::Uint128,
+            0x0c => Self::Sint8,
+            0x0d => Self::Sint12,
+            0x0e => Self::Sint16,
+            0x0f => Self::Sint24,
+            0x10 => Self::Sint32,
+            0x11 => Self::Sint48,
+            0x12 => Self::Sint64,
+            0x13 => Self::Sint128,
+            0x14 => Self::Float32,
+            0x15 => Self::Float64,
+            0x16 => Self::SFloat,
+            0x17 => Self::Float,
+            0x18 => Self::Duint16,
+            0x19 => Self::Utf8s,
+            0x1a => Self::Utf16s,
+            0x1b => Self::Struct,
+            _ => return Err(FormatError::UnsupportedFormat(value)),
+        })
+    }
+}
+
+/// An error decoding a GATT characteristic value or presentation format.
+#[derive(Clone, Debug, Error, Eq, PartialEq)]
+pub enum FormatError {
+    /// The value being decoded wasn't the expected length.
+    #[error("Wrong length {length}, expected {expected_length}")]
+    WrongLength {
+        length: usize,
+        expected_length: usize,
+    },
+    /// The value being decoded was a length which isn't valid for its type.
+    #[error("Invalid length {0}")]
+    InvalidLength(usize),
+    /// The presentation format specifies a format which isn't supported.
+    #[error("Unsupported presentation format 0x{0:02x}")]
+    UnsupportedFormat(u8),
+    /// A string value wasn't valid UTF-8.
+    #[error("Invalid UTF-8 string")]
+    InvalidUtf8,
+    /// The value was expected to be a number but wasn't.
+    #[error("Value is not a number")]
+    NotNumeric,
+}
+
+/// The contents of a Characteristic Presentation Format descriptor, which describes how the value
+/// of a characteristic should be interpreted.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub struct PresentationFormat {
+    /// The format of the value, as assigned by the Bluetooth SIG.
+    pub format: u8,
+    /// The base 10 exponent by which integer values should be scaled.
+    pub exponent: i8,
+    /// The unit of the value, as a Bluetooth SIG assigned number.
+    pub unit: u16,
+    /// The organization which defines the `description`.
+    pub namespace: u8,
+    /// A description of the value, defined by the `namespace`.
+    pub description: u16,
+}
+
+impl PresentationFormat {
+    /// Get the format of the value, if it is one which is known.
+    pub fn format_type(&self) -> Result<FormatType, FormatError> {
+        FormatType::try_from(self.format)
+    }
+
+    /// Parse the value of a Characteristic Presentation Format descriptor.
+    pub fn parse(value: &[u8]) -> Result<Self, FormatError> {
+        check_length(value, 7)?;
+        Ok(Self {
+            format: value[0],
+            exponent: value[1] as i8,
+            unit: u16::from_le_bytes(value[2..4].try_into().unwrap()),
+            namespace: value[4],
+            description: u16::from_le_bytes(value[5..7].try_into().unwrap()),
+        })
+    }
+
+    /// Decode a characteristic value according to this format. Integer values with a non-zero
+    /// exponent are scaled and returned as `CharacteristicValue::Float`.
+    pub fn decode(&self, value: &[u8]) -> Result<CharacteristicValue, FormatError> {
+        let decoded = match self.format_type()? {
+            FormatType::Boolean => {
+                check_length(value, 1)?;
+                return Ok(CharacteristicValue::Boolean(value[0] != 0));
+            }
+            FormatType::Uint8 => CharacteristicValue::Unsigned(decode_unsigned(value, 1)?),
+            FormatType::Uint16 => CharacteristicValue::Unsigned(decode_unsigned(value, 2)?),
+            FormatType::Uint24 => CharacteristicValue::Unsigned(decode_unsigned(value, 3)?),
+            FormatType::Uint32 => CharacteristicValue::Unsigned(decode_unsigned(value, 4)?),
+            FormatType::Uint48 => CharacteristicValue::Unsigned(decode_unsigned(value, 6)?),
+            FormatType::Uint64 => CharacteristicValue::Unsigned(decode_unsigned(value, 8)?),
+            FormatType::Sint8 => CharacteristicValue::Signed(decode_signed(value, 1)?),
+            FormatType::Sint16 => CharacteristicValue::Signed(decode_signed(value, 2)?),
+            FormatType::Sint24 => CharacteristicValue::Signed(decode_signed(value, 3)?),
+            FormatType::Sint32 => CharacteristicValue::Signed(decode_signed(value, 4)?),
+            FormatType::Sint48 => CharacteristicValue::Signed(decode_signed(value, 6)?),
+            FormatType::Sint64 => CharacteristicValue::Signed(decode_signed(value, 8)?),
+            FormatType::Float32 => {
+                check_length(value, 4)?;
+                return Ok(CharacteristicValue::Float(
+                    f32::from_le_bytes(value.try_into().unwrap()).into(),
+                ));
+            }
+            FormatType::Float64 => {
+                check_length(value, 8)?;
+                return Ok(CharacteristicValue::Float(f64::from_le_bytes(
+                    value.try_into().unwrap(),
+                )));
+            }
+            FormatType::SFloat => {
+                return Ok(CharacteristicValue::Float(decode_sfloat(value)?.into()))
+            }
+            FormatType::Float => return Ok(CharacteristicValue::Float(decode_float(value)?)),
+            FormatType::Utf8s => {
+                let string = std::str::from_utf8(value).map_err(|_| FormatError::InvalidUtf8)?;
+                return Ok(CharacteristicValue::String(string.to_owned()));
+            }
+            _ => return Err(FormatError::UnsupportedFormat(self.format)),
+        };
+        if self.exponent == 0 {
+            Ok(decoded)
+        } else {
+            // Integer formats are the only ones which reach here, so this can't fail.
+            let number = decoded.as_f64().unwrap();
+            Ok(CharacteristicValue::Float(scale(
+                number,
+                self.exponent.into(),
+            )))
+        }
+    }
+}
+
+/// The value of a GATT characteristic, decoded according to its presentation format.
+#[derive(Clone, Debug, PartialEq)]
+pub enum CharacteristicValue {
+    Boolean(bool),
+    Unsigned(u64),
+    Signed(i64),
+    Float(f64),
+    String(String),
+    /// The characteristic has no presentation format, so the raw bytes of its value are given.
+    Raw(Vec<u8>),
+}
+
+impl CharacteristicValue {
+    /// Get the value as a floating-point number, if it is numeric.
+    pub fn as_f64(&self) -> Option<f64> {
+        match *self {
+            CharacteristicValue::Unsigned(value) => Some(value as f64),
+            CharacteristicValue::Signed(value) => Some(value as f64),
+            CharacteristicValue::Float(value) => Some(value),
+            _ => None,
+        }
+    }
+}
+
+/// Parse the value of a Valid Range descriptor into its inclusive lower and upper bounds, which are
+/// encoded in the same format as the characteristic value.
+pub fn parse_valid_range(value: &[u8]) -> Result<(Vec<u8>, Vec<u8>), FormatError> {
+    let (lower, upper) = value.split_at(value.len() / 2);
+    if lower.is_empty() || lower.len() != upper.len() {
+        return Err(FormatError::InvalidLength(value.len()));
+    }
+    Ok((lower.to_owned(), upper.to_owned()))
+}
+
+/// Parse the value of a Characteristic Aggregate Format descriptor into the list of attribute
+/// handles of the Characteristic Presentation Format descriptors which it refers to, in order.
+pub fn parse_aggregate_format(value: &[u8]) -> Result<Vec<u16>, FormatError> {
+    let handles = value.chunks_exact(2);
+    if !handles.remainder().is_empty() {
+        return Err(FormatError::InvalidLength(value.len()));
+    }
+    Ok(handles
+        .map(|handle| u16::from_le_bytes([handle[0], handle[1]]))
+        .collect())
+}
+
+/// A decoded value of the standard Heart Rate Measurement characteristic.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct HeartRateMeasurement {
+    /// The heart rate in beats per minute.
+    pub bpm: u16,
+    /// Whether the sensor is in contact with the skin, or `None` if the sensor doesn't support
+    /// detecting this.
+    pub contact: Option<bool>,
+    /// The accumulated energy expended in kilojoules, if included.
+    pub energy: Option<u16>,
+    /// The intervals between consecutive beats, in units of 1/1024 seconds. This may be empty.
+    pub rr_intervals: Vec<u16>,
+}
+
+impl HeartRateMeasurement {
+    const FLAG_BPM_U16: u8 = 0x01;
+    const FLAG_CONTACT_DETECTED: u8 = 0x02;
+    const FLAG_CONTACT_SUPPORTED: u8 = 0x04;
+    const FLAG_ENERGY: u8 = 0x08;
+    const FLAG_RR_INTERVALS: u8 = 0x10;
+
+    /// Parse the value of a Heart Rate Measurement characteristic, e.g. from a notification.
+    pub fn parse(value: &[u8]) -> Result<Self, FormatError> {
+        let invalid_length = || FormatError::InvalidLength(value.len());
+        let (&flags, mut rest) = value.split_first().ok_or_else(invalid_length)?;
+        let take_u16 = |rest: &mut &[u8]| {
+            if rest.len() < 2 {
+                return Err(invalid_length());
+            }
+            let (bytes, remaining) = rest.split_at(2);
+            *rest = remaining;
+            Ok(u16::from_le_bytes(bytes.try_into().unwrap()))
+        };
+
+        let bpm = if flags & Self::FLAG_BPM_U16 != 0 {
+            take_u16(&mut rest)?
+        } else {
+            let (&bpm, remaining) = rest.split_first().ok_or_else(invalid_length)?;
+            rest = remaining;
+            bpm.into()
+        };
+        let contact = if flags & Self::FLAG_CONTACT_SUPPORTED != 0 {
+            Some(flags & Self::FLAG_CONTACT_DETECTED != 0)
+        } else {
+            None
+        };
+        let energy = if flags & Self::FLAG_ENERGY != 0 {
+            Some(take_u16(&mut rest)?)
+        } else {
+            None
+        };
+        let mut rr_intervals = vec![];
+        if flags & Self::FLAG_RR_INTERVALS != 0 {
+            while !rest.is_empty() {
+                rr_intervals.push(take_u16(&mut rest)?);
+            }
+        }
+        if !rest.is_empty() {
+            return Err(invalid_length());
+        }
+
+        Ok(Self {
+            bpm,
+            contact,
+            energy,
+            rr_intervals,
+        })
+    }
+}
+
+/// Decode a temperature in ºC, encoded as a signed 16-bit integer with a resolution of 0.01 degrees
+/// as used by the standard Temperature characteristic.
+pub fn decode_temperature(value: &[u8]) -> Result<f32, FormatError> {
+    Ok(decode_signed(value, 2)? as f32 / 100.0)
+}
+
+/// Decode a percentage encoded as an unsigned 8-bit integer, as used by the standard Battery Level
+/// characteristic.
+pub fn decode_percentage(value: &[u8]) -> Result<u8, FormatError> {
+    check_length(value, 1)?;
+    Ok(value[0])
+}
+
+/// Decode a UTF-8 string, as used by characteristics such as Manufacturer Name String. Trailing
+/// null bytes, which some devices use as padding, are removed.
+pub fn decode_string(value: &[u8]) -> Result<String, FormatError> {
+    let string = std::str::from_utf8(value).map_err(|_| FormatError::InvalidUtf8)?;
+    Ok(string.trim_end_matches('\0').to_owned())
+}
+
+/// Decode a UTF-8 string like [`decode_string`], but replace any invalid sequences with
+/// `U+FFFD REPLACEMENT CHARACTER` rather than failing.
+pub fn decode_string_lossy(value: &[u8]) -> String {
+    String::from_utf8_lossy(value)
+        .trim_end_matches('\0')
+        .to_owned()
+}
+
+/// Decode a 16-bit IEEE-11073 SFLOAT, with a 4-bit exponent and 12-bit mantissa.
+pub fn decode_sfloat(value: &[u8]) -> Result<f32, FormatError> {
+    check_length(value, 2)?;
+    let raw = u16::from_le_bytes(value.try_into().unwrap());
+    Ok(match raw {
+        0x07ff..=0x0801 => f32::NAN,
+        0x07fe => f32::INFINITY,
+        0x0802 => f32::NEG_INFINITY,
+        _ => {
+            // Sign-extend the 4-bit exponent and 12-bit mantissa.
+            let exponent = (raw as i16) >> 12;
+            let mantissa = ((raw << 4) as i16) >> 4;
+            scale(mantissa.into(), exponent.into()) as f32
+        }
+    })
+}
+
+/// Decode a 32-bit IEEE-11073 FLOAT, with an 8-bit exponent and 24-bit mantissa.
+pub fn decode_float(value: &[u8]) -> Result<f64, FormatError> {
+    check_length(value, 4)?;
+    let raw = u32::from_le_bytes(value.try_into().unwrap());
+    Ok(match raw {
+        0x007fffff..=0x00800001 => f64::NAN,
+        0x007ffffe => f64::INFINITY,
+        0x00800002 => f64::NEG_INFINITY,
+        _ => {
+            // Sign-extend the 8-bit exponent and 24-bit mantissa.
+            let exponent = (raw as i32) >> 24;
+            let mantissa = ((raw << 8) as i32) >> 8;
+            scale(mantissa.into(), exponent)
+        }
+    })
+}
+
+/// Multiply the given value by 10 to the power of the given exponent. Negative exponents are
+/// applied by division so that values such as 2100 * 10^-2 come out exact.
+fn scale(value: f64, exponent: i32) -> f64 {
+    if exponent < 0 {
+        value / 10f64.powi(-exponent)
+    } else {
+        value * 10f64.powi(exponent)
+    }
+}
+
+fn decode_unsigned(value: &[u8], length: usize) -> Result<u64, FormatError> {
+    check_length(value, length)?;
+    let mut bytes = [0; 8];
+    bytes[..length].copy_from_slice(value);
+    Ok(u64::from_le_bytes(bytes))
+}
+
+fn decode_signed(value: &[u8], length: usize) -> Result<i64, FormatError> {
+    let unsigned = decode_unsigned(value, length)?;
+    let shift = 64 - length * 8;
+    Ok(((unsigned << shift) as i64) >> shift)
+}
+
+fn check_length(value: &[u8], expected_length: usize) -> Result<(), FormatError> {
+    if value.len() != expected_length {
+        Err(FormatError::WrongLength {
+            length: value.len(),
+            expected_length,
+        })
+    } else {
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn format(format: FormatType, exponent: i8) -> PresentationFormat {
+        PresentationFormat {
+            format: format as u8,
+            exponent,
+            unit: 0x272f,
+            namespace: 1,
+            description: 0,
+        }
+    }
+
+    #[test]
+    fn parse_presentation_format() {
+        assert_eq!(
+            PresentationFormat::parse(&[0x0e, 0xfe, 0x2f, 0x27, 0x01, 0x00, 0x00]),
+            Ok(format(FormatType::Sint16, -2))
+        );
+        assert_eq!(
+            format(FormatType::Sint16, -2).format_type(),
+            Ok(FormatType::Sint16)
+        );
+        assert_eq!(
+            PresentationFormat::parse(&[0x0e, 0xfe]),
+            Err(FormatError::WrongLength {
+                length: 2,
+                expected_length: 7
+            })
+        );
+    }
+
+    #[test]
+    fn decode_with_format() {
+        assert_eq!(
+            format(FormatType::Sint16, 0).decode(&[0xfe, 0xff]),
+            Ok(CharacteristicValue::Signed(-2))
+        );
+        assert_eq!(
+            format(FormatType::Uint24, 0).decode(&[0x01, 0x02, 0x03]),
+            Ok(CharacteristicValue::Unsigned(0x030201))
+        );
+        assert_eq!(
+            format(FormatType::Sint16, -2).decode(&[0x34, 0x08]),
+            Ok(CharacteristicValue::Float(21.0))
+        );
+        assert_eq!(
+            format(FormatType::Utf8s, 0).decode(b"abc"),
+            Ok(CharacteristicValue::String("abc".to_string()))
+        );
+        assert_eq!(
+            format(FormatType::Struct, 0).decode(&[]),
+            Err(FormatError::UnsupportedFormat(0x1b))
+        );
+        assert_eq!(
+            PresentationFormat {
+                format: 0x42,
+                exponent: 0,
+                unit: 0,
+                namespace: 0,
+                description: 0
+            }
+            .decode(&[]),
+            Err(FormatError::UnsupportedFormat(0x42))
+        );
+    }
+
+    #[test]
+    fn valid_range() {
+        assert_eq!(
+            parse_valid_range(&[0x00, 0x00, 0x64, 0x00]),
+            Ok((vec![0x00, 0x00], vec![0x64, 0x00]))
+        );
+        assert_eq!(
+            parse_valid_range(&[0x00, 0x00, 0x64]),
+            Err(FormatError::InvalidLength(3))
+        );
+        assert_eq!(parse_valid_range(&[]), Err(FormatError::InvalidLength(0)));
+    }
+
+    #[test]
+    fn aggregate_format() {
+        assert_eq!(
+            parse_aggregate_format(&[0x12, 0x00, 0x34, 0x01]),
+            Ok(vec![0x0012, 0x0134])
+        );
+        assert_eq!(
+            parse_aggregate_format(&[0x12]),
+            Err(FormatError::InvalidLength(1))
+        );
+    }
+
+    #[test]
+    fn standard_formats() {
+        assert_eq!(decode_temperature(&[0x34, 0x08]), Ok(21.0));
+        assert_eq!(decode_temperature(&[0xcc, 0xf7]), Ok(-21.0));
+        assert_eq!(decode_percentage(&[42]), Ok(42));
+    }
+
+    #[test]
+    fn strings() {
+        assert_eq!(decode_string(b"Acme"), Ok("Acme".to_string()));
+        assert_eq!(decode_string(b"Acme\0\0"), Ok("Acme".to_string()));
+        assert_eq!(decode_string(b""), Ok("".to_string()));
+        assert_eq!(
+            decode_string(&[0x41, 0xff, 0x42]),
+            Err(FormatError::InvalidUtf8)
+        );
+        assert_eq!(decode_string_lossy(&[0x41, 0xff, 0x42, 0x00]), "A\u{fffd}B");
+    }
+
+    #[test]
+    fn heart_rate_u8() {
+        assert_eq!(
+            HeartRateMeasurement::parse(&[0x00, 0x48]),
+            Ok(HeartRateMeasurement {
+                bpm: 72,
+                contact: None,
+                energy: None,
+                rr_intervals: vec![],
+            })
+        );
+    }
+
+    #[test]
+    fn heart_rate_u16() {
+        assert_eq!(
+            HeartRateMeasurement::parse(&[0x01, 0x2c, 0x01]),
+            Ok(HeartRateMeasurement {
+                bpm: 300,
+                contact: None,
+                energy: None,
+                rr_intervals: vec![],
+            })
+        );
+    }
+
+    #[test]
+    fn heart_rate_contact() {
+        // Supported but not detected.
+        assert_eq!(
+            HeartRateMeasurement::parse(&[0x04, 0x50]).unwrap().contact,
+            Some(false)
+        );
+        // Supported and detected.
+        assert_eq!(
+            HeartRateMeasurement::parse(&[0x06, 0x50]).unwrap().contact,
+            Some(true)
+        );
+    }
+
+    #[test]
+    fn heart_rate_energy() {
+        assert_eq!(
+            HeartRateMeasurement::parse(&[0x08, 0x48, 0x10, 0x00]),
+            Ok(HeartRateMeasurement {
+                bpm: 72,
+                contact: None,
+                energy: Some(16),
+                rr_intervals: vec![],
+            })
+        );
+    }
+
+    #[test]
+    fn heart_rate_rr_intervals() {
+        // Captured from a chest strap.
+        assert_eq!(
+            HeartRateMeasurement::parse(&[0x16, 0x3c, 0x2e, 0x03]),
+            Ok(HeartRateMeasurement {
+                bpm: 60,
+                contact: Some(true),
+                energy: None,
+                rr_intervals: vec![814],
+            })
+        );
+        assert_eq!(
+            HeartRateMeasurement::parse(&[0x16, 0x4b, 0x1a, 0x03, 0x20, 0x03]),
+            Ok(HeartRateMeasurement {
+                bpm: 75,
+                contact: Some(true),
+                energy: None,
+                rr_intervals: vec![794, 800],
+            })
+        );
+    }
+
+    #[test]
+    fn heart_rate_all_fields() {
+        assert_eq!(
+            HeartRateMeasurement::parse(&[0x1f, 0x48, 0x00, 0x20, 0x00, 0x00, 0x04, 0x00, 0x04]),
+            Ok(HeartRateMeasurement {
+                bpm: 72,
+                contact: Some(true),
+                energy: Some(32),
+                rr_intervals: vec![1024, 1024],
+            })
+        );
+    }
+
+    #[test]
+    fn heart_rate_invalid() {
+        assert_eq!(
+            HeartRateMeasurement::parse(&[]),
+            Err(FormatError::InvalidLength(0))
+        );
+        assert_eq!(
+            HeartRateMeasurement::parse(&[0x01, 0x48]),
+            Err(FormatError::InvalidLength(2))
+        );
+        assert_eq!(
+            HeartRateMeasurement::parse(&[0x10, 0x48, 0x2e]),
+            Err(FormatError::InvalidLength(3))
+        );
+        assert_eq!(
+            HeartRateMeasurement::parse(&[0x00, 0x48, 0x00]),
+            Err(FormatError::InvalidLength(3))
+        );
+    }
+
+    #[test]
+    fn ieee_11073() {
+        // 365 * 10^-1
+        assert_eq!(decode_sfloat(&[0x6d, 0xf1]), Ok(36.5));
+        // -1 * 10^0
+        assert_eq!(decode_sfloat(&[0xff, 0x0f]), Ok(-1.0));
+        assert!(decode_sfloat(&[0xff, 0x07]).unwrap().is_nan());
+        assert_eq!(decode_sfloat(&[0xfe, 0x07]), Ok(f32::INFINITY));
+        // 365 * 10^-1
+        assert_eq!(decode_float(&[0x6d, 0x01, 0x00, 0xff]), Ok(36.5));
+        assert_eq!(
+            decode_float(&[0x02, 0x00, 0x80, 0x00]),
+            Ok(f64::NEG_INFINITY)
+        );
+    }
+}
@@ -0,0 +1,251 @@
+//! Support for declaring typed clients for GATT services, with the [`gatt_client`] macro.
+
+use std::convert::TryInto;
+
+use crate::format::FormatError;
+use crate::{BluetoothError, CharacteristicInfo};
+use uuid::Uuid;
+
+/// A type which can be decoded from and encoded to the value of a GATT characteristic, for use
+/// with [`gatt_client`].
+///
+/// Integers are little-endian, as is standard for Bluetooth, and strings are UTF-8.
+pub trait GattValue: Sized {
+    /// Decode a value read from a characteristic.
+    fn decode(value: &[u8]) -> Result<Self, FormatError>;
+
+    /// Encode the value to be written to a characteristic.
+    fn encode(&self) -> Vec<u8>;
+}
+
+macro_rules! impl_gatt_value_for_int {
+    ($($type:ty),*) => {
+        $(
+            impl GattValue for $type {
+                fn decode(value: &[u8]) -> Result<Self, FormatError> {
+                    let bytes = value.try_into().map_err(|_| FormatError::WrongLength {
+                        length: value.len(),
+                        expected_length: std::mem::size_of::<Self>(),
+                    })?;
+                    Ok(Self::from_le_bytes(bytes))
+                }
+
+                fn encode(&self) -> Vec<u8> {
+                    self.to_le_bytes().to_vec()
+                }
+            }
+        )*
+    };
+}
+
+impl_gatt_value_for_int!(u8, u16, u32, u64, i8, i16, i32, i64);
+
+impl GattValue for bool {
+    fn decode(value: &[u8]) -> Result<Self, FormatError> {
+        Ok(u8::decode(value)? != 0)
+    }
+
+    fn encode(&self) -> Vec<u8> {
+        vec![*self as u8]
+    }
+}
+
+impl GattValue for String {
+    fn decode(value: &[u8]) -> Result<Self, FormatError> {
+        crate::format::decode_string(value)
+    }
+
+    fn encode(&self) -> Vec<u8> {
+        self.as_bytes().to_vec()
+    }
+}
+
+impl GattValue for Vec<u8> {
+    fn decode(value: &[u8]) -> Result<Self, FormatError> {
+        Ok(value.to_owned())
+    }
+
+    fn encode(&self) -> Vec<u8> {
+        self.to_owned()
+    }
+}
+
+/// Find the characteristic with the given UUID in the given list. This is used by clients
+/// generated by [`gatt_client`].
+#[doc(hidden)]
+pub fn find_characteristic(
+    characteristics: &[CharacteristicInfo],
+    uuid: Uuid,
+) -> Result<crate::CharacteristicId, BluetoothError> {
+    characteristics
+        .iter()
+        .find(|characteristic| characteristic.uuid == uuid)
+        .map(|characteristic| characteristic.id.to_owned())
+        .ok_or(BluetoothError::UUIDNotFound { uuid })
+}
+
+/// Declare a typed client for a GATT service, with a method to read and/or write each
+/// characteristic as a type implementing [`GattValue`].
+///
+/// The generated struct has an async `new(session, device)` constructor, which looks up the
+/// service and all the declared characteristics on the given device, so fails with
+/// `BluetoothError::UUIDNotFound` if any are missing.
+///
+/// # Example
+///
+/// ```
+/// use bluez_async::{gatt_client, uuid_from_u16};
+///
+/// gatt_client! {
+///     /// A client for a thermometer.
+///     pub struct Thermometer(uuid_from_u16(0x181a)) {
+///         /// The current temperature, in hundredths of a degree Celsius.
+///         temperature: i16 = uuid_from_u16(0x2a6e) => { read read_temperature }
+///         /// The name of the location of the thermometer.
+///         location: String = uuid_from_u16(0x2ab5) => {
+///             read read_location
+///             write write_location
+///         }
+///     }
+/// }
+/// ```
+#[macro_export]
+macro_rules! gatt_client {
+    (
+        $(#[$meta:meta])*
+        $vis:vis struct $name:ident($service_uuid:expr) {
+            $(
+                $(#[$characteristic_meta:meta])*
+                $characteristic:ident: $type:ty = $uuid:expr => {
+                    $(read $read:ident)?
+                    $(write $write:ident)?
+                }
+            )*
+        }
+    ) => {
+        $(#[$meta])*
+        #[derive(Clone, Debug)]
+        $vis struct $name {
+            session: $crate::BluetoothSession,
+            $($characteristic: $crate::CharacteristicId,)*
+        }
+
+        impl $name {
+            /// Look up the service and its characteristics on the given device.
+            ///
+            /// This generally won't work until the device is connected and its services resolved.
+            #[allow(dead_code)]
+            $vis async fn new(
+                session: &$crate::BluetoothSession,
+                device: &$crate::DeviceId,
+            ) -> Result<Self, $crate::BluetoothError> {
+                let service = session.get_service_by_uuid(device, $service_uuid).await?;
+                let characteristics = session.get_characteristics(&service.id).await?;
+                Ok(Self {
+                    session: session.clone(),
+                    $($characteristic: $crate::gattclient::find_characteristic(
+                        &characteristics,
+                        $uuid,
+                    )?,)*
+                })
+            }
+
+            $(
+                $crate::gatt_client!(
+                    @accessors [$(#[$characteristic_meta])*] $vis $characteristic $type,
+                    $(read $read)? $(write $write)?
+                );
+            )*
+        }
+    };
+    (
+        @accessors [$($attr:tt)*] $vis:vis $characteristic:ident $type:ty,
+        read $read:ident write $write:ident
+    ) => {
+        $crate::gatt_client!(@accessors [$($attr)*] $vis $characteristic $type, read $read);
+        $crate::gatt_client!(@accessors [$($attr)*] $vis $characteristic $type, write $write);
+    };
+    (@accessors [$($attr:tt)*] $vis:vis $characteristic:ident $type:ty, read $read:ident) => {
+        $($attr)*
+        #[allow(dead_code)]
+        $vis async fn $read(&self) -> Result<$type, $crate::BluetoothError> {
+            let value = self
+                .session
+                .read_characteristic_value(&self.$characteristic)
+                .await?;
+            Ok(<$type as $crate::gattclient::GattValue>::decode(&value)?)
+        }
+    };
+    (@accessors [$($attr:tt)*] $vis:vis $characteristic:ident $type:ty, write $write:ident) => {
+        $($attr)*
+        #[allow(dead_code)]
+        $vis async fn $write(&self, value: &$type) -> Result<(), $crate::BluetoothError> {
+            self.session
+                .write_characteristic_value(
+                    &self.$characteristic,
+                    $crate::gattclient::GattValue::encode(value),
+                )
+                .await
+        }
+    };
+    (@accessors [$($attr:tt)*] $vis:vis $characteristic:ident $type:ty,) => {};
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{uuid_from_u16, CharacteristicFlags, CharacteristicId};
+
+    gatt_client! {
+        struct TestClient(uuid_from_u16(0x181a)) {
+            temperature: i16 = uuid_from_u16(0x2a6e) => { read read_temperature }
+            location: String = uuid_from_u16(0x2ab5) => {
+                read read_location
+                write write_location
+            }
+            control: u8 = uuid_from_u16(0x2a9f) => { write write_control }
+        }
+    }
+
+    #[test]
+    fn decode_integers() {
+        assert_eq!(i16::decode(&[0x34, 0xf8]), Ok(-1996));
+        assert_eq!(u32::decode(&[0x01, 0x00, 0x00, 0x00]), Ok(1));
+        assert_eq!(
+            u16::decode(&[0x01]),
+            Err(FormatError::WrongLength {
+                length: 1,
+                expected_length: 2
+            })
+        );
+        assert_eq!((-1996i16).encode(), vec![0x34, 0xf8]);
+    }
+
+    #[test]
+    fn decode_other_types() {
+        assert_eq!(bool::decode(&[1]), Ok(true));
+        assert_eq!(true.encode(), vec![1]);
+        assert_eq!(String::decode(b"Kitchen"), Ok("Kitchen".to_string()));
+        assert_eq!(vec![1u8, 2].encode(), vec![1, 2]);
+    }
+
+    #[test]
+    fn find() {
+        let characteristics = vec![CharacteristicInfo {
+            id: CharacteristicId::new("/org/bluez/hci0/dev_11_22_33_44_55_66/service0001/char0002"),
+            uuid: uuid_from_u16(0x2a6e),
+            flags: CharacteristicFlags::READ,
+            handle: None,
+            write_acquired: None,
+            notify_acquired: None,
+        }];
+        assert_eq!(
+            find_characteristic(&characteristics, uuid_from_u16(0x2a6e)).unwrap(),
+            characteristics[0].id
+        );
+        assert!(matches!(
+            find_characteristic(&characteristics, uuid_from_u16(0x2ab5)),
+            Err(BluetoothError::UUIDNotFound { .. })
+        ));
+    }
+}
@@ -0,0 +1,147 @@
+use serde_derive::{Deserialize, Serialize};
+use uuid::Uuid;
+
+use crate::well_known::generic_attribute::{DATABASE_HASH, SERVICE_CHANGED};
+use crate::{CharacteristicId, CharacteristicInfo, DescriptorInfo, ServiceInfo};
+
+/// A snapshot of the GATT services, characteristics and descriptors of a device, as returned by
+/// [`BluetoothSession::get_gatt_database`](crate::BluetoothSession::get_gatt_database).
+///
+/// This can be serialized with serde so that it can be saved to a file and reloaded later, rather
+/// than introspecting the device again every time it is connected. Use
+/// [`BluetoothSession::is_gatt_database_current`](crate::BluetoothSession::is_gatt_database_current)
+/// to check whether a cached snapshot is still valid when reconnecting, and
+/// [`BluetoothSession::subscribe_gatt_database_changes`](crate::BluetoothSession::subscribe_gatt_database_changes)
+/// to find out when it is invalidated by the device's Service Changed characteristic while
+/// connected.
+#[derive(Clone, Debug, Default, Deserialize, Eq, PartialEq, Serialize)]
+pub struct GattDatabase {
+    /// The services of the device.
+    pub services: Vec<GattDatabaseService>,
+    /// The value of the device's Database Hash characteristic at the time the snapshot was taken,
+    /// if it has one.
+    pub database_hash: Option<Vec<u8>>,
+}
+
+/// A GATT service in a [`GattDatabase`], along with its characteristics.
+#[derive(Clone, Debug, Deserialize, Eq, PartialEq, Serialize)]
+pub struct GattDatabaseService {
+    /// Information about the service itself.
+    pub info: ServiceInfo,
+    /// The characteristics of the service.
+    pub characteristics: Vec<GattDatabaseCharacteristic>,
+}
+
+/// A GATT characteristic in a [`GattDatabase`], along with its descriptors.
+#[derive(Clone, Debug, Deserialize, Eq, PartialEq, Serialize)]
+pub struct GattDatabaseCharacteristic {
+    /// Information about the characteristic itself.
+    pub info: CharacteristicInfo,
+    /// The descriptors of the characteristic.
+    pub descriptors: Vec<DescriptorInfo>,
+}
+
+impl GattDatabase {
+    /// Find a characteristic with the given UUID in any service of the database.
+    pub fn find_characteristic(&self, uuid: Uuid) -> Option<&CharacteristicInfo> {
+        self.services
+            .iter()
+            .flat_map(|service| &service.characteristics)
+            .map(|characteristic| &characteristic.info)
+            .find(|info| info.uuid == uuid)
+    }
+
+    /// Get the ID of the device's Service Changed characteristic, if it has one.
+    ///
+    /// If you keep a cached database while connected to a device, you should subscribe to
+    /// indications from this characteristic with
+    /// [`BluetoothSession::subscribe_gatt_database_changes`](crate::BluetoothSession::subscribe_gatt_database_changes)
+    /// and discard the cache when one is received, as it means that the device's GATT layout has
+    /// changed.
+    pub fn service_changed_characteristic(&self) -> Option<&CharacteristicId> {
+        self.find_characteristic(SERVICE_CHANGED)
+            .map(|info| &info.id)
+    }
+
+    /// Get the ID of the device's Database Hash characteristic, if it has one.
+    pub(crate) fn database_hash_characteristic(&self) -> Option<&CharacteristicId> {
+        self.find_characteristic(DATABASE_HASH).map(|info| &info.id)
+    }
+}
+
+/// Parse the value of a Service Changed indication into the inclusive range of attribute handles
+/// which have changed, or return `None` if it is not valid.
+pub(crate) fn parse_service_changed(value: &[u8]) -> Option<(u16, u16)> {
+    if value.len() != 4 {
+        return None;
+    }
+    let start = u16::from_le_bytes([value[0], value[1]]);
+    let end = u16::from_le_bytes([value[2], value[3]]);
+    Some((start, end))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::well_known::generic_attribute;
+    use crate::{CharacteristicFlags, ServiceId};
+
+    fn characteristic(path: &str, uuid: Uuid) -> GattDatabaseCharacteristic {
+        GattDatabaseCharacteristic {
+            info: CharacteristicInfo {
+                id: CharacteristicId::new(path),
+                uuid,
+                flags: CharacteristicFlags::READ,
+                handle: None,
+                write_acquired: None,
+                notify_acquired: None,
+            },
+            descriptors: vec![],
+        }
+    }
+
+    #[test]
+    fn special_characteristics() {
+        let service_path = "/org/bluez/hci0/dev_11_22_33_44_55_66/service0001";
+        let database = GattDatabase {
+            services: vec![GattDatabaseService {
+                info: ServiceInfo {
+                    id: ServiceId::new(service_path),
+                    uuid: generic_attribute::SERVICE,
+                    primary: true,
+                },
+                characteristics: vec![
+                    characteristic(&format!("{}/char0002", service_path), SERVICE_CHANGED),
+                    characteristic(&format!("{}/char0005", service_path), DATABASE_HASH),
+                ],
+            }],
+            database_hash: Some(vec![1, 2, 3]),
+        };
+
+        assert_eq!(
+            database.service_changed_characteristic(),
+            Some(&CharacteristicId::new(
+                "/org/bluez/hci0/dev_11_22_33_44_55_66/service0001/char0002"
+            ))
+        );
+        assert_eq!(
+            database.database_hash_characteristic(),
+            Some(&CharacteristicId::new(
+                "/org/bluez/hci0/dev_11_22_33_44_55_66/service0001/char0005"
+            ))
+        );
+        assert_eq!(
+            GattDatabase::default().service_changed_characteristic(),
+            None
+        );
+    }
+
+    #[test]
+    fn service_changed_value() {
+        assert_eq!(
+            parse_service_changed(&[0x01, 0x00, 0xff, 0xff]),
+            Some((0x0001, 0xffff))
+        );
+        assert_eq!(parse_service_changed(&[0x01, 0x00]), None);
+    }
+}
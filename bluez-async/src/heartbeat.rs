@@ -0,0 +1,93 @@
+use futures::future::{self, Either};
+use futures::stream::{self, StreamExt};
+use futures::{Future, Stream};
+use std::fmt::Display;
+use std::time::Duration;
+use tokio::time::{self, Instant};
+
+use crate::BluetoothEvent;
+
+/// Interleave a `BluetoothEvent::Heartbeat` into the given stream of events every `interval`.
+///
+/// Before each heartbeat is emitted `ping` is called and awaited, so that a heartbeat is only
+/// emitted if the connection it checks is still alive. If it fails then the error is logged and
+/// the returned stream ends. The returned stream also ends as soon as the underlying stream ends.
+pub(crate) fn with_heartbeat<P, F, E>(
+    events: impl Stream<Item = BluetoothEvent>,
+    interval: Duration,
+    ping: P,
+) -> impl Stream<Item = BluetoothEvent>
+where
+    P: FnMut() -> F,
+    F: Future<Output = Result<(), E>>,
+    E: Display,
+{
+    let ticker = time::interval_at(Instant::now() + interval, interval);
+    stream::unfold(
+        (Box::pin(events), ticker, ping),
+        |(mut events, mut ticker, mut ping)| async move {
+            let event = match future::select(events.next(), Box::pin(ticker.tick())).await {
+                Either::Left((Some(event), _)) => event,
+                Either::Left((None, _)) => return None,
+                Either::Right(_) => {
+                    if let Err(e) = ping().await {
+                        log::error!("Heartbeat ping failed, ending event stream: {}", e);
+                        return None;
+                    }
+                    BluetoothEvent::Heartbeat
+                }
+            };
+            Some((event, (events, ticker, ping)))
+        },
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{AdapterEvent, AdapterId};
+
+    async fn ping_ok() -> Result<(), String> {
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn heartbeats_when_quiet() {
+        let events = with_heartbeat(stream::pending(), Duration::from_millis(10), ping_ok);
+        assert_eq!(
+            events.take(2).collect::<Vec<_>>().await,
+            vec![BluetoothEvent::Heartbeat, BluetoothEvent::Heartbeat]
+        );
+    }
+
+    #[tokio::test]
+    async fn ends_with_underlying_stream() {
+        let event = BluetoothEvent::Adapter {
+            id: AdapterId::new("/org/bluez/hci0"),
+            event: AdapterEvent::Powered { powered: true },
+        };
+        let events = with_heartbeat(
+            stream::iter(vec![event.clone()]),
+            Duration::from_secs(60),
+            ping_ok,
+        );
+        assert_eq!(events.collect::<Vec<_>>().await, vec![event]);
+    }
+
+    #[tokio::test]
+    async fn ends_when_ping_fails() {
+        let mut pings = 0;
+        let events = with_heartbeat(stream::pending(), Duration::from_millis(10), move || {
+            pings += 1;
+            future::ready(if pings > 1 {
+                Err("connection lost")
+            } else {
+                Ok(())
+            })
+        });
+        assert_eq!(
+            events.collect::<Vec<_>>().await,
+            vec![BluetoothEvent::Heartbeat]
+        );
+    }
+}
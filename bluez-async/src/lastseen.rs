@@ -0,0 +1,163 @@
+use dbus::message::MatchRule;
+use dbus::strings::BusName;
+use dbus::Message;
+use std::collections::HashMap;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+use crate::{BluetoothEvent, DeviceEvent, DeviceId};
+
+/// Keeps track of when an advertisement was last received from each device, once enabled.
+#[derive(Debug, Default)]
+pub(crate) struct LastSeenTracker {
+    /// When tracking started, or `None` if it isn't enabled.
+    started: Mutex<Option<Instant>>,
+    last_seen: Mutex<HashMap<DeviceId, Instant>>,
+}
+
+impl LastSeenTracker {
+    /// Get the match rules for the signals which indicate that an advertisement has been received.
+    pub fn match_rules(service_name: &BusName<'static>) -> Vec<MatchRule<'static>> {
+        BluetoothEvent::match_rules(service_name, None::<DeviceId>)
+    }
+
+    /// Mark tracking as enabled, starting at the given time. This should only be done once the
+    /// D-Bus matches for the signals it tracks have been added. Returns whether it was already
+    /// enabled.
+    pub fn enable(&self, now: Instant) -> bool {
+        let mut started = self.started.lock().unwrap();
+        if started.is_some() {
+            true
+        } else {
+            *started = Some(now);
+            false
+        }
+    }
+
+    /// Whether tracking is enabled.
+    pub fn is_enabled(&self) -> bool {
+        self.started.lock().unwrap().is_some()
+    }
+
+    /// Get the time at which the given device was last seen, if it has been seen since tracking
+    /// started.
+    pub fn get(&self, id: &DeviceId) -> Option<Instant> {
+        self.last_seen.lock().unwrap().get(id).copied()
+    }
+
+    /// Returns whether the given device has not been seen for more than the given duration as of
+    /// `now`. A device which hasn't been seen at all is only stale once tracking has been running
+    /// for that long, and no device is stale if tracking isn't enabled.
+    pub fn is_stale(&self, id: &DeviceId, older_than: Duration, now: Instant) -> bool {
+        let started = match *self.started.lock().unwrap() {
+            Some(started) => started,
+            None => return false,
+        };
+        let last_seen = self.get(id).unwrap_or(started);
+        now.saturating_duration_since(last_seen) > older_than
+    }
+
+    /// Update the tracked times based on the given D-Bus message, received at the given time. The
+    /// message is ignored if tracking isn't enabled.
+    pub fn handle_message(&self, message: Message, now: Instant) {
+        if !self.is_enabled() {
+            return;
+        }
+        let mut last_seen = self.last_seen.lock().unwrap();
+        for event in BluetoothEvent::message_to_events(message, false) {
+            if let BluetoothEvent::Device { id, event } = event {
+                match event {
+                    DeviceEvent::Discovered
+                    | DeviceEvent::RSSI { .. }
+                    | DeviceEvent::ManufacturerData { .. } => {
+                        last_seen.insert(id, now);
+                    }
+                    DeviceEvent::Removed => {
+                        last_seen.remove(&id);
+                    }
+                    _ => {}
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use dbus::arg::{PropMap, Variant};
+    use dbus::message::SignalArgs;
+    use dbus::nonblock::stdintf::org_freedesktop_dbus::{
+        ObjectManagerInterfacesRemoved, PropertiesPropertiesChanged,
+    };
+
+    #[test]
+    fn rssi_and_removal() {
+        let tracker = LastSeenTracker::default();
+        let id = DeviceId::new("/org/bluez/hci0/dev_11_22_33_44_55_66");
+        let now = Instant::now();
+        assert_eq!(tracker.get(&id), None);
+
+        let rssi_message = || {
+            let mut changed_properties = PropMap::new();
+            changed_properties.insert("RSSI".to_string(), Variant(Box::new(-50i16)));
+            PropertiesPropertiesChanged {
+                interface_name: "org.bluez.Device1".to_string(),
+                changed_properties,
+                invalidated_properties: vec![],
+            }
+            .to_emit_message(&id.object_path)
+        };
+        tracker.handle_message(rssi_message(), now);
+        assert_eq!(tracker.get(&id), None);
+
+        tracker.enable(now);
+        tracker.handle_message(rssi_message(), now);
+        assert_eq!(tracker.get(&id), Some(now));
+
+        let mut changed_properties = PropMap::new();
+        changed_properties.insert("Connected".to_string(), Variant(Box::new(true)));
+        let message = PropertiesPropertiesChanged {
+            interface_name: "org.bluez.Device1".to_string(),
+            changed_properties,
+            invalidated_properties: vec![],
+        }
+        .to_emit_message(&id.object_path);
+        tracker.handle_message(message, now + Duration::from_secs(1));
+        assert_eq!(tracker.get(&id), Some(now));
+
+        let message = ObjectManagerInterfacesRemoved {
+            object: id.object_path.clone(),
+            interfaces: vec!["org.bluez.Device1".to_string()],
+        }
+        .to_emit_message(&"/".into());
+        tracker.handle_message(message, now);
+        assert_eq!(tracker.get(&id), None);
+    }
+
+    #[test]
+    fn stale() {
+        let tracker = LastSeenTracker::default();
+        let id = DeviceId::new("/org/bluez/hci0/dev_11_22_33_44_55_66");
+        let older_than = Duration::from_secs(60);
+        let start = Instant::now();
+        assert!(!tracker.is_stale(&id, older_than, start + Duration::from_secs(90)));
+
+        assert!(!tracker.enable(start));
+        assert!(tracker.enable(start + Duration::from_secs(10)));
+        assert!(!tracker.is_stale(&id, older_than, start + Duration::from_secs(30)));
+        assert!(tracker.is_stale(&id, older_than, start + Duration::from_secs(90)));
+
+        let mut changed_properties = PropMap::new();
+        changed_properties.insert("RSSI".to_string(), Variant(Box::new(-50i16)));
+        let message = PropertiesPropertiesChanged {
+            interface_name: "org.bluez.Device1".to_string(),
+            changed_properties,
+            invalidated_properties: vec![],
+        }
+        .to_emit_message(&id.object_path);
+        tracker.handle_message(message, start + Duration::from_secs(80));
+        assert!(!tracker.is_stale(&id, older_than, start + Duration::from_secs(90)));
+        assert!(tracker.is_stale(&id, older_than, start + Duration::from_secs(150)));
+    }
+}
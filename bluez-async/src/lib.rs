@@ -8,118 +8,259 @@
 //! [`BluetoothSession']: struct.BluetoothSession.html
 
 mod adapter;
+mod adaptercache;
+mod agent;
+#[cfg(feature = "assigned-numbers")]
+mod appearance;
 mod bleuuid;
 mod characteristic;
+#[cfg(feature = "classic")]
+mod classic;
+mod coalesce;
+mod concurrency;
+mod connectionguard;
 mod descriptor;
 mod device;
+mod deviceinformation;
+mod devicestate;
+mod discoverysession;
+mod eventbuffer;
 mod events;
+mod exportedobject;
+pub mod format;
+#[cfg(feature = "gatt-client")]
+#[doc(hidden)]
+pub mod gattclient;
+mod gattdatabase;
+mod heartbeat;
 mod introspect;
+mod lastseen;
+mod macaddress;
 mod messagestream;
+#[cfg(feature = "advertisement-monitor")]
+mod monitor;
+#[cfg(feature = "peripheral")]
+mod peripheral;
+mod refilter;
+mod retry;
+mod serde_hex;
+mod serde_path;
 mod service;
+mod snapshot;
+mod spawnondrop;
+mod summary;
+mod tree;
+mod valuecache;
+mod valuestream;
+mod version;
+pub mod well_known;
 
-pub use self::adapter::AdapterId;
+pub use self::adapter::{AdapterId, AdapterInfo};
+use self::adaptercache::AdapterCache;
+pub use self::agent::{AgentCapability, AgentError, BluetoothAgent, RegisteredAgent};
+#[cfg(feature = "assigned-numbers")]
+pub use self::appearance::Appearance;
 pub use self::bleuuid::{uuid_from_u16, uuid_from_u32, BleUuid};
+use self::characteristic::{
+    long_write_type, max_write_length, MAX_ATTRIBUTE_VALUE_LENGTH, MIN_MAX_WRITE_LENGTH,
+};
 pub use self::characteristic::{CharacteristicFlags, CharacteristicId, CharacteristicInfo};
+#[cfg(feature = "classic")]
+pub use self::classic::{RfcommStream, SERIAL_PORT_PROFILE_UUID};
+pub use self::coalesce::{BluetoothEventStreamExt, CoalescedByDevice};
+use self::concurrency::run_concurrently;
+pub use self::connectionguard::ConnectionGuard;
 pub use self::descriptor::{DescriptorId, DescriptorInfo};
-pub use self::device::{DeviceId, DeviceInfo};
-pub use self::events::{AdapterEvent, BluetoothEvent, CharacteristicEvent, DeviceEvent};
+use self::device::{convert_connect_error, convert_pair_error, is_security_error};
+pub use self::device::{AddressType, DeviceId, DeviceInfo};
+pub use self::deviceinformation::DeviceInformation;
+pub use self::devicestate::DeviceStateChange;
+use self::devicestate::DeviceStateTracker;
+pub use self::discoverysession::DiscoverySession;
+use self::eventbuffer::buffer_events;
+pub use self::eventbuffer::OverflowPolicy;
+pub use self::events::{
+    AdapterEvent, BluetoothEvent, CharacteristicEvent, DeviceEvent, DisconnectReason,
+};
+use self::format::{CharacteristicValue, FormatError, HeartRateMeasurement, PresentationFormat};
+#[cfg(feature = "gatt-client")]
+pub use self::gattclient::GattValue;
+use self::gattdatabase::parse_service_changed;
+pub use self::gattdatabase::{GattDatabase, GattDatabaseCharacteristic, GattDatabaseService};
+use self::heartbeat::with_heartbeat;
 use self::introspect::IntrospectParse;
+use self::lastseen::LastSeenTracker;
+pub use self::macaddress::{MacAddress, ParseMacAddressError};
 use self::messagestream::MessageStream;
+#[cfg(feature = "advertisement-monitor")]
+pub use self::monitor::{
+    AdvertisementMonitor, MonitorEvent, MonitorFeatures, MonitorPattern, MonitorSpec,
+};
+#[cfg(feature = "peripheral")]
+pub use self::peripheral::{Advertisement, AdvertisementType, RegisteredAdvertisement};
+pub use self::refilter::{DeviceInfoStreamExt, Refiltered};
+pub use self::retry::RetryPolicy;
 pub use self::service::{ServiceId, ServiceInfo};
+pub use self::summary::ObjectSummary;
+pub use self::tree::TreeNode;
+use self::valuecache::ValueCache;
+pub use self::valuestream::CharacteristicValueStream;
 use bluez_generated::{
-    OrgBluezAdapter1, OrgBluezDevice1, OrgBluezDevice1Properties, OrgBluezGattCharacteristic1,
-    OrgBluezGattDescriptor1, OrgBluezGattService1, ORG_BLUEZ_ADAPTER1_NAME, ORG_BLUEZ_DEVICE1_NAME,
+    OrgBluezAdapter1, OrgBluezAdapter1Properties, OrgBluezDevice1, OrgBluezDevice1Properties,
+    OrgBluezGattCharacteristic1, OrgBluezGattCharacteristic1Properties, OrgBluezGattDescriptor1,
+    OrgBluezGattDescriptor1Properties, OrgBluezGattService1, ORG_BLUEZ_ADAPTER1_NAME,
+    ORG_BLUEZ_DEVICE1_NAME, ORG_BLUEZ_GATT_CHARACTERISTIC1_NAME, ORG_BLUEZ_GATT_DESCRIPTOR1_NAME,
 };
-use dbus::arg::{PropMap, Variant};
+use dbus::arg::{prop_cast, PropMap, Variant};
+use dbus::message::MatchRule;
 use dbus::nonblock::stdintf::org_freedesktop_dbus::{Introspectable, ObjectManager, Properties};
-use dbus::nonblock::{Proxy, SyncConnection};
-use dbus::Path;
+use dbus::nonblock::{NonblockReply, Proxy, SyncConnection};
+use dbus::strings::BusName;
+use dbus::{Message, Path};
 use dbus_tokio::connection::IOResourceError;
+use futures::channel::oneshot;
+use futures::future::{join_all, try_join_all, BoxFuture};
 use futures::stream::{self, select_all, StreamExt};
 use futures::{FutureExt, Stream};
 use std::collections::HashMap;
-use std::convert::TryInto;
+use std::convert::TryFrom;
 use std::fmt::{self, Debug, Display, Formatter};
 use std::future::Future;
-use std::str::FromStr;
-use std::sync::Arc;
-use std::time::Duration;
+use std::panic::{self, AssertUnwindSafe};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
 use thiserror::Error;
 use tokio::task::JoinError;
+use tokio::time;
 use uuid::Uuid;
 
 const DBUS_METHOD_CALL_TIMEOUT: Duration = Duration::from_secs(30);
+/// How long to leave an adapter powered off when resetting it.
+const ADAPTER_RESET_DELAY: Duration = Duration::from_secs(1);
+/// How long to wait for an adapter to power back on when resetting it.
+const ADAPTER_POWER_ON_TIMEOUT: Duration = Duration::from_secs(10);
+/// The D-Bus bus name which BlueZ uses by default.
+const DEFAULT_SERVICE_NAME: &str = "org.bluez";
 
 /// An error carrying out a Bluetooth operation.
-#[derive(Debug, Error)]
+///
+/// This is `Clone` so that a single error can be sent to several tasks; errors from D-Bus, I/O,
+/// JSON serialization and the XML parser, which can't be cloned themselves, are wrapped in an
+/// `Arc`.
+#[derive(Clone, Debug, Error)]
 pub enum BluetoothError {
     /// No Bluetooth adapters were found on the system.
     #[error("No Bluetooth adapters found.")]
     NoBluetoothAdapters,
     /// There was an error talking to the BlueZ daemon over D-Bus.
     #[error(transparent)]
-    DbusError(#[from] dbus::Error),
+    DbusError(Arc<dbus::Error>),
+    /// An I/O error on a socket passed to us by BlueZ.
+    #[error("I/O error: {0}")]
+    IoError(Arc<std::io::Error>),
+    /// Error serializing to JSON.
+    #[error("Error serializing to JSON: {0}")]
+    JsonError(Arc<serde_json::Error>),
     /// Error parsing XML for introspection.
     #[error("Error parsing XML for introspection: {0}")]
-    XmlParseError(#[from] serde_xml_rs::Error),
+    XmlParseError(Arc<serde_xml_rs::Error>),
     /// No service or characteristic was found for some UUID.
     #[error("Service or characteristic UUID {uuid} not found.")]
     UUIDNotFound { uuid: Uuid },
     /// Error parsing a UUID from a string.
     #[error("Error parsing UUID string: {0}")]
     UUIDParseError(#[from] uuid::Error),
+    /// Error parsing an `AddressType` from a string.
+    #[error("Invalid address type {0:?}")]
+    AddressTypeParseError(String),
     /// Error parsing a characteristic flag from a string.
     #[error("Invalid characteristic flag {0:?}")]
     FlagParseError(String),
     /// A required property of some device or other object was not found.
     #[error("Required property {0} missing.")]
     RequiredPropertyMissing(String),
+    /// The services of a device have not yet been resolved, probably because it is not connected.
+    #[error("Services not yet resolved.")]
+    ServicesNotResolved,
+    /// Connecting to a device failed because it didn't respond, probably because it is out of
+    /// range or turned off.
+    #[error("Connection failed: page timeout.")]
+    ConnectPageTimeout,
+    /// Connecting to a device failed because the device refused the connection.
+    #[error("Connection refused.")]
+    ConnectRefused,
+    /// Pairing with a device failed, e.g. because it was rejected or cancelled, or the pairing
+    /// agent couldn't provide the required input.
+    #[error("Pairing failed: {0}")]
+    PairingFailed(String),
+    /// Connecting to a device failed because authentication failed, e.g. because the pairing keys
+    /// are missing or out of date.
+    #[error("Connection failed: authentication failed.")]
+    ConnectAuthFailed,
+    /// An operation didn't complete within the given time.
+    #[error("Timed out.")]
+    Timeout,
+    /// Writing a characteristic value wasn't acknowledged within the given time.
+    #[error("Write timed out.")]
+    WriteTimeout,
+    /// The value to be written is longer than the characteristic allows.
+    #[error("Value too long: the maximum length is {max} bytes.")]
+    ValueTooLong { max: usize },
+    /// Introspecting the GATT services of a device didn't complete within the given time.
+    #[error("Introspection timed out.")]
+    IntrospectionTimeout,
+    /// The given D-Bus service name was not valid.
+    #[error("Invalid D-Bus service name: {0}")]
+    InvalidServiceName(String),
+    /// Some feature is not supported by the version of BlueZ or the Bluetooth adapter in use.
+    #[error("{0} is not supported.")]
+    NotSupported(String),
+    /// The D-Bus object for the device, service, characteristic or descriptor no longer exists,
+    /// e.g. because the device disconnected or was removed since its ID was obtained.
+    #[error("Object no longer exists.")]
+    ObjectRemoved,
+    /// Error decoding a characteristic value or its presentation format.
+    #[error(transparent)]
+    FormatError(#[from] FormatError),
 }
 
-/// Error type for futures representing tasks spawned by this crate.
-#[derive(Debug, Error)]
-pub enum SpawnError {
-    #[error("D-Bus connection lost: {0}")]
-    DbusConnectionLost(#[source] IOResourceError),
-    #[error("Task failed: {0}")]
-    Join(#[from] JoinError),
+impl From<dbus::Error> for BluetoothError {
+    fn from(error: dbus::Error) -> Self {
+        match error.name() {
+            Some("org.freedesktop.DBus.Error.UnknownObject") => Self::ObjectRemoved,
+            _ => Self::DbusError(Arc::new(error)),
+        }
+    }
 }
 
-/// MAC address of a Bluetooth device.
-#[derive(Clone, Debug, Eq, Hash, Ord, PartialEq, PartialOrd)]
-pub struct MacAddress(String);
-
-impl Display for MacAddress {
-    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
-        f.write_str(&self.0)
+impl From<std::io::Error> for BluetoothError {
+    fn from(error: std::io::Error) -> Self {
+        Self::IoError(Arc::new(error))
     }
 }
 
-/// An error parsing a MAC address from a string.
-#[derive(Clone, Debug, Error, Eq, PartialEq)]
-#[error("Invalid MAC address")]
-pub struct ParseMacAddressError();
-
-impl FromStr for MacAddress {
-    type Err = ParseMacAddressError;
+impl From<serde_json::Error> for BluetoothError {
+    fn from(error: serde_json::Error) -> Self {
+        Self::JsonError(Arc::new(error))
+    }
+}
 
-    fn from_str(s: &str) -> Result<Self, Self::Err> {
-        let octets: Vec<_> = s.split(':').collect();
-        if octets.len() != 6 {
-            return Err(ParseMacAddressError());
-        }
-        for octet in octets {
-            if octet.len() != 2 {
-                return Err(ParseMacAddressError());
-            }
-            if !octet.chars().all(|c| c.is_ascii_hexdigit()) {
-                return Err(ParseMacAddressError());
-            }
-        }
-        Ok(MacAddress(s.to_uppercase()))
+impl From<serde_xml_rs::Error> for BluetoothError {
+    fn from(error: serde_xml_rs::Error) -> Self {
+        Self::XmlParseError(Arc::new(error))
     }
 }
 
+/// Error type for futures representing tasks spawned by this crate.
+#[derive(Debug, Error)]
+pub enum SpawnError {
+    #[error("D-Bus connection lost: {0}")]
+    DbusConnectionLost(#[source] IOResourceError),
+    #[error("Task failed: {0}")]
+    Join(#[from] JoinError),
+}
+
 /// The type of transport to use for a scan.
 #[derive(Copy, Clone, Debug, Eq, PartialEq)]
 pub enum Transport {
@@ -169,6 +310,60 @@ pub struct DiscoveryFilter {
     pub discoverable: Option<bool>,
     /// Only report devices whose address or name starts with the given pattern.
     pub pattern: Option<String>,
+    /// If non-empty, only report devices whose address or name starts with at least one of these
+    /// patterns.
+    ///
+    /// BlueZ only supports a single pattern, so unless there is exactly one of these and `pattern`
+    /// is not set, they are applied client-side by [`DiscoveryFilter::matches_device`] and
+    /// [`BluetoothSession::discovered_device_stream`] rather than being passed to BlueZ.
+    pub patterns: Vec<String>,
+}
+
+impl DiscoveryFilter {
+    /// Check whether the given device matches the pattern and patterns of this filter. Devices
+    /// with no name are matched only on their address.
+    pub fn matches_device(&self, device: &DeviceInfo) -> bool {
+        let matches = |pattern: &str| {
+            device.mac_address.to_string().starts_with(pattern)
+                || matches!(&device.name, Some(name) if name.starts_with(pattern))
+        };
+        let pattern_matches = match &self.pattern {
+            Some(pattern) => matches(pattern),
+            None => true,
+        };
+        pattern_matches
+            && (self.patterns.is_empty() || self.patterns.iter().any(|pattern| matches(pattern)))
+    }
+
+    /// Check whether the given device matches all the parts of this filter which can be checked
+    /// locally: the RSSI threshold, service UUIDs, and pattern and patterns. A device with no known
+    /// RSSI doesn't match an RSSI threshold. A device matches the service UUIDs if it advertises
+    /// any of them, or service data for any of them.
+    ///
+    /// This is useful because BlueZ merges the filters of all clients, so may report devices which
+    /// don't match this filter if another client has set a looser one. See
+    /// [`DeviceInfoStreamExt::refiltered`].
+    pub fn matches_device_locally(&self, device: &DeviceInfo) -> bool {
+        let rssi_matches = match (self.rssi_threshold, device.rssi) {
+            (Some(threshold), Some(rssi)) => rssi > threshold,
+            (Some(_), None) => false,
+            (None, _) => true,
+        };
+        let services_match = self.service_uuids.is_empty()
+            || self.service_uuids.iter().any(|uuid| {
+                device.services.contains(uuid) || device.service_data.contains_key(uuid)
+            });
+        rssi_matches && services_match && self.matches_device(device)
+    }
+
+    /// The pattern to pass to BlueZ, if any.
+    fn server_side_pattern(&self) -> Option<&String> {
+        match (&self.pattern, self.patterns.as_slice()) {
+            (Some(pattern), _) => Some(pattern),
+            (None, [pattern]) => Some(pattern),
+            (None, _) => None,
+        }
+    }
 }
 
 impl Into<PropMap> for &DiscoveryFilter {
@@ -202,18 +397,116 @@ impl Into<PropMap> for &DiscoveryFilter {
         if let Some(discoverable) = self.discoverable {
             map.insert("Discoverable".to_string(), Variant(Box::new(discoverable)));
         }
-        if let Some(pattern) = &self.pattern {
+        if let Some(pattern) = self.server_side_pattern() {
             map.insert("Pattern".to_string(), Variant(Box::new(pattern.to_owned())));
         }
         map
     }
 }
 
+/// Options for reading the value of a GATT characteristic or descriptor. These map to the options
+/// of BlueZ's `ReadValue` method.
+///
+/// BlueZ decides by itself whether an encrypted or authenticated link is needed, based on the
+/// flags of the characteristic. If it is and the device isn't paired then the read will fail with
+/// a D-Bus error such as `org.bluez.Error.NotPermitted`, at which point you can pair with the
+/// device and try again.
+#[derive(Clone, Debug, Default, Eq, PartialEq)]
+pub struct ReadOptions {
+    /// The offset within the value at which to start reading. This maps to the `offset` option.
+    pub offset: Option<u16>,
+    /// The device on whose behalf the read is being done. This maps to the `device` option, and is
+    /// mainly relevant for server-side reads.
+    pub device: Option<DeviceId>,
+}
+
+impl From<&ReadOptions> for PropMap {
+    fn from(options: &ReadOptions) -> Self {
+        let mut map: PropMap = HashMap::new();
+        if let Some(offset) = options.offset {
+            map.insert("offset".to_string(), Variant(Box::new(offset)));
+        }
+        if let Some(device) = &options.device {
+            map.insert(
+                "device".to_string(),
+                Variant(Box::new(device.object_path.to_owned())),
+            );
+        }
+        map
+    }
+}
+
+/// The type of write operation to use when writing a GATT characteristic value.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum WriteType {
+    /// A write with response, which BlueZ turns into a long write if the value doesn't fit in a
+    /// single packet. This maps to the `request` type.
+    WithResponse,
+    /// A write without response, which must fit in a single packet. This maps to the `command`
+    /// type.
+    WithoutResponse,
+    /// A reliable write, in which BlueZ verifies the value of each prepared write. This maps to
+    /// the `reliable` type.
+    Reliable,
+}
+
+impl WriteType {
+    fn as_str(&self) -> &'static str {
+        match self {
+            Self::WithResponse => "request",
+            Self::WithoutResponse => "command",
+            Self::Reliable => "reliable",
+        }
+    }
+}
+
+/// Options for writing a GATT characteristic value, which map to the options of BlueZ's
+/// `WriteValue` method.
+#[derive(Clone, Debug, Default, Eq, PartialEq)]
+pub struct WriteOptions {
+    /// The offset within the value at which to start writing. This maps to the `offset` option.
+    pub offset: Option<u16>,
+    /// The type of write to use. This maps to the `type` option. If it is `None` then BlueZ
+    /// chooses a type based on the flags of the characteristic, preferring a write with response.
+    pub write_type: Option<WriteType>,
+}
+
+impl From<&WriteOptions> for PropMap {
+    fn from(options: &WriteOptions) -> Self {
+        let mut map: PropMap = HashMap::new();
+        if let Some(offset) = options.offset {
+            map.insert("offset".to_string(), Variant(Box::new(offset)));
+        }
+        if let Some(write_type) = options.write_type {
+            map.insert(
+                "type".to_string(),
+                Variant(Box::new(write_type.as_str().to_string())),
+            );
+        }
+        map
+    }
+}
+
 /// A connection to the Bluetooth daemon. This can be cheaply cloned and passed around to be used
 /// from different places. It is the main entry point to the library.
 #[derive(Clone)]
 pub struct BluetoothSession {
     connection: Arc<SyncConnection>,
+    /// The D-Bus bus name of the BlueZ daemon.
+    service_name: BusName<'static>,
+    /// The adapters on the system, so that they don't need to be looked up every time.
+    adapter_cache: Arc<AdapterCache>,
+    /// When an advertisement was last received from each device, if enabled.
+    last_seen: Arc<LastSeenTracker>,
+    /// The total number of events dropped by buffered event streams.
+    dropped_events: Arc<AtomicU64>,
+    /// The last value of each characteristic, if enabled.
+    value_cache: Arc<ValueCache>,
+    /// The discovery filter which this session last set on each adapter.
+    discovery_filters: Arc<Mutex<HashMap<AdapterId, DiscoveryFilter>>>,
+    /// Held while adding the D-Bus matches for the adapter cache or similar, so that concurrent
+    /// callers don't add them twice.
+    adding_matches: Arc<tokio::sync::Mutex<()>>,
 }
 
 impl Debug for BluetoothSession {
@@ -230,6 +523,18 @@ impl BluetoothSession {
     /// probably restart the process.
     pub async fn new(
     ) -> Result<(impl Future<Output = Result<(), SpawnError>>, Self), BluetoothError> {
+        Self::new_with_service_name(DEFAULT_SERVICE_NAME).await
+    }
+
+    /// Establish a new D-Bus connection to communicate with a BlueZ daemon (or a mock of it) which
+    /// uses the given D-Bus bus name rather than the default of `org.bluez`.
+    ///
+    /// This is mainly useful for testing. Returns a tuple of (join handle, Self) like `new`.
+    pub async fn new_with_service_name(
+        service_name: &str,
+    ) -> Result<(impl Future<Output = Result<(), SpawnError>>, Self), BluetoothError> {
+        let service_name =
+            BusName::new(service_name.to_owned()).map_err(BluetoothError::InvalidServiceName)?;
         // Connect to the D-Bus system bus (this is blocking, unfortunately).
         let (dbus_resource, connection) = dbus_tokio::connection::new_system_sync()?;
         // The resource is a task that should be spawned onto a tokio compatible
@@ -238,9 +543,21 @@ impl BluetoothSession {
             let err = dbus_resource.await;
             Err(SpawnError::DbusConnectionLost(err))
         });
+        // Several matches may apply to the same signal, e.g. for the adapter cache and for an event
+        // stream, so make sure that each gets a copy.
+        connection.set_signal_match_mode(true);
         Ok((
             dbus_handle.map(|res| Ok(res??)),
-            BluetoothSession { connection },
+            BluetoothSession {
+                connection,
+                service_name,
+                adapter_cache: Arc::new(AdapterCache::default()),
+                last_seen: Arc::new(LastSeenTracker::default()),
+                dropped_events: Arc::new(AtomicU64::new(0)),
+                value_cache: Arc::new(ValueCache::default()),
+                discovery_filters: Arc::default(),
+                adding_matches: Arc::default(),
+            },
         ))
     }
 
@@ -261,22 +578,28 @@ impl BluetoothSession {
     /// clients on the system using Bluetooth as well.
     ///
     /// In most common cases, `DiscoveryFilter::default()` is fine.
+    #[cfg_attr(feature = "tracing", tracing::instrument(skip(self)))]
     pub async fn start_discovery_with_filter(
         &self,
         discovery_filter: &DiscoveryFilter,
     ) -> Result<(), BluetoothError> {
-        let adapters = self.get_adapters().await?;
+        let adapters = self.get_adapter_ids().await?;
         if adapters.is_empty() {
             return Err(BluetoothError::NoBluetoothAdapters);
         }
 
         for adapter_id in adapters {
             log::trace!("Starting discovery on adapter {}", adapter_id);
+            self.set_powered_and_wait(&adapter_id, true, ADAPTER_POWER_ON_TIMEOUT)
+                .await?;
             let adapter = self.adapter(&adapter_id);
-            adapter.set_powered(true).await?;
             adapter
                 .set_discovery_filter(discovery_filter.into())
                 .await?;
+            self.discovery_filters
+                .lock()
+                .unwrap()
+                .insert(adapter_id.clone(), discovery_filter.to_owned());
             adapter
                 .start_discovery()
                 .await
@@ -285,9 +608,46 @@ impl BluetoothSession {
         Ok(())
     }
 
+    /// Power on all Bluetooth adapters, set the given discovery filter, and then start scanning for
+    /// devices, like `start_discovery_with_filter`. Returns a guard which will stop discovery when
+    /// it is dropped, so that an early return or panic doesn't leave the adapters scanning. See
+    /// [`DiscoverySession`] for caveats.
+    pub async fn start_discovery_session(
+        &self,
+        discovery_filter: &DiscoveryFilter,
+    ) -> Result<DiscoverySession, BluetoothError> {
+        self.start_discovery_with_filter(discovery_filter).await?;
+        Ok(DiscoverySession::new(self.clone()))
+    }
+
+    /// Clear any discovery filter previously set on the given Bluetooth adapter, so that
+    /// subsequent scanning is unfiltered.
+    ///
+    /// BlueZ keeps a separate filter for each D-Bus client, so this only clears the filter set by
+    /// this session (and its clones), not those of other clients.
+    pub async fn clear_discovery_filter(&self, adapter: &AdapterId) -> Result<(), BluetoothError> {
+        self.adapter(adapter)
+            .set_discovery_filter(HashMap::new())
+            .await?;
+        self.discovery_filters.lock().unwrap().remove(adapter);
+        Ok(())
+    }
+
+    /// Get the discovery filter which this session (or one of its clones) last set on the given
+    /// adapter with `start_discovery_with_filter` or similar, or `None` if it has not set one or
+    /// has since cleared it with `clear_discovery_filter`.
+    ///
+    /// BlueZ merges the filters of all clients, so the filter actually used by the adapter may be
+    /// broader than this. BlueZ discards a client's filter when it stops discovery, so this also
+    /// returns `None` after `stop_discovery`.
+    pub fn get_my_discovery_filter(&self, adapter: &AdapterId) -> Option<DiscoveryFilter> {
+        self.discovery_filters.lock().unwrap().get(adapter).cloned()
+    }
+
     /// Stop scanning for devices on all Bluetooth adapters.
+    #[cfg_attr(feature = "tracing", tracing::instrument(skip(self)))]
     pub async fn stop_discovery(&self) -> Result<(), BluetoothError> {
-        let adapters = self.get_adapters().await?;
+        let adapters = self.get_adapter_ids().await?;
         if adapters.is_empty() {
             return Err(BluetoothError::NoBluetoothAdapters);
         }
@@ -295,15 +655,153 @@ impl BluetoothSession {
         for adapter_id in adapters {
             let adapter = self.adapter(&adapter_id);
             adapter.stop_discovery().await?;
+            self.discovery_filters.lock().unwrap().remove(&adapter_id);
+        }
+
+        Ok(())
+    }
+
+    /// Power-cycle the given Bluetooth adapter, which may recover it if it has got into a bad
+    /// state. Any devices connected through it will be disconnected.
+    ///
+    /// Returns `BluetoothError::Timeout` if the adapter doesn't come back on within a few seconds.
+    #[cfg_attr(feature = "tracing", tracing::instrument(skip(self, adapter), fields(adapter = %adapter)))]
+    pub async fn reset_adapter(&self, adapter: &AdapterId) -> Result<(), BluetoothError> {
+        let adapter_proxy = self.adapter(adapter);
+        adapter_proxy.set_powered(false).await?;
+        time::sleep(ADAPTER_RESET_DELAY).await;
+
+        self.set_powered_and_wait(adapter, true, ADAPTER_POWER_ON_TIMEOUT)
+            .await
+    }
+
+    /// Power the given Bluetooth adapter on or off, and wait until its `Powered` property reflects
+    /// the change. BlueZ returns from setting the property before the adapter has actually changed
+    /// state, so other operations such as starting discovery may fail if they are attempted
+    /// immediately after.
+    ///
+    /// Returns `BluetoothError::Timeout` if the adapter doesn't reach the requested state within
+    /// `timeout`.
+    #[cfg_attr(feature = "tracing", tracing::instrument(skip(self, adapter), fields(adapter = %adapter)))]
+    pub async fn set_powered_and_wait(
+        &self,
+        adapter: &AdapterId,
+        powered: bool,
+        timeout: Duration,
+    ) -> Result<(), BluetoothError> {
+        let adapter_proxy = self.adapter(adapter);
+        if adapter_proxy.powered().await? == powered {
+            return Ok(());
+        }
+        // Subscribe before changing the state so that we can't miss the event.
+        let events = self.filtered_event_stream(Some(adapter)).await?;
+        adapter_proxy.set_powered(powered).await?;
+        if adapter_proxy.powered().await? == powered {
+            return Ok(());
+        }
+        let mut changed = Box::pin(events.filter(move |event| {
+            futures::future::ready(match event {
+                BluetoothEvent::Adapter {
+                    event:
+                        AdapterEvent::Powered {
+                            powered: now_powered,
+                        },
+                    ..
+                } => *now_powered == powered,
+                _ => false,
+            })
+        }));
+        match time::timeout(timeout, changed.next()).await {
+            Ok(Some(_)) => Ok(()),
+            Ok(None) | Err(_) => Err(BluetoothError::Timeout),
+        }
+    }
+
+    /// Get information about all the Bluetooth adapters on the system.
+    ///
+    /// The list of adapters is cached after the first call, and the cache is invalidated when an
+    /// adapter is added or removed. Note that this means that the state of each adapter, such as
+    /// whether it is powered, may be out of date; use `get_adapter_info` or `refresh_adapters` to
+    /// get the current state.
+    ///
+    /// The D-Bus matches needed to invalidate the cache are added on the first call. If that fails
+    /// then the adapters are looked up every time instead.
+    pub async fn get_adapters(&self) -> Result<Vec<AdapterInfo>, BluetoothError> {
+        self.enable_adapter_cache().await;
+        match self.adapter_cache.get() {
+            (Some(adapters), _) => Ok(adapters),
+            (None, generation) => self.fetch_adapters(generation).await,
+        }
+    }
+
+    /// Look up all the Bluetooth adapters on the system again, update the cache used by
+    /// `get_adapters`, and return them.
+    pub async fn refresh_adapters(&self) -> Result<Vec<AdapterInfo>, BluetoothError> {
+        self.enable_adapter_cache().await;
+        let (_, generation) = self.adapter_cache.get();
+        self.fetch_adapters(generation).await
+    }
+
+    /// Add the D-Bus matches needed to keep the adapter cache up to date and then enable it, if
+    /// this hasn't already been done. If adding them fails then the error is logged and the cache
+    /// is left disabled.
+    async fn enable_adapter_cache(&self) {
+        if self.adapter_cache.is_enabled() {
+            return;
         }
+        let _adding_matches = self.adding_matches.lock().await;
+        if self.adapter_cache.is_enabled() {
+            return;
+        }
+        let adapter_cache = self.adapter_cache.clone();
+        match self
+            .add_match_callbacks(
+                AdapterCache::match_rules(&self.service_name),
+                move |message| adapter_cache.handle_message(&message),
+            )
+            .await
+        {
+            Ok(()) => self.adapter_cache.enable(),
+            Err(e) => log::warn!("Error adding D-Bus matches for adapter cache: {}", e),
+        }
+    }
 
+    /// Add D-Bus matches for all the given rules, passing each matching message to `callback`.
+    ///
+    /// If adding any of them fails then those which were already added are removed again, so
+    /// that trying again later doesn't leave duplicate callbacks.
+    async fn add_match_callbacks(
+        &self,
+        match_rules: Vec<MatchRule<'static>>,
+        callback: impl Fn(Message) + Clone + Send + 'static,
+    ) -> Result<(), dbus::Error> {
+        let mut added = vec![];
+        for match_rule in match_rules {
+            match self.connection.add_match(match_rule).await {
+                Ok(msg_match) => {
+                    let callback = callback.clone();
+                    let msg_match = msg_match.msg_cb(move |message| {
+                        callback(message);
+                        true
+                    });
+                    added.push(msg_match.token());
+                }
+                Err(e) => {
+                    for token in added {
+                        if let Err(e) = self.connection.remove_match(token).await {
+                            log::warn!("Error removing D-Bus match: {}", e);
+                        }
+                    }
+                    return Err(e);
+                }
+            }
+        }
         Ok(())
     }
 
-    /// Get a list of all Bluetooth adapters on the system.
-    async fn get_adapters(&self) -> Result<Vec<AdapterId>, dbus::Error> {
+    async fn fetch_adapters(&self, generation: u64) -> Result<Vec<AdapterInfo>, BluetoothError> {
         let bluez_root = Proxy::new(
-            "org.bluez",
+            self.service_name.clone(),
             "/",
             DBUS_METHOD_CALL_TIMEOUT,
             self.connection.clone(),
@@ -311,6 +809,33 @@ impl BluetoothSession {
         // TODO: See whether there is a way to do this with introspection instead, rather than
         // getting lots of objects we don't care about.
         let tree = bluez_root.get_managed_objects().await?;
+        let adapters = tree
+            .into_iter()
+            .filter_map(|(object_path, interfaces)| {
+                let adapter_properties = OrgBluezAdapter1Properties::from_interfaces(&interfaces)?;
+                AdapterInfo::from_properties(AdapterId { object_path }, adapter_properties)
+                    .map_err(|err| log::warn!("Error getting adapter info: {}", err))
+                    .ok()
+            })
+            .collect::<Vec<_>>();
+        self.adapter_cache.set(adapters.clone(), generation);
+        Ok(adapters)
+    }
+
+    /// Get the IDs of all the Bluetooth adapters on the system. These are taken from the adapter
+    /// cache if it is filled, otherwise they are looked up without parsing any of the adapters'
+    /// properties.
+    async fn get_adapter_ids(&self) -> Result<Vec<AdapterId>, BluetoothError> {
+        if let (Some(adapters), _) = self.adapter_cache.get() {
+            return Ok(adapters.into_iter().map(|adapter| adapter.id).collect());
+        }
+        let bluez_root = Proxy::new(
+            self.service_name.clone(),
+            "/",
+            DBUS_METHOD_CALL_TIMEOUT,
+            self.connection.clone(),
+        );
+        let tree = bluez_root.get_managed_objects().await?;
         Ok(tree
             .into_iter()
             .filter_map(|(object_path, interfaces)| {
@@ -321,10 +846,149 @@ impl BluetoothSession {
             .collect())
     }
 
+    /// Get the UUIDs of the BlueZ experimental features which are currently enabled on the given
+    /// adapter.
+    ///
+    /// Versions of BlueZ before 5.59 don't report this, in which case an empty list is returned.
+    pub async fn get_experimental_features(
+        &self,
+        adapter: &AdapterId,
+    ) -> Result<Vec<Uuid>, BluetoothError> {
+        let properties = self
+            .adapter(adapter)
+            .get_all(ORG_BLUEZ_ADAPTER1_NAME)
+            .await?;
+        if let Some(features) = prop_cast::<Vec<String>>(&properties, "ExperimentalFeatures") {
+            Ok(features
+                .iter()
+                .map(|uuid| Uuid::parse_str(uuid))
+                .collect::<Result<_, _>>()?)
+        } else {
+            Ok(vec![])
+        }
+    }
+
+    /// Get a best-effort guess of the version of the BlueZ daemon, as (major, minor).
+    ///
+    /// BlueZ doesn't report its version over D-Bus, so this is inferred from which interfaces and
+    /// properties its adapters expose. It is therefore a lower bound: the real version may be newer
+    /// than reported, but should not be older.
+    pub async fn get_bluez_version(&self) -> Result<(u16, u16), BluetoothError> {
+        let bluez_root = Proxy::new(
+            self.service_name.clone(),
+            "/",
+            DBUS_METHOD_CALL_TIMEOUT,
+            self.connection.clone(),
+        );
+        let tree = bluez_root.get_managed_objects().await?;
+        tree.values()
+            .filter(|interfaces| interfaces.contains_key(ORG_BLUEZ_ADAPTER1_NAME))
+            .map(version::infer_version)
+            .max()
+            .ok_or(BluetoothError::NoBluetoothAdapters)
+    }
+
+    /// Start tracking when an advertisement was last received from each device, for this session
+    /// and all its clones, so that `last_seen` and `is_recently_seen` can be used.
+    ///
+    /// This adds D-Bus matches for all device property changes, so it isn't done unless needed.
+    pub async fn enable_last_seen_tracking(&self) -> Result<(), BluetoothError> {
+        if self.last_seen.is_enabled() {
+            return Ok(());
+        }
+        let _adding_matches = self.adding_matches.lock().await;
+        if self.last_seen.is_enabled() {
+            return Ok(());
+        }
+        let last_seen = self.last_seen.clone();
+        self.add_match_callbacks(
+            LastSeenTracker::match_rules(&self.service_name),
+            move |message| last_seen.handle_message(message, Instant::now()),
+        )
+        .await?;
+        self.last_seen.enable(Instant::now());
+        Ok(())
+    }
+
+    /// Get the time at which an advertisement was last received from the given device, or `None`
+    /// if none has been received since tracking was enabled with `enable_last_seen_tracking`.
+    ///
+    /// Advertisements are only received while discovery is active, so this depends on
+    /// `start_discovery` having been called.
+    pub fn last_seen(&self, id: &DeviceId) -> Option<Instant> {
+        self.last_seen.get(id)
+    }
+
+    /// Returns whether an advertisement has been received from the given device within the given
+    /// duration, i.e. whether it is probably still in range.
+    ///
+    /// Advertisements are only received while discovery is active, so this depends on
+    /// `start_discovery` having been called, and will return `false` for all devices otherwise.
+    /// Likewise, it depends on `enable_last_seen_tracking` having been called.
+    pub fn is_recently_seen(&self, id: &DeviceId, within: Duration) -> bool {
+        match self.last_seen(id) {
+            Some(last_seen) => last_seen.elapsed() <= within,
+            None => false,
+        }
+    }
+
+    /// Remove the given device from BlueZ, along with any pairing information. It will be found
+    /// again if it is still advertising while discovery is active.
+    #[cfg_attr(feature = "tracing", tracing::instrument(skip(self, id), fields(id = %id)))]
+    pub async fn remove_device(&self, id: &DeviceId) -> Result<(), BluetoothError> {
+        Ok(self
+            .adapter(&id.adapter())
+            .remove_device(id.object_path.clone())
+            .await?)
+    }
+
+    /// Remove all devices on the given adapter from which no advertisement has been received for
+    /// longer than `older_than`, to stop BlueZ's list of devices growing without bound. Returns
+    /// the IDs of the devices removed.
+    ///
+    /// Devices which are connected or paired (including bonded) are never removed. Like
+    /// `last_seen`, this depends on discovery being active; a device which hasn't been seen at all
+    /// since tracking was enabled is only considered stale once tracking has been running for
+    /// longer than `older_than`. Tracking is enabled by the first call if it wasn't already.
+    pub async fn remove_stale_devices(
+        &self,
+        adapter: &AdapterId,
+        older_than: Duration,
+    ) -> Result<Vec<DeviceId>, BluetoothError> {
+        self.enable_last_seen_tracking().await?;
+        let now = Instant::now();
+        let stale_devices: Vec<DeviceId> = self
+            .get_devices()
+            .await?
+            .into_iter()
+            .filter(|device| {
+                device.adapter == *adapter
+                    && !device.connected
+                    && !device.paired
+                    && self.last_seen.is_stale(&device.id, older_than, now)
+            })
+            .map(|device| device.id)
+            .collect();
+        for id in &stale_devices {
+            self.remove_device(id).await?;
+        }
+        Ok(stale_devices)
+    }
+
+    /// Get the total number of events which have been dropped by event streams from this session
+    /// because their consumers couldn't keep up, since the session was created.
+    ///
+    /// Events are only dropped by streams from `event_stream_with_capacity` with
+    /// `OverflowPolicy::DropOldest`; other event streams are unbounded, so never lose events. A
+    /// data logger can check that this hasn't increased to be sure that its record is complete.
+    pub fn dropped_message_count(&self) -> u64 {
+        self.dropped_events.load(Ordering::Relaxed)
+    }
+
     /// Get a list of all Bluetooth devices which have been discovered so far.
     pub async fn get_devices(&self) -> Result<Vec<DeviceInfo>, BluetoothError> {
         let bluez_root = Proxy::new(
-            "org.bluez",
+            self.service_name.clone(),
             "/",
             DBUS_METHOD_CALL_TIMEOUT,
             self.connection.clone(),
@@ -341,14 +1005,71 @@ impl BluetoothSession {
         Ok(devices)
     }
 
+    /// Get counts of the adapters, devices, services, characteristics and descriptors which BlueZ
+    /// currently knows about, for diagnostics.
+    pub async fn get_object_summary(&self) -> Result<ObjectSummary, BluetoothError> {
+        let bluez_root = Proxy::new(
+            self.service_name.clone(),
+            "/",
+            DBUS_METHOD_CALL_TIMEOUT,
+            self.connection.clone(),
+        );
+        let tree = bluez_root.get_managed_objects().await?;
+        Ok(ObjectSummary::from_tree(&tree))
+    }
+
+    /// Get a list of the Bluetooth devices which are currently connected through the given
+    /// adapter.
+    pub async fn get_adapter_connected_devices(
+        &self,
+        adapter: &AdapterId,
+    ) -> Result<Vec<DeviceInfo>, BluetoothError> {
+        Ok(self
+            .get_devices()
+            .await?
+            .into_iter()
+            .filter(|device| device.connected && device.id.adapter() == *adapter)
+            .collect())
+    }
+
+    /// Returns whether BlueZ has GATT services for the given device from its cache of a previous
+    /// connection, which haven't yet been resolved against the device on the current connection.
+    ///
+    /// BlueZ keeps the GATT database of paired devices (or all devices, depending on the `Cache`
+    /// setting in `main.conf`) between connections, and only rediscovers services if the device
+    /// indicates that they have changed. A device which changes its services without indicating
+    /// this, e.g. after a firmware update, may leave BlueZ with a stale database. There is no way
+    /// to force BlueZ to rediscover services other than removing the device with `remove_device`
+    /// (which also removes any pairing) and then discovering and connecting to it again.
+    ///
+    /// Once services have been resolved for the current connection this returns false, as BlueZ
+    /// doesn't expose whether they were resolved from the cache. Use `get_gatt_database` and
+    /// `is_gatt_database_current` to check whether a device's services have since changed.
+    pub async fn has_cached_services(&self, device: &DeviceId) -> Result<bool, BluetoothError> {
+        let device_proxy = self.device(device);
+        if device_proxy.services_resolved().await? {
+            return Ok(false);
+        }
+        let device_node = device_proxy.introspect_parse().await?;
+        Ok(device_node
+            .nodes
+            .iter()
+            .any(|subnode| matches!(&subnode.name, Some(name) if name.starts_with("service"))))
+    }
+
     /// Get a list of all GATT services which the given Bluetooth device offers.
     ///
-    /// Note that this won't be filled in until the device is connected.
+    /// Returns `BluetoothError::ServicesNotResolved` if the device's services haven't been
+    /// resolved yet, which won't happen until the device is connected.
     pub async fn get_services(
         &self,
         device: &DeviceId,
     ) -> Result<Vec<ServiceInfo>, BluetoothError> {
-        let device_node = self.device(device).introspect_parse().await?;
+        let device_proxy = self.device(device);
+        if !device_proxy.services_resolved().await? {
+            return Err(BluetoothError::ServicesNotResolved);
+        }
+        let device_node = device_proxy.introspect_parse().await?;
         let mut services = vec![];
         for subnode in device_node.nodes {
             let subnode_name = subnode.name.as_ref().unwrap();
@@ -371,6 +1092,21 @@ impl BluetoothSession {
         Ok(services)
     }
 
+    /// Get a list of all GATT services which the given Bluetooth device offers, like
+    /// `get_services`, but failing with `BluetoothError::IntrospectionTimeout` if it doesn't
+    /// complete within the given timeout rather than waiting for the default D-Bus method call
+    /// timeout. This is useful when a device is connected but not responding.
+    pub async fn get_services_with_timeout(
+        &self,
+        device: &DeviceId,
+        timeout: Duration,
+    ) -> Result<Vec<ServiceInfo>, BluetoothError> {
+        match time::timeout(timeout, self.get_services(device)).await {
+            Ok(result) => result,
+            Err(_) => Err(BluetoothError::IntrospectionTimeout),
+        }
+    }
+
     /// Get a list of all characteristics on the given GATT service.
     pub async fn get_characteristics(
         &self,
@@ -386,14 +1122,7 @@ impl BluetoothSession {
                 let characteristic_id = CharacteristicId {
                     object_path: format!("{}/{}", service.object_path, subnode_name).into(),
                 };
-                let characteristic = self.characteristic(&characteristic_id);
-                let uuid = Uuid::parse_str(&characteristic.uuid().await?)?;
-                let flags = characteristic.flags().await?;
-                characteristics.push(CharacteristicInfo {
-                    id: characteristic_id,
-                    uuid,
-                    flags: flags.try_into()?,
-                });
+                characteristics.push(self.get_characteristic_info(&characteristic_id).await?);
             }
         }
         Ok(characteristics)
@@ -417,16 +1146,190 @@ impl BluetoothSession {
                 let descriptor_id = DescriptorId {
                     object_path: format!("{}/{}", characteristic.object_path, subnode_name).into(),
                 };
-                let uuid = Uuid::parse_str(&self.descriptor(&descriptor_id).uuid().await?)?;
-                descriptors.push(DescriptorInfo {
-                    id: descriptor_id,
-                    uuid,
-                });
+                descriptors.push(self.get_descriptor_info(&descriptor_id).await?);
             }
         }
         Ok(descriptors)
     }
 
+    /// Get a list of all characteristics of all GATT services of the given device, each along with
+    /// the service it belongs to.
+    ///
+    /// The services are introspected concurrently.
+    pub async fn get_all_characteristics(
+        &self,
+        device: &DeviceId,
+    ) -> Result<Vec<(ServiceInfo, CharacteristicInfo)>, BluetoothError> {
+        let services = self.get_services(device).await?;
+        let characteristics = try_join_all(services.into_iter().map(|service| async move {
+            let characteristics = self.get_characteristics(&service.id).await?;
+            Ok::<_, BluetoothError>(
+                characteristics
+                    .into_iter()
+                    .map(move |characteristic| (service.clone(), characteristic)),
+            )
+        }))
+        .await?;
+        Ok(characteristics.into_iter().flatten().collect())
+    }
+
+    /// Get a list of all descriptors on all characteristics of all GATT services of the given
+    /// device, each along with the UUID of the characteristic it belongs to.
+    ///
+    /// The services and characteristics are introspected concurrently.
+    pub async fn get_all_descriptors(
+        &self,
+        device: &DeviceId,
+    ) -> Result<Vec<(Uuid, DescriptorInfo)>, BluetoothError> {
+        let characteristics = self.get_all_characteristics(device).await?;
+        let descriptors = try_join_all(characteristics.iter().map(
+            |(_, characteristic)| async move {
+                let descriptors = self.get_descriptors(&characteristic.id).await?;
+                Ok::<_, BluetoothError>(
+                    descriptors
+                        .into_iter()
+                        .map(move |descriptor| (characteristic.uuid, descriptor)),
+                )
+            },
+        ))
+        .await?;
+        Ok(descriptors.into_iter().flatten().collect())
+    }
+
+    /// Get a snapshot of all GATT services, characteristics and descriptors of the given device.
+    ///
+    /// This introspects the entire GATT tree of the device, which may be slow for complex devices,
+    /// though the services and characteristics are introspected concurrently. The result can be
+    /// cached, checked later with `is_gatt_database_current`, and invalidated while connected with
+    /// `subscribe_gatt_database_changes`.
+    pub async fn get_gatt_database(
+        &self,
+        device: &DeviceId,
+    ) -> Result<GattDatabase, BluetoothError> {
+        let services = try_join_all(self.get_services(device).await?.into_iter().map(
+            |service| async move {
+                let characteristics = try_join_all(
+                    self.get_characteristics(&service.id)
+                        .await?
+                        .into_iter()
+                        .map(|characteristic| async move {
+                            let descriptors = self.get_descriptors(&characteristic.id).await?;
+                            Ok::<_, BluetoothError>(GattDatabaseCharacteristic {
+                                info: characteristic,
+                                descriptors,
+                            })
+                        }),
+                )
+                .await?;
+                Ok::<_, BluetoothError>(GattDatabaseService {
+                    info: service,
+                    characteristics,
+                })
+            },
+        ))
+        .await?;
+        let mut database = GattDatabase {
+            services,
+            database_hash: None,
+        };
+        if let Some(hash_characteristic) = database.database_hash_characteristic() {
+            database.database_hash =
+                Some(self.read_characteristic_value(hash_characteristic).await?);
+        }
+        Ok(database)
+    }
+
+    /// Check whether a previously fetched GATT database snapshot still matches the device, by
+    /// comparing the value of its Database Hash characteristic. This doesn't need to introspect
+    /// the device, so is much faster than calling `get_gatt_database` again.
+    ///
+    /// Returns false if the device doesn't have a Database Hash characteristic, as there is then
+    /// no way to tell whether it has changed. The device must be connected.
+    ///
+    /// This is for validating a snapshot loaded from a file when reconnecting to a device. The
+    /// Service Changed characteristic can't be used for that, as its indications are only received
+    /// while connected, so use `subscribe_gatt_database_changes` to invalidate the snapshot after
+    /// that.
+    pub async fn is_gatt_database_current(
+        &self,
+        database: &GattDatabase,
+    ) -> Result<bool, BluetoothError> {
+        if let (Some(hash_characteristic), Some(database_hash)) = (
+            database.database_hash_characteristic(),
+            &database.database_hash,
+        ) {
+            let current_hash = self.read_characteristic_value(hash_characteristic).await?;
+            Ok(&current_hash == database_hash)
+        } else {
+            Ok(false)
+        }
+    }
+
+    /// Subscribe to indications from the Service Changed characteristic of the given device, and
+    /// get a stream of the inclusive ranges of attribute handles which have changed. Any cached
+    /// [`GattDatabase`] for the device should be discarded when an item is received.
+    ///
+    /// Some versions of BlueZ handle Service Changed indications themselves and don't expose the
+    /// Generic Attribute service, in which case this returns `BluetoothError::UUIDNotFound`.
+    pub async fn subscribe_service_changed(
+        &self,
+        device: &DeviceId,
+    ) -> Result<impl Stream<Item = (u16, u16)>, BluetoothError> {
+        let characteristic = self
+            .get_service_characteristic_by_uuid(
+                device,
+                well_known::generic_attribute::SERVICE,
+                well_known::generic_attribute::SERVICE_CHANGED,
+            )
+            .await?;
+        self.service_changed_stream(&characteristic.id).await
+    }
+
+    /// Subscribe to indications from the Service Changed characteristic recorded in the given
+    /// GATT database snapshot, like `subscribe_service_changed` but without introspecting the
+    /// device again. The snapshot should be discarded when an item is received. The device must
+    /// be connected.
+    ///
+    /// Returns `BluetoothError::UUIDNotFound` if the snapshot doesn't include a Service Changed
+    /// characteristic.
+    pub async fn subscribe_gatt_database_changes(
+        &self,
+        database: &GattDatabase,
+    ) -> Result<impl Stream<Item = (u16, u16)>, BluetoothError> {
+        let characteristic =
+            database
+                .service_changed_characteristic()
+                .ok_or(BluetoothError::UUIDNotFound {
+                    uuid: well_known::generic_attribute::SERVICE_CHANGED,
+                })?;
+        self.service_changed_stream(characteristic).await
+    }
+
+    /// Subscribe to indications from the given Service Changed characteristic, and get a stream of
+    /// the ranges of attribute handles which have changed.
+    async fn service_changed_stream(
+        &self,
+        characteristic: &CharacteristicId,
+    ) -> Result<impl Stream<Item = (u16, u16)>, BluetoothError> {
+        let events = self.characteristic_event_stream(characteristic).await?;
+        self.start_notify(characteristic).await?;
+        Ok(events.filter_map(|event| async move {
+            match event {
+                BluetoothEvent::Characteristic {
+                    event: CharacteristicEvent::Value { value },
+                    ..
+                } => {
+                    let range = parse_service_changed(&value);
+                    if range.is_none() {
+                        log::warn!("Invalid Service Changed value {:?}", value);
+                    }
+                    range
+                }
+                _ => None,
+            }
+        }))
+    }
+
     /// Find a GATT service with the given UUID advertised by the given device, if any.
     ///
     /// Note that this generally won't work until the device is connected.
@@ -456,10 +1359,55 @@ impl BluetoothSession {
             .ok_or(BluetoothError::UUIDNotFound { uuid })
     }
 
-    /// Convenience method to get a GATT charactacteristic with the given UUID advertised by a
-    /// device as part of the given service.
+    /// Find a GATT service with the given 16 or 32-bit short UUID advertised by the given device,
+    /// such as `0x180f` for the standard Battery Service.
     ///
-    /// This is equivalent to calling `get_service_by_uuid` and then `get_characteristic_by_uuid`.
+    /// This is equivalent to calling `get_service_by_uuid` with `uuid_from_u32(short_uuid)`.
+    pub async fn get_service_by_short_uuid(
+        &self,
+        device: &DeviceId,
+        short_uuid: u32,
+    ) -> Result<ServiceInfo, BluetoothError> {
+        self.get_service_by_uuid(device, uuid_from_u32(short_uuid))
+            .await
+    }
+
+    /// Find a GATT characteristic with the given 16 or 32-bit short UUID advertised by a device as
+    /// part of the given service, such as `0x2a19` for the standard Battery Level characteristic.
+    ///
+    /// This is equivalent to calling `get_characteristic_by_uuid` with
+    /// `uuid_from_u32(short_uuid)`.
+    pub async fn get_characteristic_by_short_uuid(
+        &self,
+        service: &ServiceId,
+        short_uuid: u32,
+    ) -> Result<CharacteristicInfo, BluetoothError> {
+        self.get_characteristic_by_uuid(service, uuid_from_u32(short_uuid))
+            .await
+    }
+
+    /// Find a GATT characteristic with the given UUID in any service of the given device. This is
+    /// useful when the characteristic UUID is known but the service UUID isn't.
+    ///
+    /// If more than one service has a characteristic with the UUID then an arbitrary one of them is
+    /// returned.
+    pub async fn find_characteristic(
+        &self,
+        device: &DeviceId,
+        uuid: Uuid,
+    ) -> Result<CharacteristicInfo, BluetoothError> {
+        let characteristics = self.get_all_characteristics(device).await?;
+        characteristics
+            .into_iter()
+            .map(|(_, characteristic)| characteristic)
+            .find(|characteristic| characteristic.uuid == uuid)
+            .ok_or(BluetoothError::UUIDNotFound { uuid })
+    }
+
+    /// Convenience method to get a GATT charactacteristic with the given UUID advertised by a
+    /// device as part of the given service.
+    ///
+    /// This is equivalent to calling `get_service_by_uuid` and then `get_characteristic_by_uuid`.
     pub async fn get_service_characteristic_by_uuid(
         &self,
         device: &DeviceId,
@@ -471,6 +1419,62 @@ impl BluetoothSession {
             .await
     }
 
+    /// Get the raw D-Bus introspection XML of the BlueZ object with the given path, such as an
+    /// adapter, device, service or characteristic. This is intended for debugging, e.g. to attach
+    /// to a bug report if a device's GATT services aren't found as expected.
+    pub async fn introspect_xml(
+        &self,
+        path: impl Into<Path<'static>>,
+    ) -> Result<String, BluetoothError> {
+        let proxy = Proxy::new(
+            self.service_name.clone(),
+            path.into(),
+            DBUS_METHOD_CALL_TIMEOUT,
+            self.connection.clone(),
+        );
+        Ok(proxy.introspect().await?)
+    }
+
+    /// Recursively introspect the D-Bus objects under the given root path, returning the tree of
+    /// object paths and the interfaces each implements. This is mostly useful for debugging.
+    ///
+    /// Children are only introspected up to `max_depth` levels below the root, so a `max_depth`
+    /// of 0 returns just the root object with no children.
+    pub async fn dump_tree(
+        &self,
+        root: impl Into<Path<'static>>,
+        max_depth: usize,
+    ) -> Result<TreeNode, BluetoothError> {
+        self.dump_tree_inner(root.into(), max_depth).await
+    }
+
+    fn dump_tree_inner(
+        &self,
+        path: Path<'static>,
+        max_depth: usize,
+    ) -> BoxFuture<'_, Result<TreeNode, BluetoothError>> {
+        async move {
+            let proxy = Proxy::new(
+                self.service_name.clone(),
+                path.clone(),
+                DBUS_METHOD_CALL_TIMEOUT,
+                self.connection.clone(),
+            );
+            let node = proxy.introspect_parse().await?;
+            let mut tree_node = TreeNode::from_node(path.clone(), &node);
+            if max_depth > 0 {
+                tree_node.children = try_join_all(
+                    TreeNode::child_paths(&path, &node)
+                        .into_iter()
+                        .map(|child| self.dump_tree_inner(child, max_depth - 1)),
+                )
+                .await?;
+            }
+            Ok(tree_node)
+        }
+        .boxed()
+    }
+
     /// Get information about the given Bluetooth device.
     pub async fn get_device_info(&self, id: &DeviceId) -> Result<DeviceInfo, BluetoothError> {
         let device = self.device(&id);
@@ -478,6 +1482,22 @@ impl BluetoothSession {
         DeviceInfo::from_properties(id.to_owned(), OrgBluezDevice1Properties(&properties))
     }
 
+    /// Get information about the given Bluetooth adapter.
+    pub async fn get_adapter_info(&self, id: &AdapterId) -> Result<AdapterInfo, BluetoothError> {
+        let adapter = self.adapter(id);
+        let properties = adapter.get_all(ORG_BLUEZ_ADAPTER1_NAME).await?;
+        AdapterInfo::from_properties(id.to_owned(), OrgBluezAdapter1Properties(&properties))
+    }
+
+    /// Get the MAC address of the given Bluetooth adapter.
+    ///
+    /// This only reads the single property, so is cheaper than `get_adapter_info` when only the
+    /// address is needed, e.g. to look up configuration for the adapter.
+    pub async fn get_adapter_address(&self, id: &AdapterId) -> Result<MacAddress, BluetoothError> {
+        let address = self.adapter(id).address().await?;
+        Ok(MacAddress(address))
+    }
+
     /// Get information about the given GATT service.
     pub async fn get_service_info(&self, id: &ServiceId) -> Result<ServiceInfo, BluetoothError> {
         let service = self.service(&id);
@@ -496,13 +1516,13 @@ impl BluetoothSession {
         id: &CharacteristicId,
     ) -> Result<CharacteristicInfo, BluetoothError> {
         let characteristic = self.characteristic(&id);
-        let uuid = Uuid::parse_str(&characteristic.uuid().await?)?;
-        let flags = characteristic.flags().await?;
-        Ok(CharacteristicInfo {
-            id: id.to_owned(),
-            uuid,
-            flags: flags.try_into()?,
-        })
+        let properties = characteristic
+            .get_all(ORG_BLUEZ_GATT_CHARACTERISTIC1_NAME)
+            .await?;
+        CharacteristicInfo::from_properties(
+            id.to_owned(),
+            OrgBluezGattCharacteristic1Properties(&properties),
+        )
     }
 
     /// Get information about the given GATT descriptor.
@@ -510,16 +1530,19 @@ impl BluetoothSession {
         &self,
         id: &DescriptorId,
     ) -> Result<DescriptorInfo, BluetoothError> {
-        let uuid = Uuid::parse_str(&self.descriptor(&id).uuid().await?)?;
-        Ok(DescriptorInfo {
-            id: id.to_owned(),
-            uuid,
-        })
+        let properties = self
+            .descriptor(&id)
+            .get_all(ORG_BLUEZ_GATT_DESCRIPTOR1_NAME)
+            .await?;
+        DescriptorInfo::from_properties(
+            id.to_owned(),
+            OrgBluezGattDescriptor1Properties(&properties),
+        )
     }
 
     fn adapter(&self, id: &AdapterId) -> impl OrgBluezAdapter1 + Introspectable + Properties {
         Proxy::new(
-            "org.bluez",
+            self.service_name.clone(),
             id.object_path.to_owned(),
             DBUS_METHOD_CALL_TIMEOUT,
             self.connection.clone(),
@@ -528,7 +1551,7 @@ impl BluetoothSession {
 
     fn device(&self, id: &DeviceId) -> impl OrgBluezDevice1 + Introspectable + Properties {
         Proxy::new(
-            "org.bluez",
+            self.service_name.clone(),
             id.object_path.to_owned(),
             DBUS_METHOD_CALL_TIMEOUT,
             self.connection.clone(),
@@ -537,7 +1560,7 @@ impl BluetoothSession {
 
     fn service(&self, id: &ServiceId) -> impl OrgBluezGattService1 + Introspectable + Properties {
         Proxy::new(
-            "org.bluez",
+            self.service_name.clone(),
             id.object_path.to_owned(),
             DBUS_METHOD_CALL_TIMEOUT,
             self.connection.clone(),
@@ -549,7 +1572,7 @@ impl BluetoothSession {
         id: &CharacteristicId,
     ) -> impl OrgBluezGattCharacteristic1 + Introspectable + Properties {
         Proxy::new(
-            "org.bluez",
+            self.service_name.clone(),
             id.object_path.to_owned(),
             DBUS_METHOD_CALL_TIMEOUT,
             self.connection.clone(),
@@ -561,33 +1584,534 @@ impl BluetoothSession {
         id: &DescriptorId,
     ) -> impl OrgBluezGattDescriptor1 + Introspectable + Properties {
         Proxy::new(
-            "org.bluez",
+            self.service_name.clone(),
             id.object_path.to_owned(),
             DBUS_METHOD_CALL_TIMEOUT,
             self.connection.clone(),
         )
     }
 
+    /// Set the alias of the given Bluetooth device. BlueZ stores this persistently along with
+    /// other information about the device.
+    ///
+    /// Setting the alias to an empty string will reset it to the default, i.e. the name of the
+    /// device.
+    pub async fn set_device_alias(&self, id: &DeviceId, alias: &str) -> Result<(), BluetoothError> {
+        Ok(self.device(id).set_alias(alias.to_owned()).await?)
+    }
+
+    /// Mark the given Bluetooth device as trusted or not.
+    ///
+    /// BlueZ only accepts incoming connections from a device without asking the pairing agent for
+    /// authorization if it is trusted, so bonded devices which reconnect by themselves generally
+    /// need to be trusted.
+    ///
+    /// There is no D-Bus API to control automatic reconnection directly. BlueZ auto-connects
+    /// trusted devices itself: recent versions add LE devices which are bonded and trusted, and
+    /// have a profile which requests it such as HID over GATT, to the kernel's auto-connect list.
+    /// An explicit `disconnect` removes the device from the list until it is next connected. The
+    /// details vary between BlueZ versions.
+    #[cfg_attr(feature = "tracing", tracing::instrument(skip(self, id), fields(id = %id)))]
+    pub async fn set_trusted(&self, id: &DeviceId, trusted: bool) -> Result<(), BluetoothError> {
+        Ok(self.device(id).set_trusted(trusted).await?)
+    }
+
     /// Connect to the given Bluetooth device.
+    ///
+    /// The connection is made from the adapter on which the device was discovered, which is part
+    /// of its `DeviceId`. To connect from a different adapter, use `connect_with_adapter`.
+    #[cfg_attr(feature = "tracing", tracing::instrument(skip(self, id), fields(id = %id)))]
     pub async fn connect(&self, id: &DeviceId) -> Result<(), BluetoothError> {
-        Ok(self.device(id).connect().await?)
+        self.device(id)
+            .connect()
+            .await
+            .map_err(convert_connect_error)
+    }
+
+    /// Connect to the given dual-mode Bluetooth device over the given transport, e.g. to force an
+    /// LE connection to a device which also supports Bluetooth Classic.
+    ///
+    /// This sets the device's `PreferredBearer` property before connecting, which is only
+    /// supported by recent versions of BlueZ with experimental features enabled; otherwise
+    /// `BluetoothError::NotSupported` is returned. The preference is stored by BlueZ, so also
+    /// applies to later calls to `connect`. `Transport::Auto` connects without changing the
+    /// preference.
+    #[cfg_attr(feature = "tracing", tracing::instrument(skip(self, id), fields(id = %id)))]
+    pub async fn connect_with_transport(
+        &self,
+        id: &DeviceId,
+        transport: Transport,
+    ) -> Result<(), BluetoothError> {
+        if transport != Transport::Auto {
+            self.device(id)
+                .set(
+                    ORG_BLUEZ_DEVICE1_NAME,
+                    "PreferredBearer",
+                    transport.to_string(),
+                )
+                .await
+                .map_err(|e| match e.name() {
+                    Some("org.freedesktop.DBus.Error.UnknownProperty")
+                    | Some("org.freedesktop.DBus.Error.InvalidArgs") => {
+                        BluetoothError::NotSupported(
+                            "Choosing the connection transport".to_string(),
+                        )
+                    }
+                    _ => e.into(),
+                })?;
+        }
+        self.connect(id).await
+    }
+
+    /// Connect to the device with the given MAC address from the given adapter, even if the adapter
+    /// hasn't discovered it, and return its ID on that adapter.
+    ///
+    /// If the adapter doesn't already know about the device this uses BlueZ's `ConnectDevice`
+    /// method, which is experimental and so is only available if `bluetoothd` is run with the
+    /// `--experimental` flag; otherwise `BluetoothError::NotSupported` is returned.
+    pub async fn connect_with_adapter(
+        &self,
+        adapter: &AdapterId,
+        mac_address: &MacAddress,
+        address_type: AddressType,
+    ) -> Result<DeviceId, BluetoothError> {
+        let id = adapter.device(mac_address);
+        let mut properties: PropMap = HashMap::new();
+        properties.insert(
+            "Address".to_string(),
+            Variant(Box::new(mac_address.to_string())),
+        );
+        properties.insert(
+            "AddressType".to_string(),
+            Variant(Box::new(address_type.to_string())),
+        );
+        // ConnectDevice is experimental, so isn't included in the generated bindings.
+        let adapter_proxy = Proxy::new(
+            self.service_name.clone(),
+            adapter.object_path.to_owned(),
+            DBUS_METHOD_CALL_TIMEOUT,
+            self.connection.clone(),
+        );
+        let result: Result<(Path<'static>,), dbus::Error> = adapter_proxy
+            .method_call(ORG_BLUEZ_ADAPTER1_NAME, "ConnectDevice", (properties,))
+            .await;
+        match result {
+            Ok((object_path,)) => Ok(DeviceId { object_path }),
+            Err(e) => match e.name() {
+                // The adapter already knows about the device, so connect to it the normal way.
+                Some("org.bluez.Error.AlreadyExists") => {
+                    self.connect(&id).await?;
+                    Ok(id)
+                }
+                Some("org.freedesktop.DBus.Error.UnknownMethod") => Err(
+                    BluetoothError::NotSupported("Connecting to an unknown device".to_string()),
+                ),
+                _ => Err(convert_connect_error(e)),
+            },
+        }
+    }
+
+    /// Pair with the given Bluetooth device, using whichever pairing agent is registered with BlueZ
+    /// to handle any input or confirmation which is required.
+    ///
+    /// Returns `BluetoothError::PairingFailed` if pairing is rejected, cancelled or fails to
+    /// authenticate.
+    #[cfg_attr(feature = "tracing", tracing::instrument(skip(self, id), fields(id = %id)))]
+    pub async fn pair(&self, id: &DeviceId) -> Result<(), BluetoothError> {
+        self.device(id).pair().await.map_err(convert_pair_error)
+    }
+
+    /// Connect to all of the given Bluetooth devices, with at most `max_concurrent` connection
+    /// attempts in progress at once.
+    ///
+    /// Trying to connect to many devices at once can overwhelm the adapter and cause most of the
+    /// attempts to time out, so `max_concurrent` should generally be small.
+    ///
+    /// Returns the result for each device, in the order in which the attempts finished. A
+    /// `max_concurrent` of 0 is treated as 1.
+    pub async fn connect_all(
+        &self,
+        ids: &[DeviceId],
+        max_concurrent: usize,
+    ) -> Vec<(DeviceId, Result<(), BluetoothError>)> {
+        run_concurrently(
+            ids.iter()
+                .map(|id| async move { (id.to_owned(), self.connect(id).await) }),
+            max_concurrent,
+        )
+        .await
+    }
+
+    /// Connect to the given Bluetooth device, and return a guard which will disconnect from it when
+    /// it is dropped. See [`ConnectionGuard`] for caveats.
+    pub async fn connect_guarded(&self, id: &DeviceId) -> Result<ConnectionGuard, BluetoothError> {
+        self.connect(id).await?;
+        Ok(ConnectionGuard::new(self.clone(), id.to_owned()))
+    }
+
+    /// Connect to the given Bluetooth device, run the given function, and then disconnect from the
+    /// device again, even if the function returns an error or panics.
+    ///
+    /// If the function fails then its error is returned, and any error disconnecting is only
+    /// logged.
+    pub async fn with_connection<F, Fut, T, E>(&self, id: &DeviceId, f: F) -> Result<T, E>
+    where
+        F: FnOnce() -> Fut,
+        Fut: Future<Output = Result<T, E>>,
+        E: From<BluetoothError>,
+    {
+        self.connect(id).await?;
+        let result = AssertUnwindSafe(f()).catch_unwind().await;
+        let disconnect_result = self.disconnect(id).await;
+        match result {
+            Ok(Ok(value)) => {
+                disconnect_result?;
+                Ok(value)
+            }
+            Ok(Err(e)) => {
+                if let Err(disconnect_error) = disconnect_result {
+                    log::warn!("Error disconnecting from {}: {}", id, disconnect_error);
+                }
+                Err(e)
+            }
+            Err(panic) => panic::resume_unwind(panic),
+        }
     }
 
     /// Disconnect from the given Bluetooth device.
+    #[cfg_attr(feature = "tracing", tracing::instrument(skip(self, id), fields(id = %id)))]
     pub async fn disconnect(&self, id: &DeviceId) -> Result<(), BluetoothError> {
         Ok(self.device(id).disconnect().await?)
     }
 
+    /// Connect only the given profile of the given Bluetooth device, such as a HID or audio
+    /// profile, rather than all of its auto-connectable profiles as `connect` does.
+    #[cfg_attr(feature = "tracing", tracing::instrument(skip(self, id), fields(id = %id)))]
+    pub async fn connect_profile(
+        &self,
+        id: &DeviceId,
+        profile_uuid: Uuid,
+    ) -> Result<(), BluetoothError> {
+        self.device(id)
+            .connect_profile(&profile_uuid.to_string())
+            .await
+            .map_err(convert_connect_error)
+    }
+
+    /// Disconnect the given profile of the given Bluetooth device, leaving any other connected
+    /// profiles connected.
+    #[cfg_attr(feature = "tracing", tracing::instrument(skip(self, id), fields(id = %id)))]
+    pub async fn disconnect_profile(
+        &self,
+        id: &DeviceId,
+        profile_uuid: Uuid,
+    ) -> Result<(), BluetoothError> {
+        Ok(self
+            .device(id)
+            .disconnect_profile(&profile_uuid.to_string())
+            .await?)
+    }
+
     /// Read the value of the given GATT characteristic.
+    #[cfg_attr(feature = "tracing", tracing::instrument(skip(self, id), fields(id = %id)))]
     pub async fn read_characteristic_value(
         &self,
         id: &CharacteristicId,
     ) -> Result<Vec<u8>, BluetoothError> {
         let characteristic = self.characteristic(id);
-        Ok(characteristic.read_value(HashMap::new()).await?)
+        let value = characteristic.read_value(HashMap::new()).await?;
+        self.value_cache.insert(id, &value);
+        Ok(value)
+    }
+
+    /// Enable caching of characteristic values for this session and all its clones. Once enabled,
+    /// the last value read or notified for each characteristic is available from `cached_value`,
+    /// and `read_characteristic_value_cached` can avoid reading from the device. The cached
+    /// values for a device are discarded when it disconnects.
+    ///
+    /// The cache is disabled by default. Calling this again once it is enabled has no effect.
+    pub async fn enable_value_cache(&self) -> Result<(), BluetoothError> {
+        if self.value_cache.is_enabled() {
+            return Ok(());
+        }
+        let _adding_matches = self.adding_matches.lock().await;
+        if self.value_cache.is_enabled() {
+            return Ok(());
+        }
+        let value_cache = self.value_cache.clone();
+        self.add_match_callbacks(
+            ValueCache::match_rules(&self.service_name),
+            move |message| value_cache.handle_message(message),
+        )
+        .await?;
+        self.value_cache.enable();
+        Ok(())
+    }
+
+    /// Get the last value read or notified for the given characteristic, if the value cache has
+    /// been enabled with `enable_value_cache` and the value is known.
+    pub fn cached_value(&self, id: &CharacteristicId) -> Option<Vec<u8>> {
+        self.value_cache.get(id)
+    }
+
+    /// Get the value of the given GATT characteristic, like `read_characteristic_value`, but
+    /// return the cached value rather than reading from the device if notifications are active
+    /// for the characteristic, as the cached value is then kept up to date.
+    ///
+    /// This only avoids reads if the value cache has been enabled with `enable_value_cache`.
+    pub async fn read_characteristic_value_cached(
+        &self,
+        id: &CharacteristicId,
+    ) -> Result<Vec<u8>, BluetoothError> {
+        match self.value_cache.get_if_notifying(id) {
+            Some(value) => Ok(value),
+            None => self.read_characteristic_value(id).await,
+        }
+    }
+
+    /// Read the value of the given GATT characteristic, pairing with the device and trying again
+    /// once if the read fails because the characteristic requires an encrypted or authenticated
+    /// link.
+    ///
+    /// Pairing is done with BlueZ's `Pair` method, so requires a pairing agent to be registered
+    /// unless the device supports pairing without any input (Just Works). If pairing fails then
+    /// `BluetoothError::PairingFailed` is returned. If the device is already paired then the
+    /// original error is returned, as pairing again won't help.
+    pub async fn read_characteristic_value_ensuring_security(
+        &self,
+        id: &CharacteristicId,
+    ) -> Result<Vec<u8>, BluetoothError> {
+        match self.read_characteristic_value(id).await {
+            Err(BluetoothError::DbusError(e)) if is_security_error(&e) => {
+                let device = id.device();
+                if self.get_device_info(&device).await?.paired {
+                    return Err(BluetoothError::DbusError(e));
+                }
+                log::info!("Pairing with {} to read {}", device, id);
+                self.pair(&device).await?;
+                self.read_characteristic_value(id).await
+            }
+            result => result,
+        }
+    }
+
+    /// Read the value of the given GATT characteristic and decode it as a UTF-8 string, such as
+    /// the standard Manufacturer Name String or Model Number String characteristics. Trailing null
+    /// bytes are removed.
+    ///
+    /// Returns `FormatError::InvalidUtf8` if the value isn't valid UTF-8.
+    pub async fn read_characteristic_string(
+        &self,
+        id: &CharacteristicId,
+    ) -> Result<String, BluetoothError> {
+        let value = self.read_characteristic_value(id).await?;
+        Ok(format::decode_string(&value)?)
+    }
+
+    /// Read the value of the given GATT characteristic and decode it as a UTF-8 string, replacing
+    /// any invalid sequences with `U+FFFD REPLACEMENT CHARACTER`.
+    pub async fn read_characteristic_string_lossy(
+        &self,
+        id: &CharacteristicId,
+    ) -> Result<String, BluetoothError> {
+        let value = self.read_characteristic_value(id).await?;
+        Ok(format::decode_string_lossy(&value))
+    }
+
+    /// Read the value of the given GATT characteristic and decode it according to its
+    /// Characteristic Presentation Format descriptor. If it doesn't have one then the raw bytes are
+    /// returned as `CharacteristicValue::Raw`.
+    pub async fn read_characteristic_typed_value(
+        &self,
+        id: &CharacteristicId,
+    ) -> Result<CharacteristicValue, BluetoothError> {
+        let value = self.read_characteristic_value(id).await?;
+        Ok(match self.get_presentation_format(id).await? {
+            Some(format) => format.decode(&value)?,
+            None => CharacteristicValue::Raw(value),
+        })
+    }
+
+    /// Read a temperature in ºC from the given GATT characteristic. This is decoded according to
+    /// the characteristic's presentation format if it has one, or otherwise as a signed 16-bit
+    /// integer with a resolution of 0.01 degrees like the standard Temperature characteristic.
+    pub async fn read_temperature(&self, id: &CharacteristicId) -> Result<f32, BluetoothError> {
+        match self.read_characteristic_typed_value(id).await? {
+            CharacteristicValue::Raw(value) => Ok(format::decode_temperature(&value)?),
+            value => Ok(value.as_f64().ok_or(FormatError::NotNumeric)? as f32),
+        }
+    }
+
+    /// Read and decode a value from the given standard Heart Rate Measurement characteristic.
+    ///
+    /// Note that most heart rate sensors only support notifications for this characteristic, not
+    /// reads. In that case use `characteristic_event_stream` and decode each value with
+    /// `HeartRateMeasurement::parse`.
+    pub async fn read_heart_rate_measurement(
+        &self,
+        id: &CharacteristicId,
+    ) -> Result<HeartRateMeasurement, BluetoothError> {
+        let value = self.read_characteristic_value(id).await?;
+        Ok(HeartRateMeasurement::parse(&value)?)
+    }
+
+    /// Get the Characteristic Presentation Format of the given characteristic, if it has one. This
+    /// describes the format and unit of the characteristic's value.
+    pub async fn get_presentation_format(
+        &self,
+        id: &CharacteristicId,
+    ) -> Result<Option<PresentationFormat>, BluetoothError> {
+        match self
+            .read_descriptor_value_by_uuid(
+                id,
+                well_known::descriptors::CHARACTERISTIC_PRESENTATION_FORMAT,
+            )
+            .await?
+        {
+            Some(value) => Ok(Some(PresentationFormat::parse(&value)?)),
+            None => Ok(None),
+        }
+    }
+
+    /// Get the Characteristic User Description of the given characteristic, if it has one. This is
+    /// a human-readable name for the characteristic. Any invalid UTF-8 sequences are replaced with
+    /// U+FFFD.
+    pub async fn get_user_description(
+        &self,
+        id: &CharacteristicId,
+    ) -> Result<Option<String>, BluetoothError> {
+        Ok(self
+            .read_descriptor_value_by_uuid(
+                id,
+                well_known::descriptors::CHARACTERISTIC_USER_DESCRIPTION,
+            )
+            .await?
+            .map(|value| String::from_utf8_lossy(&value).into_owned()))
+    }
+
+    /// Get the inclusive lower and upper bounds of the valid values of the given characteristic
+    /// from its Valid Range descriptor, if it has one. The bounds are encoded in the same format as
+    /// the characteristic value.
+    pub async fn get_valid_range(
+        &self,
+        id: &CharacteristicId,
+    ) -> Result<Option<(Vec<u8>, Vec<u8>)>, BluetoothError> {
+        match self
+            .read_descriptor_value_by_uuid(id, well_known::descriptors::VALID_RANGE)
+            .await?
+        {
+            Some(value) => Ok(Some(format::parse_valid_range(&value)?)),
+            None => Ok(None),
+        }
+    }
+
+    /// Get the attribute handles of the Characteristic Presentation Format descriptors listed by
+    /// the Characteristic Aggregate Format descriptor of the given characteristic, if it has one.
+    /// These describe the fields of the characteristic value, in order.
+    pub async fn get_aggregate_format(
+        &self,
+        id: &CharacteristicId,
+    ) -> Result<Option<Vec<u16>>, BluetoothError> {
+        match self
+            .read_descriptor_value_by_uuid(
+                id,
+                well_known::descriptors::CHARACTERISTIC_AGGREGATE_FORMAT,
+            )
+            .await?
+        {
+            Some(value) => Ok(Some(format::parse_aggregate_format(&value)?)),
+            None => Ok(None),
+        }
+    }
+
+    /// Read the value of the descriptor of the given characteristic with the given UUID, or return
+    /// `None` if there is no such descriptor.
+    async fn read_descriptor_value_by_uuid(
+        &self,
+        id: &CharacteristicId,
+        uuid: Uuid,
+    ) -> Result<Option<Vec<u8>>, BluetoothError> {
+        let descriptors = self.get_descriptors(id).await?;
+        match descriptors
+            .into_iter()
+            .find(|descriptor| descriptor.uuid == uuid)
+        {
+            Some(descriptor) => Ok(Some(self.read_descriptor_value(&descriptor.id).await?)),
+            None => Ok(None),
+        }
+    }
+
+    /// Read the value of the given GATT characteristic, with the given options.
+    ///
+    /// The value is stored in the value cache if it is enabled, unless an offset was given, in
+    /// which case only part of the value was read.
+    pub async fn read_characteristic_value_with_options(
+        &self,
+        id: &CharacteristicId,
+        options: &ReadOptions,
+    ) -> Result<Vec<u8>, BluetoothError> {
+        let characteristic = self.characteristic(id);
+        let value = characteristic.read_value(options.into()).await?;
+        if options.offset.unwrap_or(0) == 0 {
+            self.value_cache.insert(id, &value);
+        }
+        Ok(value)
+    }
+
+    /// Read the value of the given GATT characteristic into the given buffer, replacing its
+    /// previous contents. The value is copied straight out of the D-Bus reply and the buffer's
+    /// existing capacity is reused, so a buffer kept across calls in a polling loop won't need to
+    /// grow again.
+    pub async fn read_characteristic_value_into(
+        &self,
+        id: &CharacteristicId,
+        buf: &mut Vec<u8>,
+    ) -> Result<(), BluetoothError> {
+        let message = Message::method_call(
+            &self.service_name,
+            &id.object_path,
+            &ORG_BLUEZ_GATT_CHARACTERISTIC1_NAME.into(),
+            &"ReadValue".into(),
+        )
+        .append1(PropMap::new());
+        let mut reply = self.method_call_raw(message).await?;
+        let value: &[u8] = reply.as_result()?.read1().map_err(dbus::Error::from)?;
+        buf.clear();
+        buf.extend_from_slice(value);
+        self.value_cache.insert(id, buf);
+        Ok(())
+    }
+
+    /// Send the given method call message to BlueZ and wait for the reply, without decoding it, so
+    /// that the caller can read borrowed arguments from it rather than having them copied.
+    async fn method_call_raw(&self, message: Message) -> Result<Message, BluetoothError> {
+        let (sender, receiver) = oneshot::channel();
+        let token = self
+            .connection
+            .send_with_reply(
+                message,
+                SyncConnection::make_f(move |reply, _| {
+                    // If the receiver has been dropped then nobody is waiting for the reply.
+                    let _ = sender.send(reply);
+                }),
+            )
+            .map_err(|()| dbus::Error::new_failed("Failed to send message"))?;
+        match time::timeout(DBUS_METHOD_CALL_TIMEOUT, receiver).await {
+            Ok(Ok(reply)) => Ok(reply),
+            Ok(Err(_)) => Err(dbus::Error::new_failed("D-Bus connection closed").into()),
+            Err(_) => {
+                self.connection.cancel_reply(token);
+                Err(dbus::Error::new_custom(
+                    "org.freedesktop.DBus.Error.Timeout",
+                    "Timeout waiting for reply",
+                )
+                .into())
+            }
+        }
     }
 
     /// Write the given value to the given GATT characteristic.
+    #[cfg_attr(feature = "tracing", tracing::instrument(skip(self, id, value), fields(id = %id)))]
     pub async fn write_characteristic_value(
         &self,
         id: &CharacteristicId,
@@ -599,7 +2123,149 @@ impl BluetoothSession {
             .await?)
     }
 
+    /// Write the given value to the given GATT characteristic, failing with
+    /// `BluetoothError::WriteTimeout` if the write isn't acknowledged within the given timeout
+    /// rather than waiting for the default D-Bus method call timeout.
+    #[cfg_attr(feature = "tracing", tracing::instrument(skip(self, id, value), fields(id = %id)))]
+    pub async fn write_characteristic_value_with_timeout(
+        &self,
+        id: &CharacteristicId,
+        value: impl Into<Vec<u8>>,
+        timeout: Duration,
+    ) -> Result<(), BluetoothError> {
+        let characteristic = self.characteristic(id);
+        match time::timeout(
+            timeout,
+            characteristic.write_value(value.into(), HashMap::new()),
+        )
+        .await
+        {
+            Ok(result) => Ok(result?),
+            Err(_) => Err(BluetoothError::WriteTimeout),
+        }
+    }
+
+    /// Write the given value to the given GATT characteristic with the given options, after
+    /// checking that it isn't longer than the characteristic allows.
+    ///
+    /// Fails with `BluetoothError::ValueTooLong` without attempting the write if the value is
+    /// known to be too long for the write type given in the options, or for the type which BlueZ
+    /// would choose if none is given. Checking costs an extra D-Bus round-trip to get the flags and
+    /// MTU of the characteristic, unless the value is short enough to fit with any MTU. If the
+    /// limit can't be determined then the write is left for BlueZ to reject.
+    #[cfg_attr(feature = "tracing", tracing::instrument(skip(self, id, value), fields(id = %id)))]
+    pub async fn write_characteristic_value_checked(
+        &self,
+        id: &CharacteristicId,
+        value: impl Into<Vec<u8>>,
+        options: &WriteOptions,
+    ) -> Result<(), BluetoothError> {
+        let value = value.into();
+        self.check_write_length(id, value.len(), options.write_type)
+            .await?;
+        let characteristic = self.characteristic(id);
+        Ok(characteristic.write_value(value, options.into()).await?)
+    }
+
+    /// Check whether a value of the given length can be written to the given characteristic with
+    /// the given write type, returning `BluetoothError::ValueTooLong` if not.
+    ///
+    /// Values short enough to fit with any MTU are allowed without checking, to avoid an extra
+    /// round-trip. If the limit can't be determined then the write is left for BlueZ to reject.
+    async fn check_write_length(
+        &self,
+        id: &CharacteristicId,
+        length: usize,
+        write_type: Option<WriteType>,
+    ) -> Result<(), BluetoothError> {
+        if length <= MIN_MAX_WRITE_LENGTH {
+            return Ok(());
+        }
+        let properties = self
+            .characteristic(id)
+            .get_all(ORG_BLUEZ_GATT_CHARACTERISTIC1_NAME)
+            .await?;
+        let flags = match OrgBluezGattCharacteristic1Properties(&properties).flags() {
+            Some(flags) => CharacteristicFlags::try_from(flags.to_owned())?,
+            None => return Ok(()),
+        };
+        let mtu = prop_cast(&properties, "MTU").copied();
+        match max_write_length(flags, mtu, write_type) {
+            Some(max) if length > max => Err(BluetoothError::ValueTooLong { max }),
+            _ => Ok(()),
+        }
+    }
+
+    /// Write the given value to the given GATT characteristic as one long write. BlueZ splits the
+    /// value into ATT Prepare Write Requests which fit within the negotiated MTU and then executes
+    /// them together, so the device either accepts the whole value or none of it. If the value fits
+    /// in a single packet then BlueZ sends a plain Write Request instead.
+    ///
+    /// If the characteristic has the Reliable Write extended property then the `reliable` write
+    /// type is used, so that BlueZ also verifies the value of each prepared write. Otherwise BlueZ
+    /// would reject reliable writes, so the `request` write type is used instead.
+    ///
+    /// Fails with `BluetoothError::ValueTooLong` without attempting the write if the value is
+    /// longer than the maximum attribute value length of 512 bytes.
+    #[cfg_attr(feature = "tracing", tracing::instrument(skip(self, id, value), fields(id = %id)))]
+    pub async fn write_characteristic_value_chunked(
+        &self,
+        id: &CharacteristicId,
+        value: impl Into<Vec<u8>>,
+    ) -> Result<(), BluetoothError> {
+        let value = value.into();
+        if value.len() > MAX_ATTRIBUTE_VALUE_LENGTH {
+            return Err(BluetoothError::ValueTooLong {
+                max: MAX_ATTRIBUTE_VALUE_LENGTH,
+            });
+        }
+        let characteristic = self.characteristic(id);
+        let flags = CharacteristicFlags::try_from(characteristic.flags().await?)?;
+        let options = WriteOptions {
+            offset: None,
+            write_type: Some(long_write_type(flags)),
+        };
+        Ok(characteristic.write_value(value, (&options).into()).await?)
+    }
+
+    /// Read the current value of the given GATT characteristic, pass it to the given function, and
+    /// write the value it returns back to the characteristic.
+    ///
+    /// This is not atomic: if another client (or another task in this process) writes to the
+    /// characteristic between the read and the write, its change will be overwritten.
+    pub async fn update_characteristic_value(
+        &self,
+        id: &CharacteristicId,
+        f: impl FnOnce(Vec<u8>) -> Vec<u8>,
+    ) -> Result<(), BluetoothError> {
+        let value = self.read_characteristic_value(id).await?;
+        self.write_characteristic_value(id, f(value)).await
+    }
+
+    /// Read the values of all readable characteristics of the given GATT service concurrently,
+    /// skipping those whose flags don't allow reading.
+    ///
+    /// The results are returned along with the information about each characteristic, so a
+    /// failure to read one characteristic doesn't prevent the others from being read.
+    pub async fn read_readable_characteristics(
+        &self,
+        service: &ServiceId,
+    ) -> Result<Vec<(CharacteristicInfo, Result<Vec<u8>, BluetoothError>)>, BluetoothError> {
+        let characteristics = self.get_characteristics(service).await?;
+        Ok(join_all(
+            characteristics
+                .into_iter()
+                .filter(|characteristic| characteristic.flags.is_readable())
+                .map(|characteristic| async move {
+                    let value = self.read_characteristic_value(&characteristic.id).await;
+                    (characteristic, value)
+                }),
+        )
+        .await)
+    }
+
     /// Read the value of the given GATT descriptor.
+    #[cfg_attr(feature = "tracing", tracing::instrument(skip(self, id), fields(id = %id)))]
     pub async fn read_descriptor_value(
         &self,
         id: &DescriptorId,
@@ -608,7 +2274,19 @@ impl BluetoothSession {
         Ok(descriptor.read_value(HashMap::new()).await?)
     }
 
+    /// Read the values of all the given GATT descriptors concurrently.
+    ///
+    /// The results are returned in the same order as the given IDs, so a failure to read one
+    /// descriptor doesn't prevent the others from being read.
+    pub async fn read_descriptor_values(
+        &self,
+        ids: &[DescriptorId],
+    ) -> Vec<Result<Vec<u8>, BluetoothError>> {
+        join_all(ids.iter().map(|id| self.read_descriptor_value(id))).await
+    }
+
     /// Write the given value to the given GATT descriptor.
+    #[cfg_attr(feature = "tracing", tracing::instrument(skip(self, id, value), fields(id = %id)))]
     pub async fn write_descriptor_value(
         &self,
         id: &DescriptorId,
@@ -619,6 +2297,7 @@ impl BluetoothSession {
     }
 
     /// Start notifications on the given GATT characteristic.
+    #[cfg_attr(feature = "tracing", tracing::instrument(skip(self, id), fields(id = %id)))]
     pub async fn start_notify(&self, id: &CharacteristicId) -> Result<(), BluetoothError> {
         let characteristic = self.characteristic(id);
         characteristic.start_notify().await?;
@@ -626,17 +2305,402 @@ impl BluetoothSession {
     }
 
     /// Stop notifications on the given GATT characteristic.
+    #[cfg_attr(feature = "tracing", tracing::instrument(skip(self, id), fields(id = %id)))]
     pub async fn stop_notify(&self, id: &CharacteristicId) -> Result<(), BluetoothError> {
         let characteristic = self.characteristic(id);
         characteristic.stop_notify().await?;
         Ok(())
     }
 
+    /// Start notifications on the given GATT characteristic, and get a stream of its values
+    /// decoded with the given function. Notifications are stopped when the stream is dropped.
+    ///
+    /// Values are only included from the time this is called, not any previous value of the
+    /// characteristic.
+    pub async fn characteristic_value_stream<T, E>(
+        &self,
+        id: &CharacteristicId,
+        decode: impl Fn(&[u8]) -> Result<T, E> + Send + 'static,
+    ) -> Result<CharacteristicValueStream<T, E>, BluetoothError>
+    where
+        T: Send + 'static,
+        E: Send + 'static,
+    {
+        // Subscribe to events before starting notifications so that the first is not missed.
+        let events = self.characteristic_event_stream(id).await?;
+        self.start_notify(id).await?;
+        let values = events
+            .filter_map(move |event| {
+                futures::future::ready(match event {
+                    BluetoothEvent::Characteristic {
+                        event: CharacteristicEvent::Value { value },
+                        ..
+                    } => Some(decode(&value)),
+                    _ => None,
+                })
+            })
+            .boxed();
+        Ok(CharacteristicValueStream::new(
+            self.clone(),
+            id.to_owned(),
+            values,
+        ))
+    }
+
+    /// Start notifications on all the given GATT characteristics concurrently, e.g. to set up
+    /// subscriptions after connecting to a device.
+    ///
+    /// Each ID is returned along with its result, in the same order as the given IDs, so a failure
+    /// for one characteristic doesn't prevent the others from being started.
+    pub async fn start_notify_all(
+        &self,
+        ids: &[CharacteristicId],
+    ) -> Vec<(CharacteristicId, Result<(), BluetoothError>)> {
+        join_all(
+            ids.iter()
+                .map(|id| async move { (id.to_owned(), self.start_notify(id).await) }),
+        )
+        .await
+    }
+
+    /// Stop notifications on all the given GATT characteristics concurrently.
+    ///
+    /// Each ID is returned along with its result, in the same order as the given IDs, so a failure
+    /// for one characteristic doesn't prevent the others from being stopped.
+    pub async fn stop_notify_all(
+        &self,
+        ids: &[CharacteristicId],
+    ) -> Vec<(CharacteristicId, Result<(), BluetoothError>)> {
+        join_all(
+            ids.iter()
+                .map(|id| async move { (id.to_owned(), self.stop_notify(id).await) }),
+        )
+        .await
+    }
+
+    /// Wait for the next notification (or indication) from the given GATT characteristic, and
+    /// return its value.
+    ///
+    /// Notifications are started if they aren't already, and stopped again afterwards in that case;
+    /// an error stopping them is logged rather than returned. Returns `BluetoothError::Timeout` if
+    /// no notification is received within `timeout`.
+    pub async fn next_notification(
+        &self,
+        id: &CharacteristicId,
+        timeout: Duration,
+    ) -> Result<Vec<u8>, BluetoothError> {
+        let events = self.characteristic_event_stream(id).await?;
+        let already_notifying = self.characteristic(id).notifying().await?;
+        if !already_notifying {
+            self.start_notify(id).await?;
+        }
+        let result = next_characteristic_value(events, timeout).await;
+        if !already_notifying {
+            if let Err(e) = self.stop_notify(id).await {
+                log::warn!("Error stopping notifications for {}: {}", id, e);
+            }
+        }
+        result
+    }
+
+    /// Write the given command to one GATT characteristic, and wait for the response to be
+    /// notified on another, returning its value.
+    ///
+    /// Notifications on the `notify` characteristic are started before writing the command, so
+    /// that the response can't be missed, and are stopped again afterwards if they weren't already
+    /// started, including when the request times out. An error stopping them is logged rather than
+    /// returned. Returns `BluetoothError::Timeout` if no response is received within `timeout`.
+    pub async fn request_response(
+        &self,
+        write: &CharacteristicId,
+        notify: &CharacteristicId,
+        command: impl Into<Vec<u8>>,
+        timeout: Duration,
+    ) -> Result<Vec<u8>, BluetoothError> {
+        let events = self.characteristic_event_stream(notify).await?;
+        let already_notifying = self.characteristic(notify).notifying().await?;
+        if !already_notifying {
+            self.start_notify(notify).await?;
+        }
+        let result = match self.write_characteristic_value(write, command).await {
+            Ok(()) => next_characteristic_value(events, timeout).await,
+            Err(e) => Err(e),
+        };
+        if !already_notifying {
+            if let Err(e) = self.stop_notify(notify).await {
+                log::warn!("Error stopping notifications for {}: {}", notify, e);
+            }
+        }
+        result
+    }
+
     /// Get a stream of events for all devices.
+    ///
+    /// Setting up the D-Bus matches for this and the other event streams is retried according to
+    /// `RetryPolicy::default()` if it fails; use `event_stream_with_retry` to choose a different
+    /// policy.
     pub async fn event_stream(&self) -> Result<impl Stream<Item = BluetoothEvent>, BluetoothError> {
         self.filtered_event_stream(None::<&DeviceId>).await
     }
 
+    /// Get a stream of events for all devices, like `event_stream`, but also including
+    /// `BluetoothEvent::Unknown` events for property changes on interfaces which this crate doesn't
+    /// otherwise handle. This is mainly useful for debugging.
+    pub async fn event_stream_with_unknown(
+        &self,
+    ) -> Result<impl Stream<Item = BluetoothEvent>, BluetoothError> {
+        self.filtered_event_stream_inner(None::<&DeviceId>, true, &RetryPolicy::default())
+            .await
+    }
+
+    /// Get a stream of events for all devices, like `event_stream`, but with the given policy for
+    /// retrying if setting up the D-Bus matches fails.
+    ///
+    /// If the underlying D-Bus message stream ever ends then the matches are added again,
+    /// following the same policy, and the stream continues. The stream only ends if this fails
+    /// after all attempts. This is intended for long-running services which should ride out
+    /// transient D-Bus errors.
+    pub async fn event_stream_with_retry(
+        &self,
+        retry: &RetryPolicy,
+    ) -> Result<impl Stream<Item = BluetoothEvent>, BluetoothError> {
+        let events = self
+            .filtered_event_stream_inner(None::<&DeviceId>, false, retry)
+            .await?;
+        let state = (Box::pin(events), self.clone(), retry.to_owned());
+        Ok(stream::unfold(
+            state,
+            |(mut events, session, retry)| async move {
+                loop {
+                    if let Some(event) = events.next().await {
+                        return Some((event, (events, session, retry)));
+                    }
+                    log::warn!("Event stream ended, adding D-Bus matches again.");
+                    match session
+                        .filtered_event_stream_inner(None::<&DeviceId>, false, &retry)
+                        .await
+                    {
+                        Ok(new_events) => events = Box::pin(new_events),
+                        Err(e) => {
+                            log::error!("Error adding D-Bus matches for event stream: {}", e);
+                            return None;
+                        }
+                    }
+                }
+            },
+        ))
+    }
+
+    /// Get a stream of events for all devices, like `event_stream`, but with a
+    /// `BluetoothEvent::Heartbeat` interleaved every `interval`.
+    ///
+    /// This lets a consumer notice if the stream has stalled, rather than assuming that there is
+    /// just nothing happening. Each heartbeat is only emitted after a successful
+    /// `org.freedesktop.DBus.Peer.Ping` round-trip to BlueZ, and the stream ends if the ping fails
+    /// or the underlying D-Bus message stream ends.
+    pub async fn event_stream_with_heartbeat(
+        &self,
+        interval: Duration,
+    ) -> Result<impl Stream<Item = BluetoothEvent>, BluetoothError> {
+        let events = self.filtered_event_stream(None::<&DeviceId>).await?;
+        let bluez_root = Proxy::new(
+            self.service_name.clone(),
+            "/",
+            DBUS_METHOD_CALL_TIMEOUT,
+            self.connection.clone(),
+        );
+        Ok(with_heartbeat(events, interval, move || {
+            let bluez_root = bluez_root.clone();
+            async move {
+                bluez_root
+                    .method_call::<(), _, _, _>("org.freedesktop.DBus.Peer", "Ping", ())
+                    .await
+            }
+        }))
+    }
+
+    /// Get a stream of events for property changes on the given D-Bus interfaces only, such as
+    /// `org.bluez.GattCharacteristic1`.
+    ///
+    /// The D-Bus daemon filters signals by interface before sending them to us, so this is much
+    /// cheaper than `event_stream` on a busy bus if you only care about a few kinds of event, such
+    /// as characteristic values. Note that this doesn't include events for devices being discovered
+    /// or removed. Property changes on interfaces which aren't otherwise handled are returned as
+    /// `BluetoothEvent::Unknown`.
+    pub async fn event_stream_for_interfaces(
+        &self,
+        interfaces: &[&str],
+    ) -> Result<impl Stream<Item = BluetoothEvent>, BluetoothError> {
+        let mut message_streams = vec![];
+        for interface in interfaces {
+            let match_rule = BluetoothEvent::properties_changed_match_rule(&self.service_name);
+            message_streams.push(
+                MessageStream::with_arg0(match_rule, interface, self.connection.clone()).await?,
+            );
+        }
+        Ok(select_all(message_streams)
+            .flat_map(|message| stream::iter(BluetoothEvent::message_to_events(message, true))))
+    }
+
+    /// Get a stream of information about newly discovered devices which match the name and address
+    /// patterns of the given filter. This doesn't start discovery itself; use
+    /// `start_discovery_with_filter` with the same filter for that.
+    ///
+    /// To also re-apply the RSSI threshold and service UUIDs of the filter locally, in case another
+    /// client has set a looser filter, use [`DeviceInfoStreamExt::refiltered`] on the result.
+    pub async fn discovered_device_stream(
+        &self,
+        filter: &DiscoveryFilter,
+    ) -> Result<impl Stream<Item = DeviceInfo>, BluetoothError> {
+        let events = self.filtered_event_stream(None::<&DeviceId>).await?;
+        let session = self.clone();
+        let filter = filter.clone();
+        Ok(events.filter_map(move |event| {
+            let session = session.clone();
+            let filter = filter.clone();
+            async move {
+                match event {
+                    BluetoothEvent::Device {
+                        id,
+                        event: DeviceEvent::Discovered,
+                    } => {
+                        let device = session
+                            .get_device_info(&id)
+                            .await
+                            .map_err(|e| log::warn!("Error getting device info: {}", e))
+                            .ok()?;
+                        if filter.matches_device(&device) {
+                            Some(device)
+                        } else {
+                            None
+                        }
+                    }
+                    _ => None,
+                }
+            }
+        }))
+    }
+
+    /// Scan for devices with the given discovery filter until one matching both the filter and the
+    /// given predicate is found, and return it. Devices which BlueZ already knows about are checked
+    /// first.
+    ///
+    /// The filter is checked with [`DiscoveryFilter::matches_device_locally`], and both are
+    /// checked again whenever a device is discovered or any of its properties change, such as its
+    /// name or RSSI, so a device which only starts to match later is still found.
+    ///
+    /// Discovery is started on all adapters while waiting, and stopped again afterwards; an error
+    /// stopping it is logged rather than returned. Returns `BluetoothError::Timeout` if no matching
+    /// device is found within the given time.
+    pub async fn wait_for_device(
+        &self,
+        filter: &DiscoveryFilter,
+        predicate: impl Fn(&DeviceInfo) -> bool,
+        timeout: Duration,
+    ) -> Result<DeviceInfo, BluetoothError> {
+        let matches =
+            |device: &DeviceInfo| filter.matches_device_locally(device) && predicate(device);
+
+        // Subscribe before checking existing devices so that we can't miss one.
+        let discovered = self.discovered_device_stream(filter).await?;
+        let changed = MessageStream::with_arg0(
+            BluetoothEvent::properties_changed_match_rule(&self.service_name),
+            ORG_BLUEZ_DEVICE1_NAME,
+            self.connection.clone(),
+        )
+        .await?
+        .filter_map(|message| async move {
+            let id = DeviceId {
+                object_path: message.path()?.into_static(),
+            };
+            self.get_device_info(&id)
+                .await
+                .map_err(|e| log::warn!("Error getting device info: {}", e))
+                .ok()
+        });
+        if let Some(device) = self.get_devices().await?.into_iter().find(&matches) {
+            return Ok(device);
+        }
+
+        let discovery = self.start_discovery_session(filter).await?;
+        let mut matching = Box::pin(
+            stream::select(discovered, changed)
+                .filter(|device| futures::future::ready(matches(device))),
+        );
+        let result = time::timeout(timeout, matching.next()).await;
+        if let Err(e) = discovery.stop().await {
+            log::warn!("Error stopping discovery: {}", e);
+        }
+        match result {
+            Ok(Some(device)) => Ok(device),
+            // The stream should never end, but treat it the same as a timeout.
+            Ok(None) | Err(_) => Err(BluetoothError::Timeout),
+        }
+    }
+
+    /// Get a stream of events for all devices, read through a buffer with the given capacity.
+    ///
+    /// The D-Bus messages are read and parsed by a separate task, so that a slow consumer doesn't
+    /// hold up their processing. If the consumer falls `capacity` events behind then the `overflow`
+    /// policy decides what happens to further events.
+    ///
+    /// Events dropped by `OverflowPolicy::DropOldest` are counted by `dropped_message_count`.
+    ///
+    /// The task stops when the returned stream is dropped. A `capacity` of 0 is treated as 1.
+    pub async fn event_stream_with_capacity(
+        &self,
+        capacity: usize,
+        overflow: OverflowPolicy,
+    ) -> Result<impl Stream<Item = BluetoothEvent>, BluetoothError> {
+        let events = self.filtered_event_stream(None::<&DeviceId>).await?;
+        Ok(buffer_events(
+            events,
+            capacity,
+            overflow,
+            self.dropped_events.clone(),
+        ))
+    }
+
+    /// Get a stream of events for all Bluetooth adapters, such as being powered on or off or
+    /// starting or stopping discovery.
+    ///
+    /// Only PropertiesChanged signals for the `org.bluez.Adapter1` interface are matched, which
+    /// BlueZ only implements on the adapter objects (`/org/bluez/hciN`), so signals for devices and
+    /// their characteristics are never sent to us.
+    pub async fn adapter_event_stream(
+        &self,
+    ) -> Result<impl Stream<Item = (AdapterId, AdapterEvent)>, BluetoothError> {
+        let match_rule = BluetoothEvent::properties_changed_match_rule(&self.service_name);
+        let messages =
+            MessageStream::with_arg0(match_rule, ORG_BLUEZ_ADAPTER1_NAME, self.connection.clone())
+                .await?;
+        Ok(messages
+            .flat_map(|message| stream::iter(BluetoothEvent::message_to_events(message, false)))
+            .filter_map(|event| async move {
+                match event {
+                    BluetoothEvent::Adapter { id, event } => Some((id, event)),
+                    _ => None,
+                }
+            }))
+    }
+
+    /// Get a stream of lifecycle state changes for all Bluetooth devices: being discovered,
+    /// connecting, having their services resolved, disconnecting and being removed.
+    ///
+    /// Redundant events are dropped, and implied transitions which weren't seen are filled in, so
+    /// for example `ServicesResolved` is always preceded by `Connected`, which is always preceded
+    /// by `Discovered`.
+    pub async fn device_state_stream(
+        &self,
+    ) -> Result<impl Stream<Item = (DeviceId, DeviceStateChange)>, BluetoothError> {
+        let events = self.filtered_event_stream(None::<&DeviceId>).await?;
+        Ok(events
+            .scan(DeviceStateTracker::default(), |tracker, event| {
+                futures::future::ready(Some(stream::iter(tracker.update(event))))
+            })
+            .flatten())
+    }
+
     /// Get a stream of events for a particular device. This includes events for all its
     /// characteristics.
     pub async fn device_event_stream(
@@ -646,6 +2710,15 @@ impl BluetoothSession {
         self.filtered_event_stream(Some(device)).await
     }
 
+    /// Get a stream of events for a particular GATT service of a device. This includes events for
+    /// all its characteristics, but not for those of the device's other services.
+    pub async fn service_event_stream(
+        &self,
+        service: &ServiceId,
+    ) -> Result<impl Stream<Item = BluetoothEvent>, BluetoothError> {
+        self.filtered_event_stream(Some(service)).await
+    }
+
     /// Get a stream of events for a particular characteristic of a device.
     pub async fn characteristic_event_stream(
         &self,
@@ -657,13 +2730,88 @@ impl BluetoothSession {
     async fn filtered_event_stream(
         &self,
         object: Option<&(impl Into<Path<'static>> + Clone)>,
+    ) -> Result<impl Stream<Item = BluetoothEvent>, BluetoothError> {
+        self.filtered_event_stream_inner(object, false, &RetryPolicy::default())
+            .await
+    }
+
+    async fn filtered_event_stream_inner(
+        &self,
+        object: Option<&(impl Into<Path<'static>> + Clone)>,
+        include_unknown: bool,
+        retry: &RetryPolicy,
     ) -> Result<impl Stream<Item = BluetoothEvent>, BluetoothError> {
         let mut message_streams = vec![];
-        for match_rule in BluetoothEvent::match_rules(object.cloned()) {
-            let msg_match = self.connection.add_match(match_rule).await?;
+        for match_rule in BluetoothEvent::match_rules(&self.service_name, object.cloned()) {
+            let msg_match = retry
+                .retry(|| self.connection.add_match(match_rule.clone()))
+                .await?;
             message_streams.push(MessageStream::new(msg_match, self.connection.clone()));
         }
-        Ok(select_all(message_streams)
-            .flat_map(|message| stream::iter(BluetoothEvent::message_to_events(message))))
+        Ok(select_all(message_streams).flat_map(move |message| {
+            stream::iter(BluetoothEvent::message_to_events(message, include_unknown))
+        }))
+    }
+}
+
+/// Wait for the first characteristic value event from the given stream, or time out.
+async fn next_characteristic_value(
+    events: impl Stream<Item = BluetoothEvent>,
+    timeout: Duration,
+) -> Result<Vec<u8>, BluetoothError> {
+    let mut values = Box::pin(events.filter_map(|event| async move {
+        match event {
+            BluetoothEvent::Characteristic {
+                event: CharacteristicEvent::Value { value },
+                ..
+            } => Some(value),
+            _ => None,
+        }
+    }));
+    match time::timeout(timeout, values.next()).await {
+        Ok(Some(value)) => Ok(value),
+        // The event stream should never end, but if it does then there will never be a value.
+        Ok(None) | Err(_) => Err(BluetoothError::Timeout),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn read_options_to_propmap() {
+        let map: PropMap = (&ReadOptions::default()).into();
+        assert!(map.is_empty());
+
+        let map: PropMap = (&ReadOptions {
+            offset: Some(42),
+            device: Some(DeviceId::new("/org/bluez/hci0/dev_11_22_33_44_55_66")),
+        })
+            .into();
+        assert_eq!(map.len(), 2);
+        assert_eq!(prop_cast::<u16>(&map, "offset"), Some(&42));
+        assert_eq!(
+            prop_cast::<Path>(&map, "device"),
+            Some(&Path::from("/org/bluez/hci0/dev_11_22_33_44_55_66"))
+        );
+    }
+
+    #[test]
+    fn write_options_to_propmap() {
+        let map: PropMap = (&WriteOptions::default()).into();
+        assert!(map.is_empty());
+
+        let map: PropMap = (&WriteOptions {
+            offset: Some(42),
+            write_type: Some(WriteType::WithoutResponse),
+        })
+            .into();
+        assert_eq!(map.len(), 2);
+        assert_eq!(prop_cast::<u16>(&map, "offset"), Some(&42));
+        assert_eq!(
+            prop_cast::<String>(&map, "type"),
+            Some(&"command".to_string())
+        );
     }
 }
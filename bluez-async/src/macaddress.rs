@@ -0,0 +1,134 @@
+use serde_derive::Serialize;
+use std::fmt::{self, Display, Formatter};
+use std::str::FromStr;
+use thiserror::Error;
+
+/// MAC address of a Bluetooth device.
+#[derive(Clone, Debug, Eq, Hash, Ord, PartialEq, PartialOrd, Serialize)]
+#[serde(transparent)]
+pub struct MacAddress(pub(crate) String);
+
+impl MacAddress {
+    /// Whether this is a static random address, assuming that it is a random rather than public
+    /// address. The two most significant bits of a static random address are both 1, whereas
+    /// private addresses have 01 (resolvable) or 00 (non-resolvable).
+    pub(crate) fn is_static_random(&self) -> bool {
+        match self.0.get(0..2).map(|octet| u8::from_str_radix(octet, 16)) {
+            Some(Ok(octet)) => octet & 0xc0 == 0xc0,
+            _ => false,
+        }
+    }
+
+    /// Parse a MAC address in any of the common formats: colon-separated (`11:22:33:44:55:66`),
+    /// dash-separated (`11-22-33-44-55-66`) or with no separators at all (`112233445566`), as
+    /// often found in config files and QR codes. Hex digits may be either case.
+    ///
+    /// The result is always in the canonical uppercase colon-separated form. Use `from_str` to
+    /// accept only the colon-separated form.
+    pub fn parse_flexible(s: &str) -> Result<Self, ParseMacAddressError> {
+        if s.len() == 12 {
+            if !s.chars().all(|c| c.is_ascii_hexdigit()) {
+                return Err(ParseMacAddressError());
+            }
+            let octets: Vec<&str> = (0..6).map(|i| &s[i * 2..i * 2 + 2]).collect();
+            Ok(MacAddress(octets.join(":").to_uppercase()))
+        } else if s.contains('-') {
+            s.replace('-', ":").parse()
+        } else {
+            s.parse()
+        }
+    }
+}
+
+impl Display for MacAddress {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        f.write_str(&self.0)
+    }
+}
+
+/// An error parsing a MAC address from a string.
+#[derive(Clone, Debug, Error, Eq, PartialEq)]
+#[error("Invalid MAC address")]
+pub struct ParseMacAddressError();
+
+impl FromStr for MacAddress {
+    type Err = ParseMacAddressError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let octets: Vec<_> = s.split(':').collect();
+        if octets.len() != 6 {
+            return Err(ParseMacAddressError());
+        }
+        for octet in octets {
+            if octet.len() != 2 {
+                return Err(ParseMacAddressError());
+            }
+            if !octet.chars().all(|c| c.is_ascii_hexdigit()) {
+                return Err(ParseMacAddressError());
+            }
+        }
+        Ok(MacAddress(s.to_uppercase()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_strict() {
+        assert_eq!(
+            "11:22:33:aa:bb:cc".parse(),
+            Ok(MacAddress("11:22:33:AA:BB:CC".to_string()))
+        );
+        assert_eq!(
+            "11-22-33-aa-bb-cc".parse::<MacAddress>(),
+            Err(ParseMacAddressError())
+        );
+        assert_eq!(
+            "112233aabbcc".parse::<MacAddress>(),
+            Err(ParseMacAddressError())
+        );
+    }
+
+    #[test]
+    fn parse_flexible_colons() {
+        assert_eq!(
+            MacAddress::parse_flexible("11:22:33:aa:bb:cc"),
+            Ok(MacAddress("11:22:33:AA:BB:CC".to_string()))
+        );
+    }
+
+    #[test]
+    fn parse_flexible_dashes() {
+        assert_eq!(
+            MacAddress::parse_flexible("11-22-33-aa-bb-cc"),
+            Ok(MacAddress("11:22:33:AA:BB:CC".to_string()))
+        );
+    }
+
+    #[test]
+    fn parse_flexible_no_separators() {
+        assert_eq!(
+            MacAddress::parse_flexible("112233aabbcc"),
+            Ok(MacAddress("11:22:33:AA:BB:CC".to_string()))
+        );
+    }
+
+    #[test]
+    fn parse_flexible_invalid() {
+        assert_eq!(MacAddress::parse_flexible(""), Err(ParseMacAddressError()));
+        assert_eq!(
+            MacAddress::parse_flexible("112233aabbcg"),
+            Err(ParseMacAddressError())
+        );
+        assert_eq!(
+            MacAddress::parse_flexible("1122-33:aa:bb:cc"),
+            Err(ParseMacAddressError())
+        );
+        assert_eq!(
+            MacAddress::parse_flexible("11:22:33:aa:bb"),
+            Err(ParseMacAddressError())
+        );
+    }
+}
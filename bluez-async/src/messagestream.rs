@@ -1,15 +1,30 @@
+use dbus::channel::{MatchingReceiver, Token};
+use dbus::message::MatchRule;
 use dbus::nonblock::{MsgMatch, SyncConnection};
 use dbus::Message;
-use futures::channel::mpsc::UnboundedReceiver;
+use futures::channel::mpsc::{self, UnboundedReceiver};
 use futures::Stream;
 use std::pin::Pin;
 use std::sync::Arc;
 use std::task::{Context, Poll};
 
+use crate::spawnondrop::spawn_on_drop;
+
+/// How the match for a `MessageStream` was registered with the D-Bus connection.
+enum Registration {
+    /// Added with `add_match`.
+    Match(MsgMatch),
+    /// Added to the bus with the given match string, which may include argument filters which
+    /// `MatchRule` doesn't support, and received locally with the given token.
+    Receive { token: Token, match_str: String },
+}
+
 /// Wrapper for a stream of D-Bus messages which automatically removes the `MsgMatch` from the D-Bus
 /// connection when it is dropped.
+///
+/// Messages are buffered in an unbounded channel, so none are lost if the consumer is slow.
 pub struct MessageStream {
-    msg_match: Option<MsgMatch>,
+    registration: Option<Registration>,
     events: UnboundedReceiver<Message>,
     connection: Arc<SyncConnection>,
 }
@@ -18,11 +33,40 @@ impl MessageStream {
     pub fn new(msg_match: MsgMatch, connection: Arc<SyncConnection>) -> Self {
         let (msg_match, events) = msg_match.msg_stream();
         Self {
-            msg_match: Some(msg_match),
+            registration: Some(Registration::Match(msg_match)),
             events,
             connection,
         }
     }
+
+    /// Add a match for the given rule, but only for messages whose first argument is the given
+    /// string. The filtering is done by the D-Bus daemon, so other messages are never sent to us.
+    pub async fn with_arg0(
+        match_rule: MatchRule<'static>,
+        arg0: &str,
+        connection: Arc<SyncConnection>,
+    ) -> Result<Self, dbus::Error> {
+        let match_str = arg0_match_str(&match_rule, arg0);
+        connection.add_match_no_cb(&match_str).await?;
+        let (sender, events) = mpsc::unbounded();
+        let arg0 = arg0.to_owned();
+        let token = connection.start_receive(
+            match_rule,
+            Box::new(move |message, _| {
+                if message.get1::<&str>() == Some(arg0.as_str()) {
+                    // If the receiver has been dropped then remove the callback.
+                    sender.unbounded_send(message).is_ok()
+                } else {
+                    true
+                }
+            }),
+        );
+        Ok(Self {
+            registration: Some(Registration::Receive { token, match_str }),
+            events,
+            connection,
+        })
+    }
 }
 
 impl Stream for MessageStream {
@@ -36,7 +80,54 @@ impl Stream for MessageStream {
 impl Drop for MessageStream {
     fn drop(&mut self) {
         let connection = self.connection.clone();
-        let msg_match = self.msg_match.take().unwrap();
-        tokio::spawn(async move { connection.remove_match(msg_match.token()).await.unwrap() });
+        let registration = self.registration.take().unwrap();
+        if let Registration::Receive { token, .. } = &registration {
+            connection.stop_receive(*token);
+        }
+        spawn_on_drop("removing D-Bus match".to_string(), async move {
+            match registration {
+                Registration::Match(msg_match) => connection.remove_match(msg_match.token()).await,
+                Registration::Receive { match_str, .. } => {
+                    connection.remove_match_no_cb(&match_str).await
+                }
+            }
+        });
+    }
+}
+
+/// Get the match string for the given rule with an additional filter on the first argument.
+///
+/// Values in match rules are quoted with apostrophes, so any apostrophes in the argument are
+/// escaped by ending the quoted string, adding an escaped apostrophe and starting it again.
+fn arg0_match_str(match_rule: &MatchRule, arg0: &str) -> String {
+    format!(
+        "{},arg0='{}'",
+        match_rule.match_str(),
+        arg0.replace('\'', r"'\''")
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn arg0_filter() {
+        let match_rule =
+            MatchRule::new_signal("org.freedesktop.DBus.Properties", "PropertiesChanged");
+        assert_eq!(
+            arg0_match_str(&match_rule, "org.bluez.GattCharacteristic1"),
+            "type='signal',interface='org.freedesktop.DBus.Properties',member='PropertiesChanged',arg0='org.bluez.GattCharacteristic1'"
+        );
+    }
+
+    #[test]
+    fn arg0_filter_escaped() {
+        let match_rule =
+            MatchRule::new_signal("org.freedesktop.DBus.Properties", "PropertiesChanged");
+        assert_eq!(
+            arg0_match_str(&match_rule, "it's"),
+            r"type='signal',interface='org.freedesktop.DBus.Properties',member='PropertiesChanged',arg0='it'\''s'"
+        );
     }
 }
@@ -0,0 +1,338 @@
+//! Support for BlueZ's `AdvertisementMonitorManager1` interface, which lets the host offload
+//! filtering of advertisements to the controller where the controller supports it.
+//!
+//! This is still marked as experimental in BlueZ, so BlueZ must be started with `--experimental`
+//! for it to be available.
+
+use dbus::arg::{prop_cast, PropMap, Variant};
+use dbus::nonblock::stdintf::org_freedesktop_dbus::Properties;
+use dbus::nonblock::Proxy;
+use dbus::{Message, Path};
+use futures::channel::mpsc::{self, UnboundedReceiver, UnboundedSender};
+use futures::Stream;
+use std::collections::HashMap;
+use std::fmt::{self, Debug, Formatter};
+use std::pin::Pin;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::task::{Context, Poll};
+
+use crate::exportedobject::ExportedObject;
+use crate::{AdapterId, BluetoothError, BluetoothSession, DeviceId, DBUS_METHOD_CALL_TIMEOUT};
+
+const ORG_BLUEZ_ADVERTISEMENT_MONITOR_MANAGER1_NAME: &str =
+    "org.bluez.AdvertisementMonitorManager1";
+const ORG_BLUEZ_ADVERTISEMENT_MONITOR1_NAME: &str = "org.bluez.AdvertisementMonitor1";
+const OBJECT_MANAGER_NAME: &str = "org.freedesktop.DBus.ObjectManager";
+
+/// AD type for service data with a 16-bit UUID.
+const AD_TYPE_SERVICE_DATA_16: u8 = 0x16;
+
+/// Used to give each registered monitor application a unique object path.
+static NEXT_MONITOR_ID: AtomicUsize = AtomicUsize::new(0);
+
+/// The advertisement monitoring features supported by a Bluetooth adapter.
+#[derive(Clone, Debug, Default, Eq, PartialEq)]
+pub struct MonitorFeatures {
+    /// The types of advertisement monitor which BlueZ supports, e.g. `"or_patterns"`.
+    pub monitor_types: Vec<String>,
+    /// The monitoring features supported by the controller, e.g. `"controller-patterns"` if it can
+    /// offload pattern matching from the host.
+    pub controller_features: Vec<String>,
+}
+
+/// A pattern to match against the advertising data of a device.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct MonitorPattern {
+    /// The index in the AD data of the given type at which to start matching.
+    pub start_position: u8,
+    /// The AD type to match, as assigned by the Bluetooth SIG.
+    pub ad_type: u8,
+    /// The bytes to match.
+    pub content: Vec<u8>,
+}
+
+impl MonitorPattern {
+    /// A pattern which matches devices advertising service data for the given 16-bit service UUID.
+    pub fn service_data(uuid: u16) -> Self {
+        Self {
+            start_position: 0,
+            ad_type: AD_TYPE_SERVICE_DATA_16,
+            content: uuid.to_le_bytes().to_vec(),
+        }
+    }
+}
+
+/// The specification for an advertisement monitor. Devices are reported as found if any of the
+/// patterns match, and they meet the RSSI thresholds (if any).
+///
+/// RSSI parameters may be set to `None` to use the BlueZ defaults.
+#[derive(Clone, Debug, Default, Eq, PartialEq)]
+pub struct MonitorSpec {
+    /// The patterns to match. A device matches if any one of them matches.
+    pub patterns: Vec<MonitorPattern>,
+    /// A device is considered lost if its RSSI is below this threshold for `rssi_low_timeout`.
+    pub rssi_low_threshold: Option<i16>,
+    /// A device is considered found if its RSSI is above this threshold for `rssi_high_timeout`.
+    pub rssi_high_threshold: Option<i16>,
+    /// The time in seconds for `rssi_low_threshold`.
+    pub rssi_low_timeout: Option<u16>,
+    /// The time in seconds for `rssi_high_threshold`.
+    pub rssi_high_timeout: Option<u16>,
+    /// The period in units of 100 ms over which the controller should sample RSSI values.
+    pub rssi_sampling_period: Option<u16>,
+}
+
+impl MonitorSpec {
+    /// Get the D-Bus properties of the `AdvertisementMonitor1` object for this spec.
+    fn to_properties(&self) -> PropMap {
+        let mut map: PropMap = HashMap::new();
+        map.insert(
+            "Type".to_string(),
+            Variant(Box::new("or_patterns".to_string())),
+        );
+        let patterns: Vec<(u8, u8, Vec<u8>)> = self
+            .patterns
+            .iter()
+            .map(|pattern| {
+                (
+                    pattern.start_position,
+                    pattern.ad_type,
+                    pattern.content.clone(),
+                )
+            })
+            .collect();
+        map.insert("Patterns".to_string(), Variant(Box::new(patterns)));
+        if let Some(rssi_low_threshold) = self.rssi_low_threshold {
+            map.insert(
+                "RSSILowThreshold".to_string(),
+                Variant(Box::new(rssi_low_threshold)),
+            );
+        }
+        if let Some(rssi_high_threshold) = self.rssi_high_threshold {
+            map.insert(
+                "RSSIHighThreshold".to_string(),
+                Variant(Box::new(rssi_high_threshold)),
+            );
+        }
+        if let Some(rssi_low_timeout) = self.rssi_low_timeout {
+            map.insert(
+                "RSSILowTimeout".to_string(),
+                Variant(Box::new(rssi_low_timeout)),
+            );
+        }
+        if let Some(rssi_high_timeout) = self.rssi_high_timeout {
+            map.insert(
+                "RSSIHighTimeout".to_string(),
+                Variant(Box::new(rssi_high_timeout)),
+            );
+        }
+        if let Some(rssi_sampling_period) = self.rssi_sampling_period {
+            map.insert(
+                "RSSISamplingPeriod".to_string(),
+                Variant(Box::new(rssi_sampling_period)),
+            );
+        }
+        map
+    }
+}
+
+/// An event from an advertisement monitor.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub enum MonitorEvent {
+    /// A device matching the monitor has been found.
+    DeviceFound { id: DeviceId },
+    /// A device which was previously found is no longer matching the monitor.
+    DeviceLost { id: DeviceId },
+}
+
+/// A registered advertisement monitor. This is a stream of events for devices which match it.
+///
+/// The monitor is unregistered when this is dropped.
+pub struct AdvertisementMonitor {
+    application: ExportedObject,
+    events: UnboundedReceiver<MonitorEvent>,
+}
+
+impl Debug for AdvertisementMonitor {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        write!(f, "AdvertisementMonitor({})", self.application.path())
+    }
+}
+
+impl Stream for AdvertisementMonitor {
+    type Item = MonitorEvent;
+
+    fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        Pin::new(&mut self.events).poll_next(cx)
+    }
+}
+
+impl BluetoothSession {
+    /// Get the advertisement monitoring features supported by the given Bluetooth adapter.
+    ///
+    /// This will fail if BlueZ wasn't started with experimental features enabled.
+    pub async fn supported_features(
+        &self,
+        adapter: &AdapterId,
+    ) -> Result<MonitorFeatures, BluetoothError> {
+        let monitor_manager = Proxy::new(
+            self.service_name.clone(),
+            adapter.object_path.to_owned(),
+            DBUS_METHOD_CALL_TIMEOUT,
+            self.connection.clone(),
+        );
+        let properties = monitor_manager
+            .get_all(ORG_BLUEZ_ADVERTISEMENT_MONITOR_MANAGER1_NAME)
+            .await?;
+        Ok(MonitorFeatures {
+            monitor_types: prop_cast::<Vec<String>>(&properties, "SupportedMonitorTypes")
+                .cloned()
+                .unwrap_or_default(),
+            controller_features: prop_cast::<Vec<String>>(&properties, "SupportedFeatures")
+                .cloned()
+                .unwrap_or_default(),
+        })
+    }
+
+    /// Register an advertisement monitor on the given adapter. BlueZ will scan for devices matching
+    /// it, offloading the matching to the controller if the controller supports it, and report
+    /// them via the returned stream.
+    ///
+    /// Returns `BluetoothError::NotSupported` if BlueZ doesn't support advertisement monitors, e.g.
+    /// because it wasn't started with experimental features enabled. In that case you can fall back
+    /// to `start_discovery_with_filter`.
+    pub async fn register_advertisement_monitor(
+        &self,
+        adapter: &AdapterId,
+        monitor: MonitorSpec,
+    ) -> Result<AdvertisementMonitor, BluetoothError> {
+        let id = NEXT_MONITOR_ID.fetch_add(1, Ordering::Relaxed);
+        let application_path: Path<'static> = format!("/bluez_async/monitor{}", id).into();
+        let monitor_path: Path<'static> = format!("{}/monitor0", application_path).into();
+
+        let (sender, events) = mpsc::unbounded();
+        let mut application = {
+            let application_path = application_path.clone();
+            ExportedObject::new(
+                self,
+                application_path.clone(),
+                true,
+                adapter.object_path.clone(),
+                ORG_BLUEZ_ADVERTISEMENT_MONITOR_MANAGER1_NAME,
+                "advertisement monitor",
+                move |message| {
+                    Some(handle_monitor_message(
+                        &message,
+                        &application_path,
+                        &monitor_path,
+                        &monitor,
+                        &sender,
+                    ))
+                },
+            )
+        };
+        let result = application
+            .register("RegisterMonitor", (application_path,), "UnregisterMonitor")
+            .await;
+        result.map_err(|e| match e.name() {
+            Some("org.freedesktop.DBus.Error.UnknownMethod")
+            | Some("org.freedesktop.DBus.Error.UnknownObject") => {
+                BluetoothError::NotSupported("Advertisement monitoring".to_string())
+            }
+            _ => e.into(),
+        })?;
+        Ok(AdvertisementMonitor {
+            application,
+            events,
+        })
+    }
+}
+
+/// Handle a method call from BlueZ to the monitor application or the monitor object, sending any
+/// resulting events to the given sender, and return the reply to send.
+fn handle_monitor_message(
+    message: &Message,
+    application_path: &Path<'static>,
+    monitor_path: &Path<'static>,
+    monitor: &MonitorSpec,
+    sender: &UnboundedSender<MonitorEvent>,
+) -> Message {
+    let path = message.path();
+    let interface = message.interface();
+    let member = message.member();
+    match (path.as_deref(), interface.as_deref(), member.as_deref()) {
+        (Some(path), Some(OBJECT_MANAGER_NAME), Some("GetManagedObjects"))
+            if path == &**application_path =>
+        {
+            let mut interfaces = HashMap::new();
+            interfaces.insert(
+                ORG_BLUEZ_ADVERTISEMENT_MONITOR1_NAME.to_string(),
+                monitor.to_properties(),
+            );
+            let mut objects = HashMap::new();
+            objects.insert(monitor_path.clone(), interfaces);
+            message.method_return().append1(objects)
+        }
+        (Some(path), Some(ORG_BLUEZ_ADVERTISEMENT_MONITOR1_NAME), Some(member))
+            if path == &**monitor_path =>
+        {
+            match member {
+                "DeviceFound" | "DeviceLost" => {
+                    if let Ok(object_path) = message.read1::<Path>() {
+                        let id = DeviceId {
+                            object_path: object_path.into_static(),
+                        };
+                        let event = if member == "DeviceFound" {
+                            MonitorEvent::DeviceFound { id }
+                        } else {
+                            MonitorEvent::DeviceLost { id }
+                        };
+                        // If the receiver has been dropped then the monitor is being unregistered,
+                        // so it doesn't matter if this fails.
+                        let _ = sender.unbounded_send(event);
+                    }
+                }
+                "Activate" => log::trace!("Advertisement monitor {} activated", monitor_path),
+                "Release" => log::trace!("Advertisement monitor {} released", monitor_path),
+                _ => {}
+            }
+            message.method_return()
+        }
+        _ => dbus::channel::default_reply(message).unwrap_or_else(|| message.method_return()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn service_data_pattern() {
+        assert_eq!(
+            MonitorPattern::service_data(0xfe95),
+            MonitorPattern {
+                start_position: 0,
+                ad_type: 0x16,
+                content: vec![0x95, 0xfe],
+            }
+        );
+    }
+
+    #[test]
+    fn monitor_properties_minimal() {
+        let properties = MonitorSpec {
+            patterns: vec![MonitorPattern::service_data(0xfe95)],
+            ..MonitorSpec::default()
+        }
+        .to_properties();
+        assert_eq!(
+            prop_cast::<String>(&properties, "Type"),
+            Some(&"or_patterns".to_string())
+        );
+        assert_eq!(
+            prop_cast::<Vec<(u8, u8, Vec<u8>)>>(&properties, "Patterns"),
+            Some(&vec![(0, 0x16, vec![0x95, 0xfe])])
+        );
+        assert!(!properties.contains_key("RSSILowThreshold"));
+    }
+}
@@ -0,0 +1,273 @@
+//! Minimal support for the peripheral role, by registering advertisements with BlueZ's
+//! `LEAdvertisingManager1` interface.
+
+use dbus::arg::{PropMap, RefArg, Variant};
+use dbus::{Message, Path};
+use std::collections::HashMap;
+use std::fmt::{self, Debug, Display, Formatter};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use uuid::Uuid;
+
+use crate::exportedobject::ExportedObject;
+use crate::{AdapterId, BluetoothError, BluetoothSession};
+
+const ORG_BLUEZ_LE_ADVERTISING_MANAGER1_NAME: &str = "org.bluez.LEAdvertisingManager1";
+const ORG_BLUEZ_LE_ADVERTISEMENT1_NAME: &str = "org.bluez.LEAdvertisement1";
+const PROPERTIES_NAME: &str = "org.freedesktop.DBus.Properties";
+
+/// Used to give each registered advertisement a unique object path.
+static NEXT_ADVERTISEMENT_ID: AtomicUsize = AtomicUsize::new(0);
+
+/// The type of an advertisement.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum AdvertisementType {
+    /// A connectable advertisement.
+    Peripheral,
+    /// A non-connectable advertisement, e.g. for a beacon.
+    Broadcast,
+}
+
+impl AdvertisementType {
+    fn as_str(&self) -> &'static str {
+        match self {
+            Self::Peripheral => "peripheral",
+            Self::Broadcast => "broadcast",
+        }
+    }
+}
+
+impl Display for AdvertisementType {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+/// The contents and parameters of an advertisement to register with
+/// [`BluetoothSession::register_advertisement`].
+///
+/// Optional parameters may be left as `None` to use the BlueZ defaults.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct Advertisement {
+    /// The type of advertisement.
+    pub advertisement_type: AdvertisementType,
+    /// Service UUIDs to include in the advertisement.
+    pub service_uuids: Vec<Uuid>,
+    /// Manufacturer-specific data to include in the advertisement, keyed by company ID.
+    pub manufacturer_data: HashMap<u16, Vec<u8>>,
+    /// The local name to include in the advertisement.
+    pub local_name: Option<String>,
+    /// Whether to include the transmit power in the advertisement.
+    pub include_tx_power: bool,
+    /// The transmit power to request, in dBm. The adapter may not support this.
+    pub tx_power: Option<i16>,
+    /// The minimum advertising interval, in milliseconds.
+    pub min_interval: Option<u32>,
+    /// The maximum advertising interval, in milliseconds.
+    pub max_interval: Option<u32>,
+}
+
+impl Default for Advertisement {
+    fn default() -> Self {
+        Self {
+            advertisement_type: AdvertisementType::Peripheral,
+            service_uuids: vec![],
+            manufacturer_data: HashMap::new(),
+            local_name: None,
+            include_tx_power: false,
+            tx_power: None,
+            min_interval: None,
+            max_interval: None,
+        }
+    }
+}
+
+impl Advertisement {
+    /// Get the D-Bus properties of the `LEAdvertisement1` object for this advertisement.
+    fn to_properties(&self) -> PropMap {
+        let mut map: PropMap = HashMap::new();
+        map.insert(
+            "Type".to_string(),
+            Variant(Box::new(self.advertisement_type.to_string())),
+        );
+        if !self.service_uuids.is_empty() {
+            let uuids: Vec<String> = self.service_uuids.iter().map(Uuid::to_string).collect();
+            map.insert("ServiceUUIDs".to_string(), Variant(Box::new(uuids)));
+        }
+        if !self.manufacturer_data.is_empty() {
+            let manufacturer_data: HashMap<u16, Variant<Box<dyn RefArg>>> = self
+                .manufacturer_data
+                .iter()
+                .map(|(&id, data)| (id, Variant(Box::new(data.clone()) as Box<dyn RefArg>)))
+                .collect();
+            map.insert(
+                "ManufacturerData".to_string(),
+                Variant(Box::new(manufacturer_data)),
+            );
+        }
+        if let Some(local_name) = &self.local_name {
+            map.insert(
+                "LocalName".to_string(),
+                Variant(Box::new(local_name.to_owned())),
+            );
+        }
+        if self.include_tx_power {
+            map.insert(
+                "Includes".to_string(),
+                Variant(Box::new(vec!["tx-power".to_string()])),
+            );
+        }
+        if let Some(tx_power) = self.tx_power {
+            map.insert("TxPower".to_string(), Variant(Box::new(tx_power)));
+        }
+        if let Some(min_interval) = self.min_interval {
+            map.insert("MinInterval".to_string(), Variant(Box::new(min_interval)));
+        }
+        if let Some(max_interval) = self.max_interval {
+            map.insert("MaxInterval".to_string(), Variant(Box::new(max_interval)));
+        }
+        map
+    }
+}
+
+/// An advertisement which has been registered with BlueZ.
+///
+/// The advertisement is unregistered when this is dropped.
+pub struct RegisteredAdvertisement {
+    advertisement: ExportedObject,
+}
+
+impl Debug for RegisteredAdvertisement {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        write!(f, "RegisteredAdvertisement({})", self.advertisement.path())
+    }
+}
+
+impl BluetoothSession {
+    /// Register an advertisement on the given adapter, so that other devices can discover it.
+    ///
+    /// The advertisement remains active until the returned value is dropped.
+    pub async fn register_advertisement(
+        &self,
+        adapter: &AdapterId,
+        advertisement: Advertisement,
+    ) -> Result<RegisteredAdvertisement, BluetoothError> {
+        let id = NEXT_ADVERTISEMENT_ID.fetch_add(1, Ordering::Relaxed);
+        let advertisement_path: Path<'static> = format!("/bluez_async/advertisement{}", id).into();
+
+        let properties = advertisement.to_properties();
+        let mut exported_advertisement = ExportedObject::new(
+            self,
+            advertisement_path.clone(),
+            false,
+            adapter.object_path.clone(),
+            ORG_BLUEZ_LE_ADVERTISING_MANAGER1_NAME,
+            "advertisement",
+            move |message| Some(handle_advertisement_message(&message, &properties)),
+        );
+        let options: PropMap = HashMap::new();
+        exported_advertisement
+            .register(
+                "RegisterAdvertisement",
+                (advertisement_path, options),
+                "UnregisterAdvertisement",
+            )
+            .await?;
+        Ok(RegisteredAdvertisement {
+            advertisement: exported_advertisement,
+        })
+    }
+}
+
+/// Handle a method call from BlueZ to an advertisement object, and return the reply to send.
+fn handle_advertisement_message(message: &Message, properties: &PropMap) -> Message {
+    let interface = message.interface();
+    let member = message.member();
+    match (interface.as_deref(), member.as_deref()) {
+        (Some(PROPERTIES_NAME), Some("GetAll")) => match message.read1::<&str>() {
+            Ok(ORG_BLUEZ_LE_ADVERTISEMENT1_NAME) => message.method_return().append1(properties),
+            _ => message.method_return().append1(PropMap::new()),
+        },
+        (Some(PROPERTIES_NAME), Some("Get")) => match message.read2::<&str, &str>() {
+            Ok((ORG_BLUEZ_LE_ADVERTISEMENT1_NAME, name)) if properties.contains_key(name) => {
+                message.method_return().append1(&properties[name])
+            }
+            _ => dbus::channel::default_reply(message).unwrap_or_else(|| message.method_return()),
+        },
+        (Some(ORG_BLUEZ_LE_ADVERTISEMENT1_NAME), Some("Release")) => {
+            log::trace!("Advertisement {:?} released", message.path());
+            message.method_return()
+        }
+        _ => dbus::channel::default_reply(message).unwrap_or_else(|| message.method_return()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::uuid_from_u16;
+    use dbus::arg::prop_cast;
+
+    #[test]
+    fn advertisement_properties_minimal() {
+        let properties = Advertisement::default().to_properties();
+        assert_eq!(
+            prop_cast::<String>(&properties, "Type"),
+            Some(&"peripheral".to_string())
+        );
+        assert_eq!(properties.len(), 1);
+    }
+
+    #[test]
+    fn advertisement_properties() {
+        let mut manufacturer_data = HashMap::new();
+        manufacturer_data.insert(0x1234, vec![1, 2, 3]);
+        let properties = Advertisement {
+            advertisement_type: AdvertisementType::Broadcast,
+            service_uuids: vec![uuid_from_u16(0x180f)],
+            manufacturer_data,
+            local_name: Some("gateway".to_string()),
+            include_tx_power: true,
+            ..Default::default()
+        }
+        .to_properties();
+        assert_eq!(
+            prop_cast::<String>(&properties, "Type"),
+            Some(&"broadcast".to_string())
+        );
+        assert_eq!(
+            prop_cast::<Vec<String>>(&properties, "ServiceUUIDs"),
+            Some(&vec!["0000180f-0000-1000-8000-00805f9b34fb".to_string()])
+        );
+        assert_eq!(
+            prop_cast::<String>(&properties, "LocalName"),
+            Some(&"gateway".to_string())
+        );
+        assert_eq!(
+            prop_cast::<Vec<String>>(&properties, "Includes"),
+            Some(&vec!["tx-power".to_string()])
+        );
+        assert!(properties.contains_key("ManufacturerData"));
+        assert!(!properties.contains_key("TxPower"));
+    }
+
+    #[test]
+    fn get_all_properties() {
+        let properties = Advertisement::default().to_properties();
+        let mut message = Message::new_method_call(
+            "org.bluez",
+            "/bluez_async/advertisement0",
+            PROPERTIES_NAME,
+            "GetAll",
+        )
+        .unwrap()
+        .append1(ORG_BLUEZ_LE_ADVERTISEMENT1_NAME);
+        message.set_serial(1);
+
+        let reply = handle_advertisement_message(&message, &properties);
+        let returned: PropMap = reply.read1().unwrap();
+        assert_eq!(
+            prop_cast::<String>(&returned, "Type"),
+            Some(&"peripheral".to_string())
+        );
+    }
+}
@@ -0,0 +1,104 @@
+use futures::Stream;
+use std::pin::Pin;
+use std::task::{Context, Poll};
+
+use crate::{DeviceInfo, DiscoveryFilter};
+
+/// Extension trait for streams of `DeviceInfo`s.
+pub trait DeviceInfoStreamExt: Stream<Item = DeviceInfo> + Sized {
+    /// Drop devices which don't match the given discovery filter, checked locally with
+    /// [`DiscoveryFilter::matches_device_locally`].
+    ///
+    /// BlueZ merges the discovery filters of all clients, so if another process on the system has
+    /// set a looser filter then devices which don't match yours will still be reported. This
+    /// re-applies your filter so you get the semantics you asked for regardless.
+    fn refiltered(self, filter: DiscoveryFilter) -> Refiltered<Self> {
+        Refiltered {
+            devices: Box::pin(self),
+            filter,
+        }
+    }
+}
+
+impl<S: Stream<Item = DeviceInfo>> DeviceInfoStreamExt for S {}
+
+/// Stream returned by [`DeviceInfoStreamExt::refiltered`].
+pub struct Refiltered<S> {
+    devices: Pin<Box<S>>,
+    filter: DiscoveryFilter,
+}
+
+impl<S: Stream<Item = DeviceInfo>> Stream for Refiltered<S> {
+    type Item = DeviceInfo;
+
+    fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<DeviceInfo>> {
+        loop {
+            match self.devices.as_mut().poll_next(cx) {
+                Poll::Ready(Some(device)) if !self.filter.matches_device_locally(&device) => {}
+                other => return other,
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::device::test_device_info;
+    use crate::{uuid_from_u16, MacAddress};
+    use futures::stream::{self, StreamExt};
+
+    fn device(mac_address: &str, rssi: Option<i16>) -> DeviceInfo {
+        DeviceInfo {
+            mac_address: MacAddress(mac_address.to_string()),
+            rssi,
+            ..test_device_info()
+        }
+    }
+
+    #[test]
+    fn rssi_threshold() {
+        let filter = DiscoveryFilter {
+            rssi_threshold: Some(-70),
+            ..Default::default()
+        };
+        assert!(filter.matches_device_locally(&device("11:22:33:44:55:66", Some(-60))));
+        assert!(!filter.matches_device_locally(&device("11:22:33:44:55:66", Some(-80))));
+        assert!(!filter.matches_device_locally(&device("11:22:33:44:55:66", None)));
+    }
+
+    #[test]
+    fn service_uuids() {
+        let uuid = uuid_from_u16(0x180f);
+        let filter = DiscoveryFilter {
+            service_uuids: vec![uuid],
+            ..Default::default()
+        };
+        let mut advertised = device("11:22:33:44:55:66", None);
+        assert!(!filter.matches_device_locally(&advertised));
+        advertised.services = vec![uuid];
+        assert!(filter.matches_device_locally(&advertised));
+
+        let mut with_service_data = device("11:22:33:44:55:66", None);
+        with_service_data.service_data.insert(uuid, vec![1, 2]);
+        assert!(filter.matches_device_locally(&with_service_data));
+    }
+
+    #[tokio::test]
+    async fn refiltered_stream() {
+        let filter = DiscoveryFilter {
+            rssi_threshold: Some(-70),
+            pattern: Some("11:22".to_string()),
+            ..Default::default()
+        };
+        let devices = stream::iter(vec![
+            device("11:22:33:44:55:66", Some(-60)),
+            device("11:22:33:44:55:66", Some(-80)),
+            device("AA:BB:33:44:55:66", Some(-60)),
+        ]);
+        assert_eq!(
+            devices.refiltered(filter).collect::<Vec<_>>().await,
+            vec![device("11:22:33:44:55:66", Some(-60))]
+        );
+    }
+}
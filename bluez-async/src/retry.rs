@@ -0,0 +1,111 @@
+use std::fmt::Display;
+use std::future::Future;
+use std::time::Duration;
+use tokio::time;
+
+/// How many times to try setting up D-Bus matches for an event stream, and how long to wait
+/// between attempts, so that a transient D-Bus error doesn't fail the whole stream.
+///
+/// The default is three attempts one second apart.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct RetryPolicy {
+    /// The maximum number of attempts, including the first. If this is 0 or 1 then there are no
+    /// retries.
+    pub max_attempts: u32,
+    /// How long to wait after a failed attempt before trying again.
+    pub delay: Duration,
+}
+
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        Self {
+            max_attempts: 3,
+            delay: Duration::from_secs(1),
+        }
+    }
+}
+
+impl RetryPolicy {
+    /// Call the given function until it succeeds or the maximum number of attempts is reached,
+    /// returning the last error in the latter case.
+    pub(crate) async fn retry<T, E: Display, Fut: Future<Output = Result<T, E>>>(
+        &self,
+        mut f: impl FnMut() -> Fut,
+    ) -> Result<T, E> {
+        let mut attempt = 1;
+        loop {
+            match f().await {
+                Ok(value) => return Ok(value),
+                Err(e) if attempt < self.max_attempts => {
+                    log::warn!(
+                        "Attempt {} of {} failed, retrying: {}",
+                        attempt,
+                        self.max_attempts,
+                        e
+                    );
+                    time::sleep(self.delay).await;
+                    attempt += 1;
+                }
+                Err(e) => return Err(e),
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::cell::Cell;
+
+    fn policy(max_attempts: u32) -> RetryPolicy {
+        RetryPolicy {
+            max_attempts,
+            delay: Duration::from_millis(0),
+        }
+    }
+
+    #[tokio::test]
+    async fn succeeds_after_retries() {
+        let attempts = Cell::new(0);
+        let result = policy(3)
+            .retry(|| {
+                attempts.set(attempts.get() + 1);
+                let attempt = attempts.get();
+                async move {
+                    if attempt < 3 {
+                        Err("failed")
+                    } else {
+                        Ok(attempt)
+                    }
+                }
+            })
+            .await;
+        assert_eq!(result, Ok(3));
+    }
+
+    #[tokio::test]
+    async fn gives_up() {
+        let attempts = Cell::new(0);
+        let result: Result<(), _> = policy(2)
+            .retry(|| {
+                attempts.set(attempts.get() + 1);
+                async { Err("failed") }
+            })
+            .await;
+        assert_eq!(result, Err("failed"));
+        assert_eq!(attempts.get(), 2);
+    }
+
+    #[tokio::test]
+    async fn no_retries() {
+        let attempts = Cell::new(0);
+        let result: Result<(), _> = policy(0)
+            .retry(|| {
+                attempts.set(attempts.get() + 1);
+                async { Err("failed") }
+            })
+            .await;
+        assert_eq!(result, Err("failed"));
+        assert_eq!(attempts.get(), 1);
+    }
+}
@@ -0,0 +1,27 @@
+//! Serde support for serializing byte values as hex strings, for use with
+//! `#[serde(serialize_with = "crate::serde_hex::...")]`.
+
+use serde::{Serialize, Serializer};
+use std::collections::HashMap;
+
+/// Encode the given bytes as a lowercase hexadecimal string.
+pub(crate) fn hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|byte| format!("{:02x}", byte)).collect()
+}
+
+pub(crate) fn serialize_option<S: Serializer>(
+    value: &Option<Vec<u8>>,
+    serializer: S,
+) -> Result<S::Ok, S::Error> {
+    match value {
+        Some(value) => serializer.serialize_some(&hex(value)),
+        None => serializer.serialize_none(),
+    }
+}
+
+pub(crate) fn serialize_map<K: Serialize, S: Serializer>(
+    map: &HashMap<K, Vec<u8>>,
+    serializer: S,
+) -> Result<S::Ok, S::Error> {
+    serializer.collect_map(map.iter().map(|(key, value)| (key, hex(value))))
+}
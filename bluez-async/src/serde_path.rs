@@ -0,0 +1,16 @@
+//! Serde support for D-Bus object paths, for use with `#[serde(with = "crate::serde_path")]`.
+
+use dbus::Path;
+use serde::de::Error;
+use serde::{Deserialize, Deserializer, Serializer};
+
+pub(crate) fn serialize<S: Serializer>(path: &Path, serializer: S) -> Result<S::Ok, S::Error> {
+    serializer.serialize_str(path)
+}
+
+pub(crate) fn deserialize<'de, D: Deserializer<'de>>(
+    deserializer: D,
+) -> Result<Path<'static>, D::Error> {
+    let path = String::deserialize(deserializer)?;
+    Path::new(path).map_err(D::Error::custom)
+}
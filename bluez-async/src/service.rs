@@ -1,12 +1,14 @@
 use dbus::Path;
+use serde_derive::{Deserialize, Serialize};
 use std::fmt::{self, Display, Formatter};
 use uuid::Uuid;
 
 use crate::DeviceId;
 
 /// Opaque identifier for a GATT service on a Bluetooth device.
-#[derive(Clone, Debug, Eq, Hash, Ord, PartialEq, PartialOrd)]
+#[derive(Clone, Debug, Deserialize, Eq, Hash, Ord, PartialEq, PartialOrd, Serialize)]
 pub struct ServiceId {
+    #[serde(with = "crate::serde_path")]
     pub(crate) object_path: Path<'static>,
 }
 
@@ -17,6 +19,11 @@ impl ServiceId {
         }
     }
 
+    /// Get the D-Bus object path of the service, for use with the `dbus` crate directly.
+    pub fn path(&self) -> &Path<'static> {
+        &self.object_path
+    }
+
     /// Get the ID of the device on which this service was advertised.
     pub fn device(&self) -> DeviceId {
         let index = self
@@ -27,6 +34,12 @@ impl ServiceId {
     }
 }
 
+impl AsRef<Path<'static>> for ServiceId {
+    fn as_ref(&self) -> &Path<'static> {
+        &self.object_path
+    }
+}
+
 impl From<ServiceId> for Path<'static> {
     fn from(id: ServiceId) -> Self {
         id.object_path
@@ -47,7 +60,7 @@ impl Display for ServiceId {
 }
 
 /// Information about a GATT service on a Bluetooth device.
-#[derive(Clone, Debug, Eq, PartialEq)]
+#[derive(Clone, Debug, Deserialize, Eq, PartialEq, Serialize)]
 pub struct ServiceInfo {
     /// An opaque identifier for the service on the device, including a reference to which adapter
     /// it was discovered on.
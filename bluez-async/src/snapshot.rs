@@ -0,0 +1,205 @@
+use serde_derive::Serialize;
+
+use crate::serde_hex::hex;
+use crate::{
+    BluetoothError, BluetoothSession, CharacteristicInfo, DescriptorInfo, DeviceId, DeviceInfo,
+    GattDatabase, ServiceInfo,
+};
+
+/// A snapshot of everything known about a device, for serializing to JSON.
+#[derive(Clone, Debug, Serialize)]
+struct DeviceSnapshot {
+    #[serde(flatten)]
+    device: DeviceInfo,
+    gatt_services: Vec<ServiceSnapshot>,
+    /// The error getting the GATT database, if any.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    gatt_error: Option<String>,
+}
+
+#[derive(Clone, Debug, Serialize)]
+struct ServiceSnapshot {
+    info: ServiceInfo,
+    characteristics: Vec<CharacteristicSnapshot>,
+}
+
+#[derive(Clone, Debug, Serialize)]
+struct CharacteristicSnapshot {
+    info: CharacteristicInfo,
+    #[serde(flatten)]
+    value: ValueSnapshot,
+    descriptors: Vec<DescriptorSnapshot>,
+}
+
+#[derive(Clone, Debug, Serialize)]
+struct DescriptorSnapshot {
+    info: DescriptorInfo,
+    #[serde(flatten)]
+    value: ValueSnapshot,
+}
+
+/// The result of reading a characteristic or descriptor value.
+#[derive(Clone, Debug, Default, Serialize)]
+struct ValueSnapshot {
+    /// The value, hex encoded, if it was read successfully.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    value: Option<String>,
+    /// The error reading the value, if any.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    read_error: Option<String>,
+}
+
+impl From<Result<Vec<u8>, BluetoothError>> for ValueSnapshot {
+    fn from(result: Result<Vec<u8>, BluetoothError>) -> Self {
+        match result {
+            Ok(value) => Self {
+                value: Some(hex(&value)),
+                read_error: None,
+            },
+            Err(e) => Self {
+                value: None,
+                read_error: Some(e.to_string()),
+            },
+        }
+    }
+}
+
+impl BluetoothSession {
+    /// Get a snapshot of everything known about the given device as JSON, for attaching to bug
+    /// reports or comparing between devices.
+    ///
+    /// This includes the device's properties, and if its services have been resolved then its
+    /// full GATT tree along with the current values of all readable characteristics and all
+    /// descriptors. Values are hex encoded. Errors getting the GATT tree or reading values are
+    /// included in the snapshot rather than returned.
+    pub async fn device_snapshot_json(&self, id: &DeviceId) -> Result<String, BluetoothError> {
+        let device = self.get_device_info(id).await?;
+        let (database, gatt_error) = if device.services_resolved {
+            match self.get_gatt_database(id).await {
+                Ok(database) => (database, None),
+                Err(e) => (GattDatabase::default(), Some(e.to_string())),
+            }
+        } else {
+            (GattDatabase::default(), None)
+        };
+
+        let mut gatt_services = vec![];
+        for service in database.services {
+            let mut characteristics = vec![];
+            for characteristic in service.characteristics {
+                let value = if characteristic.info.flags.is_readable() {
+                    self.read_characteristic_value(&characteristic.info.id)
+                        .await
+                        .into()
+                } else {
+                    ValueSnapshot::default()
+                };
+                let mut descriptors = vec![];
+                for descriptor in characteristic.descriptors {
+                    let value = self.read_descriptor_value(&descriptor.id).await.into();
+                    descriptors.push(DescriptorSnapshot {
+                        info: descriptor,
+                        value,
+                    });
+                }
+                characteristics.push(CharacteristicSnapshot {
+                    info: characteristic.info,
+                    value,
+                    descriptors,
+                });
+            }
+            gatt_services.push(ServiceSnapshot {
+                info: service.info,
+                characteristics,
+            });
+        }
+
+        let snapshot = DeviceSnapshot {
+            device,
+            gatt_services,
+            gatt_error,
+        };
+        Ok(serde_json::to_string_pretty(&snapshot)?)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::device::test_device_info;
+    use crate::{uuid_from_u16, CharacteristicFlags, CharacteristicId, ServiceId};
+    use std::collections::HashMap;
+
+    #[test]
+    fn snapshot_json() {
+        let mut manufacturer_data = HashMap::new();
+        manufacturer_data.insert(0x1234, vec![0x01, 0xab]);
+        let device = DeviceInfo {
+            name: Some("Name".to_string()),
+            connected: true,
+            rssi: Some(-50),
+            manufacturer_data,
+            services_resolved: true,
+            ..test_device_info()
+        };
+        let gatt_services = vec![ServiceSnapshot {
+            info: ServiceInfo {
+                id: ServiceId::new("/org/bluez/hci0/dev_11_22_33_44_55_66/service0001"),
+                uuid: uuid_from_u16(0x180f),
+                primary: true,
+            },
+            characteristics: vec![CharacteristicSnapshot {
+                info: CharacteristicInfo {
+                    id: CharacteristicId::new(
+                        "/org/bluez/hci0/dev_11_22_33_44_55_66/service0001/char0002",
+                    ),
+                    uuid: uuid_from_u16(0x2a19),
+                    flags: CharacteristicFlags::READ,
+                    handle: None,
+                    write_acquired: None,
+                    notify_acquired: None,
+                },
+                value: Ok(vec![0x64]).into(),
+                descriptors: vec![],
+            }],
+        }];
+
+        let json = serde_json::to_value(DeviceSnapshot {
+            device,
+            gatt_services,
+            gatt_error: None,
+        })
+        .unwrap();
+        assert_eq!(
+            json["id"]["object_path"],
+            "/org/bluez/hci0/dev_11_22_33_44_55_66"
+        );
+        assert_eq!(json["mac_address"], "11:22:33:44:55:66");
+        assert_eq!(json["address_type"], "public");
+        assert_eq!(json["manufacturer_data"]["4660"], "01ab");
+        assert_eq!(json["advertising_flags"], serde_json::Value::Null);
+        assert!(json.get("gatt_error").is_none());
+        let characteristic = &json["gatt_services"][0]["characteristics"][0];
+        assert_eq!(characteristic["value"], "64");
+        assert!(characteristic.get("read_error").is_none());
+    }
+
+    #[test]
+    fn gatt_error() {
+        let json = serde_json::to_value(DeviceSnapshot {
+            device: test_device_info(),
+            gatt_services: vec![],
+            gatt_error: Some(BluetoothError::Timeout.to_string()),
+        })
+        .unwrap();
+        assert_eq!(json["gatt_error"], "Timed out.");
+        assert_eq!(json["gatt_services"], serde_json::json!([]));
+    }
+
+    #[test]
+    fn read_error() {
+        let value: ValueSnapshot = Err(BluetoothError::Timeout).into();
+        assert_eq!(value.value, None);
+        assert_eq!(value.read_error, Some("Timed out.".to_string()));
+    }
+}
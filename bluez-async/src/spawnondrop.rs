@@ -0,0 +1,26 @@
+use std::fmt::Display;
+use std::future::Future;
+
+/// Spawn a task on the current Tokio runtime to do some cleanup for a value which is being
+/// dropped, such as disconnecting from a device or removing a D-Bus match.
+///
+/// As `Drop` can't be async, the cleanup may not have completed by the time `drop` returns, and
+/// any error is only logged. If there is no current runtime then the cleanup isn't done at all, and
+/// a warning is logged instead.
+///
+/// `action` describes the cleanup for log messages, e.g. "stopping discovery".
+pub(crate) fn spawn_on_drop<E: Display + Send + 'static>(
+    action: String,
+    cleanup: impl Future<Output = Result<(), E>> + Send + 'static,
+) {
+    match tokio::runtime::Handle::try_current() {
+        Ok(handle) => {
+            handle.spawn(async move {
+                if let Err(e) = cleanup.await {
+                    log::warn!("Error {}: {}", action, e);
+                }
+            });
+        }
+        Err(_) => log::warn!("Not {} as there is no runtime", action),
+    }
+}
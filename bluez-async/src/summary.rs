@@ -0,0 +1,109 @@
+use bluez_generated::{
+    OrgBluezDevice1Properties, ORG_BLUEZ_ADAPTER1_NAME, ORG_BLUEZ_GATT_CHARACTERISTIC1_NAME,
+    ORG_BLUEZ_GATT_DESCRIPTOR1_NAME, ORG_BLUEZ_GATT_SERVICE1_NAME,
+};
+use dbus::arg::PropMap;
+use dbus::Path;
+use std::collections::HashMap;
+
+/// Counts of the objects which BlueZ currently knows about, as returned by
+/// [`BluetoothSession::get_object_summary`](crate::BluetoothSession::get_object_summary).
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+pub struct ObjectSummary {
+    /// The number of Bluetooth adapters.
+    pub adapters: usize,
+    /// The total number of devices, whether connected or not.
+    pub devices: usize,
+    /// The number of devices which are currently connected.
+    pub connected_devices: usize,
+    /// The number of GATT services across all devices.
+    pub services: usize,
+    /// The number of GATT characteristics across all devices.
+    pub characteristics: usize,
+    /// The number of GATT descriptors across all devices.
+    pub descriptors: usize,
+}
+
+impl ObjectSummary {
+    /// Count the objects in the given tree, as returned by `GetManagedObjects`.
+    pub(crate) fn from_tree(tree: &HashMap<Path<'static>, HashMap<String, PropMap>>) -> Self {
+        let mut summary = Self::default();
+        for interfaces in tree.values() {
+            if interfaces.contains_key(ORG_BLUEZ_ADAPTER1_NAME) {
+                summary.adapters += 1;
+            }
+            if let Some(device_properties) = OrgBluezDevice1Properties::from_interfaces(interfaces)
+            {
+                summary.devices += 1;
+                if device_properties.connected() == Some(true) {
+                    summary.connected_devices += 1;
+                }
+            }
+            if interfaces.contains_key(ORG_BLUEZ_GATT_SERVICE1_NAME) {
+                summary.services += 1;
+            }
+            if interfaces.contains_key(ORG_BLUEZ_GATT_CHARACTERISTIC1_NAME) {
+                summary.characteristics += 1;
+            }
+            if interfaces.contains_key(ORG_BLUEZ_GATT_DESCRIPTOR1_NAME) {
+                summary.descriptors += 1;
+            }
+        }
+        summary
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use bluez_generated::ORG_BLUEZ_DEVICE1_NAME;
+    use dbus::arg::Variant;
+
+    fn object(interface: &str, properties: PropMap) -> HashMap<String, PropMap> {
+        let mut interfaces = HashMap::new();
+        interfaces.insert(interface.to_string(), properties);
+        interfaces
+    }
+
+    #[test]
+    fn count_objects() {
+        let mut connected = PropMap::new();
+        connected.insert("Connected".to_string(), Variant(Box::new(true)));
+        let mut disconnected = PropMap::new();
+        disconnected.insert("Connected".to_string(), Variant(Box::new(false)));
+
+        let mut tree = HashMap::new();
+        tree.insert(
+            "/org/bluez/hci0".into(),
+            object(ORG_BLUEZ_ADAPTER1_NAME, PropMap::new()),
+        );
+        tree.insert(
+            "/org/bluez/hci0/dev_11_22_33_44_55_66".into(),
+            object(ORG_BLUEZ_DEVICE1_NAME, connected),
+        );
+        tree.insert(
+            "/org/bluez/hci0/dev_66_55_44_33_22_11".into(),
+            object(ORG_BLUEZ_DEVICE1_NAME, disconnected),
+        );
+        tree.insert(
+            "/org/bluez/hci0/dev_11_22_33_44_55_66/service0001".into(),
+            object(ORG_BLUEZ_GATT_SERVICE1_NAME, PropMap::new()),
+        );
+        tree.insert(
+            "/org/bluez/hci0/dev_11_22_33_44_55_66/service0001/char0002".into(),
+            object(ORG_BLUEZ_GATT_CHARACTERISTIC1_NAME, PropMap::new()),
+        );
+
+        assert_eq!(
+            ObjectSummary::from_tree(&tree),
+            ObjectSummary {
+                adapters: 1,
+                devices: 2,
+                connected_devices: 1,
+                services: 1,
+                characteristics: 1,
+                descriptors: 0,
+            }
+        );
+    }
+}
@@ -0,0 +1,79 @@
+use dbus::Path;
+
+use crate::introspect::Node;
+
+/// A D-Bus object and its children, as returned by
+/// [`BluetoothSession::dump_tree`](crate::BluetoothSession::dump_tree).
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct TreeNode {
+    /// The object path of the object.
+    pub path: Path<'static>,
+    /// The names of the D-Bus interfaces which the object implements.
+    pub interfaces: Vec<String>,
+    /// The child objects which were introspected, if the depth limit wasn't reached.
+    pub children: Vec<TreeNode>,
+}
+
+impl TreeNode {
+    /// Construct a node for the object at the given path from its introspection data, without any
+    /// children.
+    pub(crate) fn from_node(path: Path<'static>, node: &Node) -> Self {
+        Self {
+            path,
+            interfaces: node
+                .interfaces
+                .iter()
+                .map(|interface| interface.name.to_owned())
+                .collect(),
+            children: vec![],
+        }
+    }
+
+    /// Get the paths of the children of the given introspected node.
+    pub(crate) fn child_paths(path: &Path, node: &Node) -> Vec<Path<'static>> {
+        let parent = path.trim_end_matches('/');
+        node.nodes
+            .iter()
+            .filter_map(|child| child.name.as_ref())
+            .map(|name| format!("{}/{}", parent, name).into())
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn from_introspection() {
+        let node: Node = serde_xml_rs::from_str(
+            r#"<node>
+                <interface name="org.freedesktop.DBus.Introspectable"/>
+                <interface name="org.bluez.AgentManager1"/>
+                <node name="hci0"/>
+                <node name="hci1"/>
+            </node>"#,
+        )
+        .unwrap();
+        let root: Path = "/org/bluez".into();
+        assert_eq!(
+            TreeNode::from_node(root.clone(), &node),
+            TreeNode {
+                path: root.clone(),
+                interfaces: vec![
+                    "org.freedesktop.DBus.Introspectable".to_string(),
+                    "org.bluez.AgentManager1".to_string()
+                ],
+                children: vec![],
+            }
+        );
+        assert_eq!(
+            TreeNode::child_paths(&root, &node),
+            vec![Path::from("/org/bluez/hci0"), Path::from("/org/bluez/hci1")]
+        );
+        assert_eq!(
+            TreeNode::child_paths(&"/".into(), &node),
+            vec![Path::from("/hci0"), Path::from("/hci1")]
+        );
+    }
+}
@@ -0,0 +1,199 @@
+use bluez_generated::{OrgBluezGattCharacteristic1Properties, ORG_BLUEZ_GATT_CHARACTERISTIC1_NAME};
+use dbus::message::{MatchRule, SignalArgs};
+use dbus::nonblock::stdintf::org_freedesktop_dbus::PropertiesPropertiesChanged;
+use dbus::strings::BusName;
+use dbus::Message;
+use std::collections::{HashMap, HashSet};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Mutex;
+
+use crate::{BluetoothEvent, CharacteristicEvent, CharacteristicId, DeviceEvent, DeviceId};
+
+/// Keeps track of the last value read or notified for each characteristic, and which
+/// characteristics are currently notifying.
+#[derive(Debug, Default)]
+pub(crate) struct ValueCache {
+    enabled: AtomicBool,
+    values: Mutex<HashMap<CharacteristicId, Vec<u8>>>,
+    notifying: Mutex<HashSet<CharacteristicId>>,
+}
+
+impl ValueCache {
+    /// Get the match rules for the signals which the cache needs to handle.
+    pub fn match_rules(service_name: &BusName<'static>) -> Vec<MatchRule<'static>> {
+        BluetoothEvent::match_rules(service_name, None::<DeviceId>)
+    }
+
+    /// Mark the cache as enabled. This should only be done once the D-Bus matches for the signals
+    /// which it handles have been added.
+    pub fn enable(&self) {
+        self.enabled.store(true, Ordering::SeqCst);
+    }
+
+    /// Whether the cache is enabled.
+    pub fn is_enabled(&self) -> bool {
+        self.enabled.load(Ordering::SeqCst)
+    }
+
+    /// Get the cached value of the given characteristic, if any.
+    pub fn get(&self, id: &CharacteristicId) -> Option<Vec<u8>> {
+        self.values.lock().unwrap().get(id).cloned()
+    }
+
+    /// Get the cached value of the given characteristic if it is notifying, so that the cached
+    /// value is up to date.
+    pub fn get_if_notifying(&self, id: &CharacteristicId) -> Option<Vec<u8>> {
+        if self.notifying.lock().unwrap().contains(id) {
+            self.get(id)
+        } else {
+            None
+        }
+    }
+
+    /// Store a value which has just been read, if the cache is enabled.
+    pub fn insert(&self, id: &CharacteristicId, value: &[u8]) {
+        if self.is_enabled() {
+            self.values
+                .lock()
+                .unwrap()
+                .insert(id.to_owned(), value.to_owned());
+        }
+    }
+
+    /// Update the cache based on the given D-Bus message.
+    pub fn handle_message(&self, message: Message) {
+        if !self.is_enabled() {
+            return;
+        }
+
+        if let Some(properties_changed) = PropertiesPropertiesChanged::from_message(&message) {
+            if properties_changed.interface_name == ORG_BLUEZ_GATT_CHARACTERISTIC1_NAME {
+                let properties =
+                    OrgBluezGattCharacteristic1Properties(&properties_changed.changed_properties);
+                if let (Some(notifying), Some(path)) = (properties.notifying(), message.path()) {
+                    let id = CharacteristicId::new(&path);
+                    let mut notifying_characteristics = self.notifying.lock().unwrap();
+                    if notifying {
+                        notifying_characteristics.insert(id);
+                    } else {
+                        notifying_characteristics.remove(&id);
+                    }
+                }
+            }
+        }
+
+        for event in BluetoothEvent::message_to_events(message, false) {
+            match event {
+                BluetoothEvent::Characteristic {
+                    id,
+                    event: CharacteristicEvent::Value { value },
+                } => {
+                    self.values.lock().unwrap().insert(id, value);
+                }
+                BluetoothEvent::Device {
+                    id,
+                    event: DeviceEvent::Connected { connected: false },
+                }
+                | BluetoothEvent::Device {
+                    id,
+                    event: DeviceEvent::Removed,
+                } => self.invalidate_device(&id),
+                _ => {}
+            }
+        }
+    }
+
+    /// Remove all cached state for characteristics of the given device.
+    fn invalidate_device(&self, device: &DeviceId) {
+        let is_on_device = |id: &CharacteristicId| id.service().device() == *device;
+        self.values
+            .lock()
+            .unwrap()
+            .retain(|id, _| !is_on_device(id));
+        self.notifying
+            .lock()
+            .unwrap()
+            .retain(|id| !is_on_device(id));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use dbus::arg::{PropMap, RefArg, Variant};
+
+    const CHARACTERISTIC_PATH: &str = "/org/bluez/hci0/dev_11_22_33_44_55_66/service0001/char0002";
+
+    fn properties_changed(
+        interface: &str,
+        path: &str,
+        name: &str,
+        value: Variant<Box<dyn RefArg>>,
+    ) -> Message {
+        let mut changed_properties = PropMap::new();
+        changed_properties.insert(name.to_string(), value);
+        PropertiesPropertiesChanged {
+            interface_name: interface.to_string(),
+            changed_properties,
+            invalidated_properties: vec![],
+        }
+        .to_emit_message(&path.into())
+    }
+
+    #[test]
+    fn disabled() {
+        let cache = ValueCache::default();
+        let id = CharacteristicId::new(CHARACTERISTIC_PATH);
+        cache.insert(&id, &[1, 2, 3]);
+        cache.handle_message(properties_changed(
+            "org.bluez.GattCharacteristic1",
+            CHARACTERISTIC_PATH,
+            "Value",
+            Variant(Box::new(vec![1u8, 2, 3])),
+        ));
+        assert_eq!(cache.get(&id), None);
+    }
+
+    #[test]
+    fn notified_value() {
+        let cache = ValueCache::default();
+        cache.enable();
+        let id = CharacteristicId::new(CHARACTERISTIC_PATH);
+        cache.handle_message(properties_changed(
+            "org.bluez.GattCharacteristic1",
+            CHARACTERISTIC_PATH,
+            "Value",
+            Variant(Box::new(vec![1u8, 2, 3])),
+        ));
+        assert_eq!(cache.get(&id), Some(vec![1, 2, 3]));
+        assert_eq!(cache.get_if_notifying(&id), None);
+
+        cache.handle_message(properties_changed(
+            "org.bluez.GattCharacteristic1",
+            CHARACTERISTIC_PATH,
+            "Notifying",
+            Variant(Box::new(true)),
+        ));
+        assert_eq!(cache.get_if_notifying(&id), Some(vec![1, 2, 3]));
+    }
+
+    #[test]
+    fn invalidate_on_disconnect() {
+        let cache = ValueCache::default();
+        cache.enable();
+        let id = CharacteristicId::new(CHARACTERISTIC_PATH);
+        let other_id =
+            CharacteristicId::new("/org/bluez/hci0/dev_AA_BB_CC_DD_EE_FF/service0001/char0002");
+        cache.insert(&id, &[1]);
+        cache.insert(&other_id, &[2]);
+
+        cache.handle_message(properties_changed(
+            "org.bluez.Device1",
+            "/org/bluez/hci0/dev_11_22_33_44_55_66",
+            "Connected",
+            Variant(Box::new(false)),
+        ));
+        assert_eq!(cache.get(&id), None);
+        assert_eq!(cache.get(&other_id), Some(vec![2]));
+    }
+}
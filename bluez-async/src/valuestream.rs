@@ -0,0 +1,64 @@
+use futures::stream::BoxStream;
+use futures::Stream;
+use std::fmt::{self, Debug, Formatter};
+use std::pin::Pin;
+use std::task::{Context, Poll};
+
+use crate::spawnondrop::spawn_on_drop;
+use crate::{BluetoothSession, CharacteristicId};
+
+/// A stream of decoded values from notifications of a GATT characteristic, returned by
+/// [`BluetoothSession::characteristic_value_stream`](crate::BluetoothSession::characteristic_value_stream),
+/// which stops notifications when it is dropped.
+///
+/// Notifications are stopped in the background, so they may still be enabled for a moment after the
+/// stream is dropped.
+#[must_use = "streams do nothing unless polled"]
+pub struct CharacteristicValueStream<T, E> {
+    session: BluetoothSession,
+    id: CharacteristicId,
+    values: BoxStream<'static, Result<T, E>>,
+}
+
+impl<T, E> CharacteristicValueStream<T, E> {
+    pub(crate) fn new(
+        session: BluetoothSession,
+        id: CharacteristicId,
+        values: BoxStream<'static, Result<T, E>>,
+    ) -> Self {
+        Self {
+            session,
+            id,
+            values,
+        }
+    }
+
+    /// Get the ID of the characteristic whose values are being streamed.
+    pub fn id(&self) -> &CharacteristicId {
+        &self.id
+    }
+}
+
+impl<T, E> Debug for CharacteristicValueStream<T, E> {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        write!(f, "CharacteristicValueStream({})", self.id)
+    }
+}
+
+impl<T, E> Stream for CharacteristicValueStream<T, E> {
+    type Item = Result<T, E>;
+
+    fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        self.values.as_mut().poll_next(cx)
+    }
+}
+
+impl<T, E> Drop for CharacteristicValueStream<T, E> {
+    fn drop(&mut self) {
+        let session = self.session.clone();
+        let id = self.id.clone();
+        spawn_on_drop(format!("stopping notifications for {}", id), async move {
+            session.stop_notify(&id).await
+        });
+    }
+}
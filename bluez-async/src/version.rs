@@ -0,0 +1,62 @@
+use bluez_generated::ORG_BLUEZ_ADAPTER1_NAME;
+use dbus::arg::PropMap;
+use std::collections::HashMap;
+
+/// The oldest BlueZ version which this crate expects to work with, used if no newer features are
+/// found.
+const MINIMUM_VERSION: (u16, u16) = (5, 0);
+
+/// Features which were added in particular BlueZ versions, as (version, interface, property). If
+/// the property is `None` then only the presence of the interface is checked.
+const VERSION_MARKERS: &[((u16, u16), &str, Option<&str>)] = &[
+    (
+        (5, 59),
+        ORG_BLUEZ_ADAPTER1_NAME,
+        Some("ExperimentalFeatures"),
+    ),
+    ((5, 56), "org.bluez.AdvertisementMonitorManager1", None),
+];
+
+/// Infer a lower bound for the BlueZ version from the interfaces and properties of an adapter
+/// object, by looking for features which were added in particular versions.
+pub(crate) fn infer_version(interfaces: &HashMap<String, PropMap>) -> (u16, u16) {
+    VERSION_MARKERS
+        .iter()
+        .filter(
+            |(_, interface, property)| match (interfaces.get(*interface), property) {
+                (Some(properties), Some(property)) => properties.contains_key(*property),
+                (Some(_), None) => true,
+                (None, _) => false,
+            },
+        )
+        .map(|(version, _, _)| *version)
+        .max()
+        .unwrap_or(MINIMUM_VERSION)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use dbus::arg::Variant;
+
+    #[test]
+    fn infer_from_features() {
+        let mut interfaces = HashMap::new();
+        interfaces.insert(ORG_BLUEZ_ADAPTER1_NAME.to_string(), PropMap::new());
+        assert_eq!(infer_version(&interfaces), MINIMUM_VERSION);
+
+        interfaces.insert(
+            "org.bluez.AdvertisementMonitorManager1".to_string(),
+            PropMap::new(),
+        );
+        assert_eq!(infer_version(&interfaces), (5, 56));
+
+        let mut adapter_properties = PropMap::new();
+        adapter_properties.insert(
+            "ExperimentalFeatures".to_string(),
+            Variant(Box::new(Vec::<String>::new())),
+        );
+        interfaces.insert(ORG_BLUEZ_ADAPTER1_NAME.to_string(), adapter_properties);
+        assert_eq!(infer_version(&interfaces), (5, 59));
+    }
+}
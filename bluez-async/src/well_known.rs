@@ -0,0 +1,91 @@
+//! UUIDs of some commonly used standard GATT services, characteristics and descriptors, as assigned
+//! by the Bluetooth SIG.
+//!
+//! Each service has its own module, containing the UUID of the service itself and of its
+//! characteristics.
+
+use uuid::Uuid;
+
+use crate::uuid_from_u16;
+
+/// The Generic Access service.
+pub mod generic_access {
+    use super::*;
+
+    pub const SERVICE: Uuid = uuid_from_u16(0x1800);
+    pub const DEVICE_NAME: Uuid = uuid_from_u16(0x2a00);
+    pub const APPEARANCE: Uuid = uuid_from_u16(0x2a01);
+}
+
+/// The Generic Attribute service.
+pub mod generic_attribute {
+    use super::*;
+
+    pub const SERVICE: Uuid = uuid_from_u16(0x1801);
+    pub const SERVICE_CHANGED: Uuid = uuid_from_u16(0x2a05);
+    pub const DATABASE_HASH: Uuid = uuid_from_u16(0x2b2a);
+}
+
+/// The Current Time service.
+pub mod current_time {
+    use super::*;
+
+    pub const SERVICE: Uuid = uuid_from_u16(0x1805);
+    pub const CURRENT_TIME: Uuid = uuid_from_u16(0x2a2b);
+    pub const LOCAL_TIME_INFORMATION: Uuid = uuid_from_u16(0x2a0f);
+}
+
+/// The Device Information service.
+pub mod device_information {
+    use super::*;
+
+    pub const SERVICE: Uuid = uuid_from_u16(0x180a);
+    pub const SYSTEM_ID: Uuid = uuid_from_u16(0x2a23);
+    pub const MODEL_NUMBER: Uuid = uuid_from_u16(0x2a24);
+    pub const SERIAL_NUMBER: Uuid = uuid_from_u16(0x2a25);
+    pub const FIRMWARE_REVISION: Uuid = uuid_from_u16(0x2a26);
+    pub const HARDWARE_REVISION: Uuid = uuid_from_u16(0x2a27);
+    pub const SOFTWARE_REVISION: Uuid = uuid_from_u16(0x2a28);
+    pub const MANUFACTURER_NAME: Uuid = uuid_from_u16(0x2a29);
+    pub const PNP_ID: Uuid = uuid_from_u16(0x2a50);
+}
+
+/// The Heart Rate service.
+pub mod heart_rate {
+    use super::*;
+
+    pub const SERVICE: Uuid = uuid_from_u16(0x180d);
+    pub const MEASUREMENT: Uuid = uuid_from_u16(0x2a37);
+    pub const BODY_SENSOR_LOCATION: Uuid = uuid_from_u16(0x2a38);
+    pub const CONTROL_POINT: Uuid = uuid_from_u16(0x2a39);
+}
+
+/// The Battery service.
+pub mod battery {
+    use super::*;
+
+    pub const SERVICE: Uuid = uuid_from_u16(0x180f);
+    pub const BATTERY_LEVEL: Uuid = uuid_from_u16(0x2a19);
+}
+
+/// The Environmental Sensing service.
+pub mod environmental_sensing {
+    use super::*;
+
+    pub const SERVICE: Uuid = uuid_from_u16(0x181a);
+    pub const PRESSURE: Uuid = uuid_from_u16(0x2a6d);
+    pub const TEMPERATURE: Uuid = uuid_from_u16(0x2a6e);
+    pub const HUMIDITY: Uuid = uuid_from_u16(0x2a6f);
+}
+
+/// Standard GATT descriptors, which may be found on characteristics of any service.
+pub mod descriptors {
+    use super::*;
+
+    pub const CHARACTERISTIC_EXTENDED_PROPERTIES: Uuid = uuid_from_u16(0x2900);
+    pub const CHARACTERISTIC_USER_DESCRIPTION: Uuid = uuid_from_u16(0x2901);
+    pub const CLIENT_CHARACTERISTIC_CONFIGURATION: Uuid = uuid_from_u16(0x2902);
+    pub const CHARACTERISTIC_PRESENTATION_FORMAT: Uuid = uuid_from_u16(0x2904);
+    pub const CHARACTERISTIC_AGGREGATE_FORMAT: Uuid = uuid_from_u16(0x2905);
+    pub const VALID_RANGE: Uuid = uuid_from_u16(0x2906);
+}
@@ -479,7 +479,7 @@ async fn handle_bluetooth_event(
     let homie = &mut state.homie;
     let sensors = &mut state.sensors;
     match event {
-        MijiaEvent::Readings { id, readings } => {
+        MijiaEvent::Readings { id, readings, .. } => {
             if let Some(sensor) = get_mut_sensor_by_id(sensors, &id) {
                 sensor
                     .publish_readings(homie, &readings, state.min_update_period)
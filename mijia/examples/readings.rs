@@ -41,7 +41,7 @@ async fn main() -> Result<(), Report> {
     println!("Readings:");
     while let Some(event) = events.next().await {
         match event {
-            MijiaEvent::Readings { id, readings } => {
+            MijiaEvent::Readings { id, readings, .. } => {
                 println!("{}: {}", id, readings);
             }
             _ => println!("Event: {:?}", event),
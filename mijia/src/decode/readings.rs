@@ -26,7 +26,30 @@ impl Display for Readings {
     }
 }
 
+/// Offsets to correct for a known bias in the readings of a particular sensor, set with
+/// [`MijiaSession::set_calibration`](crate::MijiaSession::set_calibration).
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct Calibration {
+    /// Offset in ºC to add to the temperature.
+    pub temperature_offset: f32,
+    /// Offset in percentage points to add to the humidity.
+    pub humidity_offset: f32,
+}
+
 impl Readings {
+    /// Apply the given calibration offsets to these readings. The humidity is rounded to the
+    /// nearest whole percent and clamped to the range 0-100%.
+    pub fn calibrated(&self, calibration: &Calibration) -> Readings {
+        let humidity = (self.humidity as f32 + calibration.humidity_offset)
+            .round()
+            .clamp(0.0, 100.0) as u8;
+        Readings {
+            temperature: self.temperature + calibration.temperature_offset,
+            humidity,
+            ..self.clone()
+        }
+    }
+
     /// Decode the readings from the raw bytes of the Bluetooth characteristic value, if they are
     /// valid.
     /// Returns `None` if the value is not valid.
@@ -97,4 +120,45 @@ mod tests {
             })
         );
     }
+
+    #[test]
+    fn calibrate() {
+        let readings = Readings {
+            temperature: 20.5,
+            humidity: 50,
+            battery_voltage: 2564,
+            battery_percent: 46,
+        };
+        assert_eq!(readings.calibrated(&Calibration::default()), readings);
+        assert_eq!(
+            readings.calibrated(&Calibration {
+                temperature_offset: -1.25,
+                humidity_offset: 2.6,
+            }),
+            Readings {
+                temperature: 19.25,
+                humidity: 53,
+                battery_voltage: 2564,
+                battery_percent: 46
+            }
+        );
+        assert_eq!(
+            readings
+                .calibrated(&Calibration {
+                    temperature_offset: 0.0,
+                    humidity_offset: 60.0,
+                })
+                .humidity,
+            100
+        );
+        assert_eq!(
+            readings
+                .calibrated(&Calibration {
+                    temperature_offset: 0.0,
+                    humidity_offset: -60.0,
+                })
+                .humidity,
+            0
+        );
+    }
 }
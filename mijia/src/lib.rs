@@ -13,7 +13,9 @@ use bluez_async::{
 };
 use core::future::Future;
 use futures::Stream;
+use std::collections::HashMap;
 use std::ops::Range;
+use std::sync::{Arc, Mutex};
 use std::time::{Duration, SystemTime};
 use thiserror::Error;
 use tokio::pin;
@@ -24,7 +26,7 @@ mod decode;
 pub use decode::comfort_level::ComfortLevel;
 use decode::history::decode_range;
 pub use decode::history::HistoryRecord;
-pub use decode::readings::Readings;
+pub use decode::readings::{Calibration, Readings};
 pub use decode::temperature_unit::TemperatureUnit;
 use decode::time::{decode_time, encode_time};
 pub use decode::{DecodeError, EncodeError};
@@ -54,6 +56,8 @@ const CONNECTION_INTERVAL_CHARACTERISTIC_UUID: Uuid =
 const CONNECTION_INTERVAL_500_MS: [u8; 3] = [0xF4, 0x01, 0x00];
 const HISTORY_DELETE_VALUE: [u8; 1] = [0x01];
 const HISTORY_RECORD_TIMEOUT: Duration = Duration::from_secs(2);
+/// How far the time read back from a sensor after setting it may be from the time which was set.
+const SET_TIME_TOLERANCE: Duration = Duration::from_secs(5);
 
 /// An error interacting with a Mijia sensor.
 #[derive(Debug, Error)]
@@ -67,6 +71,12 @@ pub enum MijiaError {
     /// The error was with encoding a value to send to a sensor.
     #[error(transparent)]
     Encoding(#[from] EncodeError),
+    /// The time read back from the sensor after setting it didn't match the time which was set.
+    #[error("Tried to set sensor time to {expected:?} but it reported {actual:?}")]
+    TimeNotSet {
+        expected: SystemTime,
+        actual: SystemTime,
+    },
 }
 
 /// The MAC address and opaque connection ID of a Mijia sensor which was discovered.
@@ -85,8 +95,14 @@ pub struct SensorProps {
 pub enum MijiaEvent {
     /// A new sensor has been discovered.
     Discovered { id: DeviceId },
-    /// A sensor has sent a new set of readings.
-    Readings { id: DeviceId, readings: Readings },
+    /// A sensor has sent a new set of readings. `readings` has any calibration set for the sensor
+    /// applied, while `raw_readings` are the values exactly as reported by the sensor.
+    #[non_exhaustive]
+    Readings {
+        id: DeviceId,
+        readings: Readings,
+        raw_readings: Readings,
+    },
     /// A sensor has sent a new historical record.
     HistoryRecord { id: DeviceId, record: HistoryRecord },
     /// The Bluetooth connection to a sensor has been lost.
@@ -94,7 +110,11 @@ pub enum MijiaEvent {
 }
 
 impl MijiaEvent {
-    async fn from(event: BluetoothEvent, session: BluetoothSession) -> Option<Self> {
+    async fn from(
+        event: BluetoothEvent,
+        session: BluetoothSession,
+        calibrations: Arc<Mutex<HashMap<DeviceId, Calibration>>>,
+    ) -> Option<Self> {
         match event {
             BluetoothEvent::Characteristic {
                 id: characteristic,
@@ -107,10 +127,20 @@ impl MijiaEvent {
                     .ok()?;
                 match info.uuid {
                     SENSOR_READING_CHARACTERISTIC_UUID => match Readings::decode(&value) {
-                        Ok(readings) => Some(MijiaEvent::Readings {
-                            id: characteristic.service().device(),
-                            readings,
-                        }),
+                        Ok(raw_readings) => {
+                            let id = characteristic.service().device();
+                            let calibration = calibrations
+                                .lock()
+                                .unwrap()
+                                .get(&id)
+                                .copied()
+                                .unwrap_or_default();
+                            Some(MijiaEvent::Readings {
+                                id,
+                                readings: raw_readings.calibrated(&calibration),
+                                raw_readings,
+                            })
+                        }
                         Err(e) => {
                             log::error!("Error decoding readings: {:?}", e);
                             None
@@ -189,6 +219,7 @@ pub struct MijiaSession {
     /// The underlying `BluetoothSession`. You can use this for Bluetooth operations which are not
     /// specific to Mijia sensors, such as connecting and disconnecting.
     pub bt_session: BluetoothSession,
+    calibrations: Arc<Mutex<HashMap<DeviceId, Calibration>>>,
 }
 
 impl MijiaSession {
@@ -198,7 +229,32 @@ impl MijiaSession {
     pub async fn new(
     ) -> Result<(impl Future<Output = Result<(), SpawnError>>, Self), BluetoothError> {
         let (handle, bt_session) = BluetoothSession::new().await?;
-        Ok((handle, MijiaSession { bt_session }))
+        Ok((handle, MijiaSession::from_bt_session(bt_session)))
+    }
+
+    /// Create a new `MijiaSession` using an existing `BluetoothSession`, with no calibrations set.
+    pub fn from_bt_session(bt_session: BluetoothSession) -> Self {
+        MijiaSession {
+            bt_session,
+            calibrations: Default::default(),
+        }
+    }
+
+    /// Set offsets to correct for a known bias in the temperature and humidity readings of the
+    /// given sensor. These are applied client-side to readings delivered by
+    /// `MijiaSession::event_stream()`; the sensor itself is not changed. Setting both offsets to 0
+    /// removes the calibration.
+    pub fn set_calibration(&self, id: &DeviceId, temp_offset: f32, humidity_offset: f32) {
+        let calibration = Calibration {
+            temperature_offset: temp_offset,
+            humidity_offset,
+        };
+        let mut calibrations = self.calibrations.lock().unwrap();
+        if calibration == Calibration::default() {
+            calibrations.remove(id);
+        } else {
+            calibrations.insert(id.to_owned(), calibration);
+        }
     }
 
     /// Get a list of all Mijia sensors which have currently been discovered.
@@ -240,17 +296,25 @@ impl MijiaSession {
         Ok(decode_time(&value)?)
     }
 
-    /// Set the current time of the sensor.
+    /// Set the current time of the sensor, and read it back to check that it was set correctly.
+    ///
+    /// The sensor's clock is reset when its battery is changed, so this should be called after
+    /// connecting if historical records are to have the correct timestamps.
     pub async fn set_time(&self, id: &DeviceId, time: SystemTime) -> Result<(), MijiaError> {
         let time_bytes = encode_time(time)?;
         let characteristic = self
             .bt_session
             .get_service_characteristic_by_uuid(id, SERVICE_UUID, CLOCK_CHARACTERISTIC_UUID)
             .await?;
-        Ok(self
-            .bt_session
+        self.bt_session
             .write_characteristic_value(&characteristic.id, time_bytes)
-            .await?)
+            .await?;
+        let value = self
+            .bt_session
+            .read_characteristic_value(&characteristic.id)
+            .await?;
+        let actual = decode_time(&value)?;
+        check_time_set(time, actual)
     }
 
     /// Get the temperature unit which the sensor uses for its display.
@@ -475,6 +539,26 @@ impl MijiaSession {
         Ok(history)
     }
 
+    /// Download all historical records stored on the sensor, e.g. to backfill a log after some
+    /// downtime.
+    ///
+    /// Unlike `get_all_history`, records which the sensor failed to send are left out rather than
+    /// returned as `None`, so the result may have fewer entries than `get_history_range`.
+    pub async fn download_history(&self, id: &DeviceId) -> Result<Vec<HistoryRecord>, MijiaError> {
+        let history = self.get_all_history(id).await?;
+        let expected = history.len();
+        let records: Vec<HistoryRecord> = history.into_iter().flatten().collect();
+        if records.len() < expected {
+            log::warn!(
+                "Only got {} of {} historical records from sensor {:?}",
+                records.len(),
+                expected,
+                id
+            );
+        }
+        Ok(records)
+    }
+
     /// Assuming that the given device ID refers to a Mijia sensor device and that it has already
     /// been connected, subscribe to notifications of temperature/humidity readings, and adjust the
     /// connection interval to save power.
@@ -509,14 +593,63 @@ impl MijiaSession {
     pub async fn event_stream(&self) -> Result<impl Stream<Item = MijiaEvent>, BluetoothError> {
         let events = self.bt_session.event_stream().await?;
         let session = self.bt_session.clone();
+        let calibrations = self.calibrations.clone();
         Ok(Box::pin(futures::stream::StreamExt::filter_map(
             events,
-            move |event| MijiaEvent::from(event, session.clone()),
+            move |event| MijiaEvent::from(event, session.clone(), calibrations.clone()),
         )))
     }
 }
 
+/// Check that the time read back from a sensor is close enough to the time which was set, allowing
+/// for the sensor's clock having ticked in between and the loss of sub-second precision.
+fn check_time_set(expected: SystemTime, actual: SystemTime) -> Result<(), MijiaError> {
+    let difference = match actual.duration_since(expected) {
+        Ok(difference) => difference,
+        Err(e) => e.duration(),
+    };
+    if difference <= SET_TIME_TOLERANCE {
+        Ok(())
+    } else {
+        Err(MijiaError::TimeNotSet { expected, actual })
+    }
+}
+
 /// Check whether the given Bluetooth device is a Mijia sensor which we support.
 fn is_mijia_sensor(device: &DeviceInfo) -> bool {
     device.name.as_deref() == Some(MIJIA_NAME)
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn time_set_within_tolerance() {
+        let expected = SystemTime::UNIX_EPOCH + Duration::from_secs(1_600_000_000);
+        assert!(check_time_set(expected, expected).is_ok());
+        assert!(check_time_set(expected, expected + Duration::from_secs(3)).is_ok());
+        assert!(check_time_set(expected, expected + SET_TIME_TOLERANCE).is_ok());
+    }
+
+    #[test]
+    fn time_set_beyond_tolerance() {
+        let expected = SystemTime::UNIX_EPOCH + Duration::from_secs(1_600_000_000);
+        let actual = expected + SET_TIME_TOLERANCE + Duration::from_secs(1);
+        assert!(matches!(
+            check_time_set(expected, actual),
+            Err(MijiaError::TimeNotSet { expected: e, actual: a }) if e == expected && a == actual
+        ));
+    }
+
+    #[test]
+    fn time_set_sensor_behind() {
+        let expected = SystemTime::UNIX_EPOCH + Duration::from_secs(1_600_000_000);
+        assert!(check_time_set(expected, expected - Duration::from_secs(1)).is_ok());
+        assert!(check_time_set(expected, expected - SET_TIME_TOLERANCE).is_ok());
+        assert!(matches!(
+            check_time_set(expected, expected - Duration::from_secs(10)),
+            Err(MijiaError::TimeNotSet { .. })
+        ));
+    }
+}